@@ -27,18 +27,9 @@ fn main() {
 
     // Get clock capabilities
     println!("\n=== Getting Clock Capabilities ===");
-    match device.get_caps() {
+    match device.caps() {
         Ok(caps) => {
-            println!("Clock capabilities:");
-            println!("  max_adj: {}", caps.max_adj);
-            println!("  n_alarm: {}", caps.n_alarm);
-            println!("  n_ext_ts: {}", caps.n_ext_ts);
-            println!("  n_per_out: {}", caps.n_per_out);
-            println!("  pps: {}", caps.pps);
-            println!("  n_pins: {}", caps.n_pins);
-            println!("  cross_timestamping: {}", caps.cross_timestamping);
-            println!("  adjust_phase: {}", caps.adjust_phase);
-            println!("  max_phase_adj: {}", caps.max_phase_adj);
+            println!("Clock capabilities:\n{caps}");
         }
         Err(e) => {
             eprintln!("Could not get capabilities: {}", e);
@@ -88,5 +79,20 @@ fn main() {
         }
     }
 
+    // Measure offset using the best available method
+    println!("\n=== Measuring Offset ===");
+    match device.measure_offset() {
+        Ok(m) => {
+            println!("Offset measurement ({:?}):", m.method);
+            println!("  PHC time: {}.{:09}", m.phc_time.sec, m.phc_time.nsec);
+            println!("  System time: {}.{:09}", m.sys_time.sec, m.sys_time.nsec);
+            println!("  Offset: {} ns", m.offset_ns);
+            println!("  Delay: {} ns", m.delay_ns);
+        }
+        Err(e) => {
+            eprintln!("Could not measure offset: {}", e);
+        }
+    }
+
     println!("\nDemo completed successfully!");
 }
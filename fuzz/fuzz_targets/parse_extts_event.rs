@@ -0,0 +1,14 @@
+//! Fuzz the decoding of external timestamp events read from a PTP device
+//!
+//! Run with `cargo +nightly fuzz run parse_extts_event` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(event) = ptp_time::parse_extts_event(data) {
+        assert_eq!(data.len(), std::mem::size_of_val(&event));
+        assert!(event.t.nsec < 1_000_000_000);
+    }
+});
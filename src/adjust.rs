@@ -0,0 +1,651 @@
+//! Adjustment of the PTP hardware clock through `clock_adjtime`
+
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Result},
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime},
+};
+
+use crate::{lock, ptp::ptp_clock_time, stats::estimate_frequency_ppb, PtpDevice, PtpError};
+
+/// Convert parts per billion to the `timex` frequency unit (ppm with a 16-bit fractional part)
+///
+/// The result is rounded to the nearest unit, about 0.015 ppb.
+pub fn ppb_to_scaled_ppm(ppb: f64) -> i64 {
+    (ppb * 65.536).round() as i64
+}
+
+/// Convert the `timex` frequency unit (ppm with a 16-bit fractional part) to parts per billion
+pub fn scaled_ppm_to_ppb(scaled_ppm: i64) -> f64 {
+    scaled_ppm as f64 / 65.536
+}
+
+/// The leap second state returned by `clock_adjtime`
+///
+/// The kernel's PTP core always reports `TIME_OK` for a PHC, since PHCs do not implement leap
+/// seconds themselves; other states are only seen if a future kernel or driver reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClockStatus {
+    /// `TIME_OK`: no leap second pending
+    Ok,
+    /// `TIME_INS`: a leap second will be inserted at the end of the UTC day
+    Insert,
+    /// `TIME_DEL`: a leap second will be deleted at the end of the UTC day
+    Delete,
+    /// `TIME_OOP`: a leap second is being inserted
+    InProgress,
+    /// `TIME_WAIT`: a leap second has just occurred
+    Wait,
+    /// `TIME_ERROR`: the clock is not synchronised
+    Error,
+    /// A state this crate does not know
+    Unknown(i32),
+}
+
+impl ClockStatus {
+    /// Decode a `clock_adjtime` return value
+    pub fn from_raw(state: i32) -> ClockStatus {
+        match state {
+            libc::TIME_OK => ClockStatus::Ok,
+            libc::TIME_INS => ClockStatus::Insert,
+            libc::TIME_DEL => ClockStatus::Delete,
+            libc::TIME_OOP => ClockStatus::InProgress,
+            libc::TIME_WAIT => ClockStatus::Wait,
+            libc::TIME_ERROR => ClockStatus::Error,
+            other => ClockStatus::Unknown(other),
+        }
+    }
+
+    /// Whether a leap second is scheduled for the end of the UTC day
+    pub fn leap_pending(&self) -> bool {
+        matches!(self, ClockStatus::Insert | ClockStatus::Delete)
+    }
+}
+
+/// The writable `STA_*` status bits of a clock, for [`PtpDevice::set_status_flags`]
+///
+/// The kernel also reports read-only bits such as `STA_NANO`, which are kept by
+/// [`StatusFlags::from_bits`] but have no constant here since they cannot be set.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatusFlags(i32);
+
+impl StatusFlags {
+    /// `STA_PLL`: phase-locked loop updates are enabled
+    pub const PLL: StatusFlags = StatusFlags(libc::STA_PLL);
+    /// `STA_PPSFREQ`: PPS frequency discipline is enabled
+    pub const PPS_FREQ: StatusFlags = StatusFlags(libc::STA_PPSFREQ);
+    /// `STA_PPSTIME`: PPS time discipline is enabled
+    pub const PPS_TIME: StatusFlags = StatusFlags(libc::STA_PPSTIME);
+    /// `STA_FLL`: frequency-locked rather than phase-locked mode
+    pub const FLL: StatusFlags = StatusFlags(libc::STA_FLL);
+    /// `STA_INS`: insert a leap second at the end of the UTC day
+    pub const INSERT_LEAP: StatusFlags = StatusFlags(libc::STA_INS);
+    /// `STA_DEL`: delete a leap second at the end of the UTC day
+    pub const DELETE_LEAP: StatusFlags = StatusFlags(libc::STA_DEL);
+    /// `STA_UNSYNC`: the clock is not synchronised
+    pub const UNSYNC: StatusFlags = StatusFlags(libc::STA_UNSYNC);
+    /// `STA_FREQHOLD`: hold the frequency rather than integrating corrections
+    pub const FREQ_HOLD: StatusFlags = StatusFlags(libc::STA_FREQHOLD);
+
+    /// Names of the known flags, for display
+    const NAMES: [(StatusFlags, &'static str); 8] = [
+        (StatusFlags::PLL, "pll"),
+        (StatusFlags::PPS_FREQ, "pps_freq"),
+        (StatusFlags::PPS_TIME, "pps_time"),
+        (StatusFlags::FLL, "fll"),
+        (StatusFlags::INSERT_LEAP, "insert_leap"),
+        (StatusFlags::DELETE_LEAP, "delete_leap"),
+        (StatusFlags::UNSYNC, "unsync"),
+        (StatusFlags::FREQ_HOLD, "freq_hold"),
+    ];
+
+    /// Wrap raw `STA_*` bits
+    pub fn from_bits(bits: i32) -> StatusFlags {
+        StatusFlags(bits)
+    }
+
+    /// The raw `STA_*` bits
+    pub fn bits(&self) -> i32 {
+        self.0
+    }
+
+    /// Whether every flag in `other` is set
+    pub fn contains(&self, other: StatusFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether no flags are set
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// These flags with those in `set` added and then those in `clear` removed
+    pub fn with(self, set: StatusFlags, clear: StatusFlags) -> StatusFlags {
+        StatusFlags((self.0 | set.0) & !clear.0)
+    }
+}
+
+impl std::ops::BitOr for StatusFlags {
+    type Output = StatusFlags;
+
+    fn bitor(self, rhs: StatusFlags) -> StatusFlags {
+        StatusFlags(self.0 | rhs.0)
+    }
+}
+
+/// Known flags are displayed as a comma-separated list of their names, or `none`
+impl fmt::Display for StatusFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (flag, name) in StatusFlags::NAMES {
+            if self.contains(flag) {
+                if !first {
+                    f.write_str(", ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        if first {
+            f.write_str("none")?;
+        }
+        Ok(())
+    }
+}
+
+/// Clock state as reported by `clock_adjtime`
+///
+/// The PTP core only fills in the frequency when reading a PHC's state; the other fields are
+/// reported as the kernel returns them, which for most PHCs is zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockState {
+    /// Current frequency adjustment in parts per billion
+    pub frequency_ppb: f64,
+    /// Maximum error in microseconds
+    pub maxerror_us: i64,
+    /// Estimated error in microseconds
+    pub esterror_us: i64,
+    /// Raw `STA_*` status bits
+    pub status: i32,
+    /// Leap second state, from the return value of `clock_adjtime`
+    pub clock_status: ClockStatus,
+}
+
+impl ClockState {
+    /// Typed view of the status bits
+    pub fn status_flags(&self) -> StatusFlags {
+        StatusFlags(self.status)
+    }
+}
+
+/// The leap second state of a clock, from [`PtpDevice::leap_status`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LeapStatus {
+    /// A leap second will be inserted at the end of the UTC day
+    pub pending_insert: bool,
+    /// A leap second will be deleted at the end of the UTC day
+    pub pending_delete: bool,
+    /// A leap second is being inserted now
+    pub in_progress: bool,
+    /// A leap second has just occurred, and the clock has not yet been told it is over
+    pub just_occurred: bool,
+}
+
+impl LeapStatus {
+    /// Whether any leap second is pending, in progress or just over, so that timestamps taken
+    /// now may be either side of a discontinuity
+    pub fn is_active(&self) -> bool {
+        self.pending_insert || self.pending_delete || self.in_progress || self.just_occurred
+    }
+}
+
+impl ClockState {
+    /// The leap second state, from both the `clock_adjtime` return value and the status bits
+    ///
+    /// A leap second is pending if either the state or `STA_INS`/`STA_DEL` says so, since the
+    /// PTP core reports `TIME_OK` for PHCs even when a daemon has set those bits.
+    pub fn leap_status(&self) -> LeapStatus {
+        let flags = self.status_flags();
+        LeapStatus {
+            pending_insert: self.clock_status == ClockStatus::Insert || flags.contains(StatusFlags::INSERT_LEAP),
+            pending_delete: self.clock_status == ClockStatus::Delete || flags.contains(StatusFlags::DELETE_LEAP),
+            in_progress: self.clock_status == ClockStatus::InProgress,
+            just_occurred: self.clock_status == ClockStatus::Wait,
+        }
+    }
+}
+
+/// The kind of clock adjustment described by an [`AdjustmentRecord`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdjustAction {
+    /// A frequency adjustment with [`PtpDevice::adjust_frequency`], in ppb
+    Slew,
+    /// A step with [`PtpDevice::step_clock`], in nanoseconds
+    Step,
+    /// A phase adjustment with [`PtpDevice::adjust_phase`], in nanoseconds
+    Phase,
+}
+
+/// A clock adjustment made by this crate, passed to the hook set with
+/// [`PtpDevice::set_adjustment_hook`]
+#[derive(Debug, Clone, Copy)]
+pub struct AdjustmentRecord {
+    /// When the adjustment was applied, by `CLOCK_REALTIME`
+    pub when: ptp_clock_time,
+    /// The offset from the last [`PtpDevice::measure_offset`] on this device, if there was one
+    pub measured_offset_ns: Option<i64>,
+    /// The kind of adjustment
+    pub action: AdjustAction,
+    /// The value applied: ppb for [`AdjustAction::Slew`], otherwise nanoseconds
+    pub applied_value: f64,
+}
+
+/// A callback receiving each [`AdjustmentRecord`]
+pub(crate) type AdjustmentHook = dyn Fn(&AdjustmentRecord) + Send + Sync;
+
+/// A zeroed `timex`, ready for the caller to set `modes` and the corresponding fields
+fn new_timex() -> libc::timex {
+    // Safety: timex is a plain C struct of integers, for which all zeroes is a valid value
+    unsafe { std::mem::zeroed() }
+}
+
+/// Restores a PHC's frequency adjustment when dropped
+struct FrequencyGuard<'a> {
+    device: &'a PtpDevice,
+    ppb: f64,
+}
+
+impl Drop for FrequencyGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.device.adjust_frequency(self.ppb) {
+            log_warn!("failed to restore frequency adjustment of {} ppb: {e}", self.ppb);
+        }
+    }
+}
+
+impl PtpDevice {
+    /// Validate and log clock adjustments instead of applying them, if `enabled`
+    ///
+    /// This lets a servo's control loop be exercised against real hardware without disturbing
+    /// it.  [`PtpDevice::adjust_frequency`], [`PtpDevice::step_clock`], [`PtpDevice::set_time`],
+    /// [`PtpDevice::adjust_phase`] and [`PtpDevice::set_error_bounds`] then check their arguments
+    /// the way the kernel's PTP core would, returning the same errors, but leave the clock alone.
+    /// Reading the clock state is unaffected.
+    pub fn set_dry_run(&self, enabled: bool) {
+        self.dry_run.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Call `hook` with a record of every frequency adjustment, step and phase adjustment applied
+    /// through this device, for an audit trail
+    ///
+    /// The hook runs on the adjusting thread after the adjustment succeeds, and replaces any
+    /// previous hook.  Adjustments which fail, and those only checked in dry-run mode, are not
+    /// recorded.
+    pub fn set_adjustment_hook<F: Fn(&AdjustmentRecord) + Send + Sync + 'static>(&self, hook: F) {
+        *lock(&self.adjustment_hook) = Some(Arc::new(hook));
+    }
+
+    /// Remove the hook set with [`PtpDevice::set_adjustment_hook`]
+    pub fn clear_adjustment_hook(&self) {
+        *lock(&self.adjustment_hook) = None;
+    }
+
+    /// Pass a record of an applied adjustment to the adjustment hook, if there is one
+    fn record_adjustment(&self, action: AdjustAction, applied_value: f64) {
+        // Clone the hook out so that it may itself use the device without deadlocking
+        let Some(hook) = lock(&self.adjustment_hook).clone() else {
+            return;
+        };
+        if self.dry_run() {
+            return;
+        }
+        let since_epoch = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        let last_offset = self.last_offset.load(Ordering::Relaxed);
+        hook(&AdjustmentRecord {
+            when: ptp_clock_time::from_duration(since_epoch),
+            measured_offset_ns: (last_offset != i64::MIN).then_some(last_offset),
+            action,
+            applied_value,
+        });
+    }
+
+    /// Whether dry-run mode is enabled; see [`PtpDevice::set_dry_run`]
+    pub fn dry_run(&self) -> bool {
+        self.dry_run.load(Ordering::Relaxed)
+    }
+
+    /// Check an adjustment as the kernel's PTP core would, without applying it
+    fn check_adjtime(&self, timex: &libc::timex) -> Result<i32> {
+        if timex.modes & libc::ADJ_SETOFFSET != 0 {
+            if timex.time.tv_usec < 0 || timex.time.tv_usec >= 1_000_000_000 {
+                return Err(Error::from_raw_os_error(libc::EINVAL));
+            }
+        } else if timex.modes & libc::ADJ_FREQUENCY != 0 {
+            if !self.can_adjust_frequency(scaled_ppm_to_ppb(timex.freq as _))? {
+                return Err(Error::from_raw_os_error(libc::ERANGE));
+            }
+        } else if timex.modes & libc::ADJ_OFFSET != 0 && self.cached_caps()?.adjust_phase != 0 {
+            if !self.can_adjust_phase(timex.offset as _)? {
+                return Err(Error::from_raw_os_error(libc::ERANGE));
+            }
+        } else {
+            return Err(Error::from_raw_os_error(libc::EOPNOTSUPP));
+        }
+        log_debug!(
+            "dry run: clock_adjtime modes {:#x} freq {} offset {} time {}.{:09}",
+            timex.modes,
+            timex.freq,
+            timex.offset,
+            timex.time.tv_sec,
+            timex.time.tv_usec
+        );
+        Ok(libc::TIME_OK)
+    }
+
+    /// Call `clock_adjtime` on this device's clock with `modes` and the fields of `timex`
+    ///
+    /// This is the raw system call, for mode combinations the other methods do not cover.
+    /// `timex.modes` is overwritten with `modes`, and the kernel writes the clock's current
+    /// state back into `timex`.  Returns the clock state, the `TIME_*` value which
+    /// [`ClockStatus::from_raw`] decodes.  Any modes other than 0 need the device to be opened
+    /// read-write, and are only checked, not applied, in [`dry-run`](PtpDevice::set_dry_run)
+    /// mode.  Note that the kernel's PTP core implements only some modes for PHCs and ignores or
+    /// rejects the rest.
+    pub fn adjtime(&self, modes: u32, timex: &mut libc::timex) -> Result<i32> {
+        timex.modes = modes as _;
+        self.clock_adjtime("adjtime", timex)
+    }
+
+    /// Call `clock_adjtime` on this device's clock for `operation`, returning the clock state
+    ///
+    /// Calls with non-zero modes fail with [`PtpError::ReadOnly`] on a read-only device before
+    /// reaching the kernel, which would only report `EACCES`.
+    fn clock_adjtime(&self, operation: &'static str, timex: &mut libc::timex) -> Result<i32> {
+        if timex.modes != 0 {
+            self.require_writable(operation)?;
+            if self.dry_run() {
+                return self.check_adjtime(timex);
+            }
+        }
+        let _span = trace_span!("ptp.clock_adjtime");
+        // Safety: timex is a valid, initialised timex which lives for the duration of the call
+        match unsafe { libc::clock_adjtime(self.clock_id(), timex) } {
+            -1 => Err(Error::last_os_error()),
+            state => Ok(state),
+        }
+    }
+
+    /// Adjust the frequency of the PHC by `ppb` parts per billion
+    ///
+    /// Positive values make the clock run faster.  The adjustment replaces any previous frequency
+    /// adjustment rather than adding to it.
+    pub fn adjust_frequency(&self, ppb: f64) -> Result<()> {
+        let mut timex = new_timex();
+        timex.modes = libc::ADJ_FREQUENCY;
+        timex.freq = ppb_to_scaled_ppm(ppb) as _;
+        self.clock_adjtime("adjust_frequency", &mut timex)?;
+        self.record_adjustment(AdjustAction::Slew, ppb);
+        Ok(())
+    }
+
+    /// Step the PHC by `offset_ns` nanoseconds
+    ///
+    /// Positive values move the clock forward.
+    pub fn step_clock(&self, offset_ns: i64) -> Result<()> {
+        let mut timex = new_timex();
+        timex.modes = libc::ADJ_SETOFFSET | libc::ADJ_NANO;
+        // With ADJ_NANO, tv_usec holds nanoseconds, which the kernel requires to be non-negative
+        timex.time.tv_sec = offset_ns.div_euclid(1_000_000_000) as _;
+        timex.time.tv_usec = offset_ns.rem_euclid(1_000_000_000) as _;
+        self.clock_adjtime("step_clock", &mut timex)?;
+        self.record_adjustment(AdjustAction::Step, offset_ns as f64);
+        Ok(())
+    }
+
+    /// Set the PHC to `time`
+    ///
+    /// This is `clock_settime` on the device's clock, which needs the device to be opened
+    /// read-write.  In [`dry-run`](PtpDevice::set_dry_run) mode the time is only checked.
+    pub fn set_time(&self, time: ptp_clock_time) -> Result<()> {
+        self.require_writable("set_time")?;
+        if time.sec < 0 || time.nsec >= 1_000_000_000 {
+            return Err(Error::from_raw_os_error(libc::EINVAL));
+        }
+        if self.dry_run() {
+            log_debug!("dry run: clock_settime {}.{:09}", time.sec, time.nsec);
+            return Ok(());
+        }
+        let ts = libc::timespec {
+            tv_sec: time.sec as _,
+            tv_nsec: time.nsec as _,
+        };
+        // Safety: clock_settime reads a timespec, which lives for the duration of the call
+        if unsafe { libc::clock_settime(self.clock_id(), &ts) } == -1 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Adjust the phase of the PHC by `offset_ns` nanoseconds
+    ///
+    /// Unlike [`PtpDevice::step_clock`], the driver slews the clock to absorb the offset.  Only
+    /// supported on clocks reporting `adjust_phase` in their capabilities.
+    pub fn adjust_phase(&self, offset_ns: i64) -> Result<()> {
+        let mut timex = new_timex();
+        timex.modes = libc::ADJ_OFFSET | libc::ADJ_NANO;
+        timex.offset = offset_ns as _;
+        self.clock_adjtime("adjust_phase", &mut timex)?;
+        self.record_adjustment(AdjustAction::Phase, offset_ns as f64);
+        Ok(())
+    }
+
+    /// Whether a frequency adjustment of `ppb` is within the clock's `max_adj` capability
+    ///
+    /// A servo can use this to decide between slewing and stepping without provoking `EINVAL`.
+    pub fn can_adjust_frequency(&self, ppb: f64) -> Result<bool> {
+        let caps = self.cached_caps()?;
+        Ok(ppb.abs() <= caps.max_adj as f64)
+    }
+
+    /// Whether a phase adjustment of `ns` nanoseconds is within the clock's capabilities
+    ///
+    /// This requires the clock to support phase adjustment and `ns` to be within `max_phase_adj`.
+    pub fn can_adjust_phase(&self, ns: i64) -> Result<bool> {
+        let caps = self.cached_caps()?;
+        Ok(caps.adjust_phase != 0 && ns.unsigned_abs() <= caps.max_phase_adj.unsigned_abs() as u64)
+    }
+
+    /// Read the clock's current adjustment state
+    pub fn read_clock_state(&self) -> Result<ClockState> {
+        let mut timex = new_timex();
+        let state = self.clock_adjtime("read_clock_state", &mut timex)?;
+        Ok(ClockState {
+            frequency_ppb: scaled_ppm_to_ppb(timex.freq as _),
+            maxerror_us: timex.maxerror as _,
+            esterror_us: timex.esterror as _,
+            status: timex.status,
+            clock_status: ClockStatus::from_raw(state),
+        })
+    }
+
+    /// The clock's leap second state; see [`ClockState::leap_status`]
+    pub fn leap_status(&self) -> Result<LeapStatus> {
+        self.read_clock_state().map(|state| state.leap_status())
+    }
+
+    /// The frequency adjustment currently applied to the PHC in parts per billion
+    ///
+    /// This is the value last set with [`PtpDevice::adjust_frequency`] by any process, so a servo
+    /// can resume from the existing correction after a restart.
+    pub fn current_frequency_ppb(&self) -> Result<f64> {
+        let mut timex = new_timex();
+        self.clock_adjtime("current_frequency_ppb", &mut timex)?;
+        Ok(scaled_ppm_to_ppb(timex.freq as _))
+    }
+
+    /// Whether something appears to be steering the PHC
+    ///
+    /// This is a best-effort heuristic: the kernel has no flag recording that a clock is
+    /// disciplined, or by whom.  The clock is considered disciplined if its frequency adjustment
+    /// is non-zero or its status has `STA_PLL` or `STA_FREQHOLD` set.  The PTP core currently
+    /// reports a PHC's status as always `TIME_OK` with no `STA_*` bits, so in practice only the
+    /// frequency counts.  A servo which happens to have converged on exactly zero, or which only
+    /// steps the clock, goes undetected; a frequency left behind by a servo which has since
+    /// exited is reported as disciplined.
+    pub fn is_disciplined(&self) -> Result<bool> {
+        let state = self.read_clock_state()?;
+        Ok(state.frequency_ppb != 0.0 || state.status & (libc::STA_PLL | libc::STA_FREQHOLD) != 0)
+    }
+
+    /// Set the status flags in `set` and clear those in `clear`, leaving the others unchanged
+    ///
+    /// The current status is read with [`PtpDevice::read_clock_state`], and the result written
+    /// back with `ADJ_STATUS`; flags in both `set` and `clear` end up cleared.  This needs the
+    /// device to be opened read-write.  Note that the kernel's PTP core does not currently
+    /// implement `ADJ_STATUS` for PHCs, in which case this fails with `EOPNOTSUPP`.
+    pub fn set_status_flags(&self, set: StatusFlags, clear: StatusFlags) -> Result<()> {
+        let status = self.read_clock_state()?.status_flags().with(set, clear);
+        let mut timex = new_timex();
+        timex.modes = libc::ADJ_STATUS;
+        timex.status = status.bits();
+        self.clock_adjtime("set_status_flags", &mut timex).map(|_| ())
+    }
+
+    /// Whether the clock's status has `STA_FREQHOLD` set, from [`PtpDevice::read_clock_state`]
+    ///
+    /// While it is set the kernel holds the frequency rather than integrating corrections, so a
+    /// servo should not expect its adjustments to accumulate.  As with
+    /// [`PtpDevice::is_disciplined`], the PTP core currently reports no `STA_*` bits for PHCs, so
+    /// this is `false` unless that changes.
+    pub fn frequency_held(&self) -> Result<bool> {
+        Ok(self.read_clock_state()?.status_flags().contains(StatusFlags::FREQ_HOLD))
+    }
+
+    /// Apply each frequency adjustment in `steps` and measure the rate the PHC then runs at
+    ///
+    /// For each step, the adjustment in ppb is applied, the clock is left for `dwell`, and the
+    /// rate of the PHC relative to the system clock is measured over a further `dwell`.  Returns
+    /// `(applied_ppb, measured_ppb)` pairs in the order of `steps`.  The measured rates include
+    /// the natural frequency difference between the two oscillators, so look at how they change
+    /// from step to step: they should track the applied values one for one, and stop doing so
+    /// beyond the hardware's true adjustment range.  The system clock must not be disciplined
+    /// during the sweep.  The original frequency adjustment is restored at the end, including
+    /// when a step fails.
+    pub fn frequency_response(&self, steps: &[f64], dwell: Duration) -> Result<Vec<(f64, f64)>> {
+        if dwell.is_zero() {
+            return Err(Error::new(ErrorKind::InvalidInput, "dwell time must be non-zero"));
+        }
+        let _guard = FrequencyGuard {
+            device: self,
+            ppb: self.current_frequency_ppb()?,
+        };
+        let mut response = Vec::with_capacity(steps.len());
+        for &ppb in steps {
+            self.adjust_frequency(ppb)?;
+            std::thread::sleep(dwell);
+            let start = self.measure_offset()?;
+            std::thread::sleep(dwell);
+            let end = self.measure_offset()?;
+            let measured = estimate_frequency_ppb(&start, &end)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "system clock did not advance during sweep"))?;
+            response.push((ppb, measured));
+        }
+        Ok(response)
+    }
+
+    /// Set the clock's maximum and estimated error, in microseconds as in `timex`
+    ///
+    /// These are informational values for monitoring tools and do not affect the clock.  Note that
+    /// the kernel's PTP core does not currently implement these modes for PHCs, in which case this
+    /// fails with `EOPNOTSUPP`.
+    pub fn set_error_bounds(&self, maxerror_us: i64, esterror_us: i64) -> Result<()> {
+        if maxerror_us < 0 || esterror_us < 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "error bounds must be non-negative"));
+        }
+        let mut timex = new_timex();
+        timex.modes = libc::ADJ_MAXERROR | libc::ADJ_ESTERROR;
+        timex.maxerror = maxerror_us as _;
+        timex.esterror = esterror_us as _;
+        self.clock_adjtime("set_error_bounds", &mut timex).map(|_| ())
+    }
+
+    /// Fail with [`PtpError::ReadOnly`] naming `operation` if the device was not opened for writing
+    pub(crate) fn require_writable(&self, operation: &'static str) -> Result<()> {
+        if !self.is_writable()? {
+            return Err(PtpError::ReadOnly { operation }.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[test]
+    fn scaled_ppm_round_trip() {
+        assert_eq!(ppb_to_scaled_ppm(1000.0), 65536);
+        assert_eq!(ppb_to_scaled_ppm(-500_000.0), -32_768_000);
+        assert_eq!(scaled_ppm_to_ppb(65536), 1000.0);
+        assert_eq!(scaled_ppm_to_ppb(ppb_to_scaled_ppm(-500_000.0)), -500_000.0);
+        // 1 ppb is not an exact number of units, so it rounds to 66 / 65.536 ppb
+        assert_eq!(ppb_to_scaled_ppm(1.0), 66);
+        assert!((scaled_ppm_to_ppb(ppb_to_scaled_ppm(1.0)) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn leap_status_combines_state_and_flags() {
+        let state = |clock_status, status| ClockState {
+            frequency_ppb: 0.0,
+            maxerror_us: 0,
+            esterror_us: 0,
+            status,
+            clock_status,
+        };
+        assert!(!state(ClockStatus::Ok, 0).leap_status().is_active());
+        let flagged = state(ClockStatus::Ok, libc::STA_INS).leap_status();
+        assert!(flagged.pending_insert && !flagged.pending_delete);
+        let status = state(ClockStatus::InProgress, 0).leap_status();
+        assert!(status.in_progress && status.is_active());
+        assert!(state(ClockStatus::Wait, 0).leap_status().just_occurred);
+    }
+
+    #[test]
+    fn status_flags_set_then_clear() {
+        let current = StatusFlags::UNSYNC | StatusFlags::PLL;
+        let updated = current.with(StatusFlags::FREQ_HOLD, StatusFlags::UNSYNC);
+        assert_eq!(updated, StatusFlags::PLL | StatusFlags::FREQ_HOLD);
+        assert_eq!(updated.to_string(), "pll, freq_hold");
+        assert_eq!(StatusFlags::default().to_string(), "none");
+    }
+
+    #[test]
+    fn adjustment_hook_receives_records() {
+        let device = null_device();
+        let records = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = records.clone();
+        device.set_adjustment_hook(move |record| sink.lock().unwrap().push(*record));
+
+        device.record_adjustment(AdjustAction::Step, -5.0);
+        device.last_offset.store(120, Ordering::Relaxed);
+        device.record_adjustment(AdjustAction::Slew, 1.5);
+        device.clear_adjustment_hook();
+        device.record_adjustment(AdjustAction::Phase, 1.0);
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[0].action, records[0].measured_offset_ns), (AdjustAction::Step, None));
+        assert_eq!((records[1].action, records[1].measured_offset_ns), (AdjustAction::Slew, Some(120)));
+        assert_eq!(records[1].applied_value, 1.5);
+    }
+
+    #[test]
+    fn read_only_device_rejects_set_time() {
+        let device = null_device();
+        let e = device.set_time(ptp_clock_time::default()).unwrap_err();
+        assert!(matches!(PtpError::from_io_error(&e), Some(PtpError::ReadOnly { operation: "set_time" })));
+        assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+    }
+}
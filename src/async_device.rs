@@ -0,0 +1,66 @@
+//! Async counterparts of the blocking waits of [`PtpDevice`], driven by `tokio::time`
+
+use std::{io::Result, time::Duration};
+
+use tokio::time::{self, Instant};
+
+use crate::{device::SyncPoll, PtpDevice};
+
+/// A [`PtpDevice`] whose waits sleep on the tokio timer instead of blocking a runtime thread
+///
+/// Only the waiting is async: each clock read or ioctl is still a short system call made on the
+/// calling task.  Use [`AsyncPtpDevice::get_ref`] for everything which does not wait.
+pub struct AsyncPtpDevice {
+    device: PtpDevice,
+}
+
+impl AsyncPtpDevice {
+    /// Wrap an open device
+    pub fn new(device: PtpDevice) -> AsyncPtpDevice {
+        AsyncPtpDevice { device }
+    }
+
+    /// The wrapped device
+    pub fn get_ref(&self) -> &PtpDevice {
+        &self.device
+    }
+
+    /// Unwrap the device
+    pub fn into_inner(self) -> PtpDevice {
+        self.device
+    }
+
+    /// Wait until the PHC is within `threshold_ns` of the system clock, for up to `timeout`
+    ///
+    /// The async version of [`PtpDevice::wait_until_synced`], with the same convergence test and
+    /// polling interval; the polling sleeps and the deadline use `tokio::time`, so this must be
+    /// called within a tokio runtime with the timer enabled.  A failed measurement is returned as
+    /// an error as soon as it occurs.
+    pub async fn wait_until_synced(&self, threshold_ns: i64, timeout: Duration) -> Result<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.device.poll_synced(threshold_ns, deadline.into_std())? {
+                SyncPoll::Done(synced) => return Ok(synced),
+                SyncPoll::Wait(delay) => time::sleep_until((Instant::now() + delay).min(deadline)).await,
+            }
+        }
+    }
+}
+
+impl From<PtpDevice> for AsyncPtpDevice {
+    fn from(device: PtpDevice) -> AsyncPtpDevice {
+        AsyncPtpDevice::new(device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[tokio::test]
+    async fn measurement_errors_end_the_wait() {
+        let device = AsyncPtpDevice::new(null_device());
+        assert!(device.wait_until_synced(1_000, Duration::from_secs(1)).await.is_err());
+    }
+}
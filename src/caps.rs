@@ -0,0 +1,271 @@
+//! Helpers for PTP clock capabilities
+
+use std::{fmt, io::Result};
+
+use crate::{ptp::*, PtpDevice, PtpError};
+
+/// A count reported by the kernel as a C int, where negative values are meaningless
+fn count(n: i32) -> u32 {
+    n.max(0) as u32
+}
+
+/// More channels, pins or alarms than any driver registers, so a larger count means the fields
+/// were misread
+const MAX_PLAUSIBLE_COUNT: i32 = 1024;
+
+/// Check that `caps` holds values a driver could have reported, as laid out by this crate
+fn check_caps(caps: &ptp_clock_caps) -> std::result::Result<(), PtpError> {
+    let counts = [
+        ("n_alarm", caps.n_alarm),
+        ("n_ext_ts", caps.n_ext_ts),
+        ("n_per_out", caps.n_per_out),
+        ("n_pins", caps.n_pins),
+    ];
+    let flags = [
+        ("pps", caps.pps),
+        ("cross_timestamping", caps.cross_timestamping),
+        ("adjust_phase", caps.adjust_phase),
+    ];
+    let limits = [("max_adj", caps.max_adj), ("max_phase_adj", caps.max_phase_adj)];
+    let detail = if let Some((name, n)) = counts.iter().find(|(_, n)| !(0..=MAX_PLAUSIBLE_COUNT).contains(n)) {
+        format!("PTP_CLOCK_GETCAPS reported {n} for {name}")
+    } else if let Some((name, n)) = flags.iter().find(|(_, n)| !(0..=1).contains(n)) {
+        format!("PTP_CLOCK_GETCAPS reported {n} for the flag {name}")
+    } else if let Some((name, n)) = limits.iter().find(|(_, n)| *n < 0) {
+        format!("PTP_CLOCK_GETCAPS reported a negative {name} of {n}")
+    } else {
+        return Ok(());
+    };
+    Err(PtpError::AbiMismatch { detail })
+}
+
+/// Boolean features of a PTP hardware clock, decoded from its capabilities
+///
+/// The kernel reports each feature as a separate integer; they are collected here as bits so
+/// that features added by later kernels can be tested for without new accessors.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CapFlags(u32);
+
+impl CapFlags {
+    /// The clock can report PPS events to the kernel's PPS subsystem
+    pub const PPS: CapFlags = CapFlags(1 << 0);
+    /// The clock supports the precise (cross-timestamp) offset ioctl
+    pub const CROSS_TIMESTAMPING: CapFlags = CapFlags(1 << 1);
+    /// The clock supports phase adjustment with `ADJ_OFFSET`
+    pub const ADJUST_PHASE: CapFlags = CapFlags(1 << 2);
+
+    /// Names of the known flags, for display
+    const NAMES: [(CapFlags, &'static str); 3] = [
+        (CapFlags::PPS, "pps"),
+        (CapFlags::CROSS_TIMESTAMPING, "cross_timestamping"),
+        (CapFlags::ADJUST_PHASE, "adjust_phase"),
+    ];
+
+    /// Wrap raw flag bits
+    pub fn from_bits(bits: u32) -> CapFlags {
+        CapFlags(bits)
+    }
+
+    /// The raw flag bits
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Whether every flag in `other` is set
+    pub fn contains(&self, other: CapFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether no flags are set
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl std::ops::BitOr for CapFlags {
+    type Output = CapFlags;
+
+    fn bitor(self, rhs: CapFlags) -> CapFlags {
+        CapFlags(self.0 | rhs.0)
+    }
+}
+
+/// Flags are displayed as a comma-separated list of their names, or `none`
+impl fmt::Display for CapFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("none");
+        }
+        let mut first = true;
+        for (flag, name) in CapFlags::NAMES {
+            if self.contains(flag) {
+                if !first {
+                    f.write_str(", ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The capabilities of a PTP hardware clock
+///
+/// With the `serde` feature this is serialized in the stable form described in the `wire` module.
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(into = "crate::wire::CapsV1"))]
+pub struct ClockCaps(ptp_clock_caps);
+
+impl ClockCaps {
+    /// Maximum frequency adjustment in parts per billion
+    pub fn max_adj_ppb(&self) -> i32 {
+        self.0.max_adj
+    }
+
+    /// Number of programmable alarms
+    pub fn n_alarm(&self) -> u32 {
+        count(self.0.n_alarm)
+    }
+
+    /// Number of external timestamp channels
+    pub fn n_ext_ts(&self) -> u32 {
+        count(self.0.n_ext_ts)
+    }
+
+    /// Number of periodic output channels
+    pub fn n_per_out(&self) -> u32 {
+        count(self.0.n_per_out)
+    }
+
+    /// Whether the clock can report PPS events to the kernel's PPS subsystem
+    pub fn pps(&self) -> bool {
+        self.0.pps != 0
+    }
+
+    /// Number of programmable pins
+    pub fn n_pins(&self) -> u32 {
+        count(self.0.n_pins)
+    }
+
+    /// Whether the clock supports the precise (cross-timestamp) offset ioctl
+    pub fn cross_timestamping(&self) -> bool {
+        self.0.cross_timestamping != 0
+    }
+
+    /// Whether the clock supports phase adjustment with `ADJ_OFFSET`
+    pub fn adjust_phase(&self) -> bool {
+        self.0.adjust_phase != 0
+    }
+
+    /// Maximum phase adjustment in nanoseconds
+    pub fn max_phase_adj_ns(&self) -> i32 {
+        self.0.max_phase_adj
+    }
+
+    /// The clock's boolean features as flags
+    ///
+    /// The individual accessors such as [`ClockCaps::pps`] and the raw fields remain available.
+    pub fn features(&self) -> CapFlags {
+        let mut flags = CapFlags::default();
+        for (set, flag) in [
+            (self.pps(), CapFlags::PPS),
+            (self.cross_timestamping(), CapFlags::CROSS_TIMESTAMPING),
+            (self.adjust_phase(), CapFlags::ADJUST_PHASE),
+        ] {
+            if set {
+                flags = flags | flag;
+            }
+        }
+        flags
+    }
+
+    /// The reserved words following `max_phase_adj`, which later kernels may assign meanings to
+    ///
+    /// The kernel zeroes these, so a non-zero word indicates a capability this crate predates.
+    pub fn reserved(&self) -> &[i32] {
+        &self.0.rsv
+    }
+
+    /// The underlying kernel structure
+    pub fn raw(&self) -> &ptp_clock_caps {
+        &self.0
+    }
+}
+
+/// Capabilities are displayed as a multi-line report, one capability per line
+impl fmt::Display for ClockCaps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        writeln!(f, "max frequency adjustment: {} ppb", self.max_adj_ppb())?;
+        writeln!(f, "max phase adjustment: {} ns", self.max_phase_adj_ns())?;
+        writeln!(f, "alarms: {}", self.n_alarm())?;
+        writeln!(f, "external timestamp channels: {}", self.n_ext_ts())?;
+        writeln!(f, "periodic output channels: {}", self.n_per_out())?;
+        writeln!(f, "programmable pins: {}", self.n_pins())?;
+        writeln!(f, "pps: {}", yes_no(self.pps()))?;
+        writeln!(f, "cross timestamping: {}", yes_no(self.cross_timestamping()))?;
+        write!(f, "adjust phase: {}", yes_no(self.adjust_phase()))
+    }
+}
+
+impl From<ptp_clock_caps> for ClockCaps {
+    fn from(caps: ptp_clock_caps) -> ClockCaps {
+        ClockCaps(caps)
+    }
+}
+
+impl From<ClockCaps> for ptp_clock_caps {
+    fn from(caps: ClockCaps) -> ptp_clock_caps {
+        caps.0
+    }
+}
+
+impl PtpDevice {
+    /// The clock's capabilities
+    ///
+    /// These are read from the device once and cached; use [`PtpDevice::get_caps`] for the raw
+    /// kernel structure read afresh.
+    pub fn caps(&self) -> Result<ClockCaps> {
+        self.cached_caps().map(ClockCaps)
+    }
+
+    /// Check that the running kernel agrees with this crate about the layout of its structures
+    ///
+    /// This reads the capabilities afresh and checks that every field holds a value a driver
+    /// could have reported: counts between 0 and 1024, flags of 0 or 1, and non-negative limits.
+    /// A kernel expecting a different layout from the one the crate's ioctl numbers encode would
+    /// fill the fields at other offsets and fail these checks.  Fails with
+    /// [`PtpError::AbiMismatch`] if a field is implausible, or the error of `PTP_CLOCK_GETCAPS`
+    /// if it fails.  [`OpenBuilder::check_abi`](crate::OpenBuilder::check_abi) runs this when
+    /// the device is opened.
+    pub fn check_abi(&self) -> Result<()> {
+        check_caps(&self.get_caps()?).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[test]
+    fn implausible_caps_are_an_abi_mismatch() {
+        let caps = ptp_clock_caps {
+            max_adj: 1_000_000_000,
+            n_ext_ts: 2,
+            n_pins: 4,
+            pps: 1,
+            ..Default::default()
+        };
+        assert!(check_caps(&caps).is_ok());
+        let shifted = ptp_clock_caps { cross_timestamping: 500_000, ..caps };
+        let e = std::io::Error::from(check_caps(&shifted).unwrap_err());
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains("cross_timestamping"), "{e}");
+
+        let device = null_device();
+        assert_eq!(PtpError::os_error(&device.check_abi().unwrap_err()), Some(libc::ENOTTY));
+    }
+}
@@ -0,0 +1,999 @@
+//! The PTP device handle and its core ioctls
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{Error, ErrorKind, Read, Result},
+    mem::{size_of, MaybeUninit},
+    os::{
+        fd::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+        raw::c_ulong,
+    },
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    adjust::AdjustmentHook, extts::parse_extts_event, ptp::*, Epoch, ExttsEvent, OffsetMeasurement, OffsetMethod,
+    PerOutConfig, PinFunction, PtpError, TimescaleMode,
+};
+
+// PTP ioctl constants - These are standard Linux PTP driver ioctls
+// Based on linux/ptp_clock.h: PTP_CLK_MAGIC = '=' = 0x3D
+
+// Correct ioctl values partially calculated from the header file and partially
+// worked out by stracing chrony.  Per asm-generic/ioctl.h, the top 2 bits are the
+// direction and the next 14 the size of the argument, so each constant only
+// matches the kernel's if the structure's size does; the tests check this.
+
+// _IOR(PTP_CLK_MAGIC, 1, struct ptp_clock_caps) -> 0x80503d01
+// _IOW(PTP_CLK_MAGIC, 5, struct ptp_sys_offset) -> 0x43403d05
+// _IOWR(PTP_CLK_MAGIC, 8, struct ptp_sys_offset_precise) -> 0xc0403d08
+// _IOWR(PTP_CLK_MAGIC, 9, struct ptp_sys_offset_extended) -> 0xc4c03d09
+// _IOW(PTP_CLK_MAGIC, 11, struct ptp_extts_request) -> 0x40103d0b
+// _IOW(PTP_CLK_MAGIC, 12, struct ptp_perout_request) -> 0x40383d0c
+// _IOWR(PTP_CLK_MAGIC, 15, struct ptp_pin_desc) -> 0xc0603d0f
+// _IOW(PTP_CLK_MAGIC, 16, struct ptp_pin_desc) -> 0x40603d10
+// _IOW(PTP_CLK_MAGIC, 13, int) -> 0x40043d0d
+
+// Which helper to use depends on the ioctl's direction.  Only _IOR ioctls, where the kernel
+// never reads the argument, may use ioctl_uninit.  _IOW and _IOWR ioctls read their argument,
+// so it must start zeroed: the offset structures' zeroed() constructors, otherwise a
+// `..Default::default()` structure, passed to ioctl_request.  PTP_ENABLE_PPS2 takes its int by
+// value and calls libc::ioctl directly.
+//
+//   PTP_CLOCK_GETCAPS        _IOR   ioctl_uninit
+//   PTP_SYS_OFFSET           _IOW   ioctl_request on zeroed() with n_samples
+//   PTP_SYS_OFFSET_PRECISE   _IOWR  ioctl_request on zeroed()
+//   PTP_SYS_OFFSET_EXTENDED  _IOWR  ioctl_request on zeroed() with n_samples
+//   PTP_EXTTS_REQUEST2       _IOW   ioctl_request
+//   PTP_PEROUT_REQUEST2      _IOW   ioctl_request
+//   PTP_PIN_GETFUNC2         _IOWR  ioctl_request
+//   PTP_PIN_SETFUNC2         _IOW   ioctl_request
+
+const PTP_CLOCK_GETCAPS: c_ulong = 0x80503d01; // _IOR(PTP_CLK_MAGIC, 1, struct ptp_clock_caps)
+const PTP_SYS_OFFSET: c_ulong = 0x43403d05;   // _IOW(PTP_CLK_MAGIC, 5, struct ptp_sys_offset)
+const PTP_SYS_OFFSET_PRECISE: c_ulong = 0xc0403d08; // _IOWR(PTP_CLK_MAGIC, 8, struct ptp_sys_offset_precise)
+const PTP_SYS_OFFSET_EXTENDED: c_ulong = 0xc4c03d09; // _IOWR(PTP_CLK_MAGIC, 9, struct ptp_sys_offset_extended)
+const PTP_EXTTS_REQUEST2: c_ulong = 0x40103d0b; // _IOW(PTP_CLK_MAGIC, 11, struct ptp_extts_request)
+pub(crate) const PTP_PEROUT_REQUEST2: c_ulong = 0x40383d0c; // _IOW(PTP_CLK_MAGIC, 12, struct ptp_perout_request)
+pub(crate) const PTP_PIN_GETFUNC2: c_ulong = 0xc0603d0f; // _IOWR(PTP_CLK_MAGIC, 15, struct ptp_pin_desc)
+pub(crate) const PTP_PIN_SETFUNC2: c_ulong = 0x40603d10; // _IOW(PTP_CLK_MAGIC, 16, struct ptp_pin_desc)
+pub(crate) const PTP_ENABLE_PPS2: c_ulong = 0x40043d0d; // _IOW(PTP_CLK_MAGIC, 13, int)
+
+/// Initial value of [`PtpDevice::default_samples`]
+const DEFAULT_SAMPLES: u32 = 10;
+
+/// Interval between offset measurements in [`PtpDevice::wait_until_synced`]
+const SYNC_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What [`PtpDevice::wait_until_synced`] should do after one measurement
+pub(crate) enum SyncPoll {
+    /// Stop waiting: `true` if the clock is synchronised, `false` if the deadline has passed
+    Done(bool),
+    /// Measure again after this delay
+    Wait(Duration),
+}
+
+/// The instant against which [`PtpDevice::time_since_last_read`] stores its timestamps
+static READ_EPOCH: OnceLock<Instant> = OnceLock::new();
+
+/// Delay before the first retry in [`PtpDevice::with_retry`], doubled for each later one
+const RETRY_INITIAL_BACKOFF: Duration = Duration::from_millis(1);
+
+/// Longest delay between retries in [`PtpDevice::with_retry`]
+const RETRY_MAX_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Whether an error is one which drivers return transiently, e.g. during a link flap
+fn is_transient(e: &Error) -> bool {
+    matches!(
+        PtpError::os_error(e),
+        Some(libc::EINTR) | Some(libc::EAGAIN) | Some(libc::EBUSY)
+    )
+}
+
+/// Whether an error indicates that the driver does not implement an ioctl
+pub(crate) fn is_unsupported(e: &Error) -> bool {
+    matches!(PtpError::os_error(e), Some(libc::EOPNOTSUPP) | Some(libc::ENOTTY))
+}
+
+/// The error for a failed ioctl `name`, from the current `errno`
+pub(crate) fn ioctl_error(name: &'static str) -> Error {
+    PtpError::Ioctl {
+        name,
+        source: Error::last_os_error(),
+    }
+    .into()
+}
+
+/// Check that a requested sample count is one the kernel will accept
+fn check_samples(n: u32) -> Result<()> {
+    if n == 0 || n > PTP_MAX_SAMPLES {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("sample count must be between 1 and {PTP_MAX_SAMPLES}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Fail with `ErrorKind::InvalidData` if the kernel filled in fewer samples than requested
+fn check_delivered(name: &str, requested: u32, delivered: u32) -> Result<()> {
+    if delivered < requested {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{name} delivered {delivered} of {requested} samples"),
+        ));
+    }
+    Ok(())
+}
+
+/// A request structure whose reserved words the kernel rejects unless they are zero
+pub(crate) trait Reserved {
+    /// Zero the reserved words
+    fn clear_reserved(&mut self);
+
+    /// Whether the reserved words are all zero
+    fn reserved_is_zero(&self) -> bool;
+}
+
+impl Reserved for ptp_extts_request {
+    fn clear_reserved(&mut self) {
+        self.rsv = [0; 2];
+    }
+
+    fn reserved_is_zero(&self) -> bool {
+        self.rsv == [0; 2]
+    }
+}
+
+/// The reserved words share a union with `on`, so only count while `PTP_PEROUT_DUTY_CYCLE` is clear
+impl Reserved for ptp_perout_request {
+    fn clear_reserved(&mut self) {
+        if self.flags & PTP_PEROUT_DUTY_CYCLE == 0 {
+            self.__bindgen_anon_2.rsv = [0; 4];
+        }
+    }
+
+    fn reserved_is_zero(&self) -> bool {
+        // Safety: every bit pattern is a valid [c_uint; 4]
+        self.flags & PTP_PEROUT_DUTY_CYCLE != 0 || unsafe { self.__bindgen_anon_2.rsv } == [0; 4]
+    }
+}
+
+impl Reserved for ptp_pin_desc {
+    fn clear_reserved(&mut self) {
+        self.rsv = [0; 5];
+    }
+
+    fn reserved_is_zero(&self) -> bool {
+        self.rsv == [0; 5]
+    }
+}
+
+impl Reserved for ptp_sys_offset {
+    fn clear_reserved(&mut self) {
+        self.rsv = [0; 3];
+    }
+
+    fn reserved_is_zero(&self) -> bool {
+        self.rsv == [0; 3]
+    }
+}
+
+impl Reserved for ptp_sys_offset_extended {
+    fn clear_reserved(&mut self) {
+        self.rsv = [0; 3];
+    }
+
+    fn reserved_is_zero(&self) -> bool {
+        self.rsv == [0; 3]
+    }
+}
+
+impl Reserved for ptp_sys_offset_precise {
+    fn clear_reserved(&mut self) {
+        self.rsv = [0; 4];
+    }
+
+    fn reserved_is_zero(&self) -> bool {
+        self.rsv == [0; 4]
+    }
+}
+
+/// Lock a mutex, ignoring poisoning since the protected state is always left consistent
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Hardware resources configured through a particular handle
+#[derive(Debug, Default)]
+pub(crate) struct Resources {
+    /// Enabled external timestamp channels
+    pub(crate) extts: BTreeSet<u32>,
+    /// Enabled periodic output channels and how they were configured
+    pub(crate) perout: BTreeMap<u32, PerOutConfig>,
+    /// Pins assigned a function other than `PinFunction::None`
+    pub(crate) pins: BTreeSet<u32>,
+    /// Whether PPS events were enabled
+    pub(crate) pps: bool,
+}
+
+/// The [`Resources`] of a [`PtpDevice`], released when it is dropped if cleanup is enabled
+///
+/// The cleanup lives in this field rather than in a `Drop` for the device itself, so that the
+/// device can still be taken apart by moving its fields out, as [`IntoRawFd`] does.  It derefs
+/// to the resources' mutex.
+#[derive(Debug, Default)]
+pub(crate) struct ResourceGuard {
+    resources: Mutex<Resources>,
+    /// The device's descriptor while cleanup on drop is enabled, owned by the device's `file`
+    cleanup_fd: Option<RawFd>,
+}
+
+impl std::ops::Deref for ResourceGuard {
+    type Target = Mutex<Resources>;
+
+    fn deref(&self) -> &Mutex<Resources> {
+        &self.resources
+    }
+}
+
+impl Drop for ResourceGuard {
+    /// Release resources configured through the device, if enabled with
+    /// [`PtpDevice::configure_cleanup_on_drop`]
+    fn drop(&mut self) {
+        let Some(fd) = self.cleanup_fd else {
+            return;
+        };
+        // Safety: fd is owned by the device's file, which is declared after this guard and so is
+        // still open; the temporary device hands it back with into_raw_fd rather than closing it
+        let device = PtpDevice::from_file(unsafe { File::from_raw_fd(fd) });
+        *lock(&device.resources) = std::mem::take(&mut *lock(&self.resources));
+        device.release_resources();
+        let PtpDevice { file, .. } = device;
+        let _ = file.into_raw_fd();
+    }
+}
+
+/// A safe wrapper for PTP hardware clock devices
+///
+/// # Thread safety
+///
+/// `PtpDevice` is `Send` and `Sync`, so it can be shared between threads in an `Arc` and used
+/// from all of them at once.  Every method takes `&self`; the state it keeps between calls is
+/// held in atomics, `OnceLock`s and mutexes, never in `Cell` or `RefCell`.  Each call is a
+/// separate system call, so concurrent measurements do not corrupt each other, but they do
+/// compete for the device: the kernel serialises reads of some PHCs, so the delay of each
+/// measurement grows with the number of threads.  Operations made of several calls, such as
+/// configuring a pin and then enabling its channel, are not atomic with respect to other
+/// threads using the same pins or channels.  All threads reading events share one queue, so
+/// each event goes to whichever thread reads first.
+pub struct PtpDevice {
+    /// Declared before `file`, so that cleanup on drop runs while the descriptor is still open
+    pub(crate) resources: ResourceGuard,
+    pub(crate) file: File,
+    /// Capabilities, read on first use since they do not change while the device is open
+    pub(crate) caps: OnceLock<ptp_clock_caps>,
+    /// Whether clock adjustments are validated but not applied
+    pub(crate) dry_run: AtomicBool,
+    /// Sample count used by the offset methods which do not take one
+    pub(crate) default_samples: AtomicU32,
+    /// The timescale the device's times are on, set by the user
+    pub(crate) timescale: Mutex<TimescaleMode>,
+    /// The epoch the device's times count from, set by the user
+    pub(crate) epoch: Mutex<Epoch>,
+    /// The best offset method the driver supports, once found by [`PtpDevice::measure_offset`]
+    pub(crate) method: Mutex<Option<OffsetMethod>>,
+    /// Nanoseconds after [`READ_EPOCH`] of the last successful read, plus one; 0 if there was none
+    pub(crate) last_read: AtomicU64,
+    /// The offset of the last successful [`PtpDevice::measure_offset`], or `i64::MIN` if none
+    pub(crate) last_offset: AtomicI64,
+    /// Called after each clock adjustment, if set
+    pub(crate) adjustment_hook: Mutex<Option<Arc<AdjustmentHook>>>,
+    /// The frequency last applied by [`PtpDevice::apply_correction_limited`], which limits the next
+    pub(crate) limited_ppb: Mutex<Option<f64>>,
+}
+
+impl PtpDevice {
+    /// Wrap an open device file
+    pub(crate) fn from_file(file: File) -> PtpDevice {
+        PtpDevice {
+            resources: ResourceGuard::default(),
+            file,
+            caps: OnceLock::new(),
+            dry_run: AtomicBool::new(false),
+            default_samples: AtomicU32::new(DEFAULT_SAMPLES),
+            timescale: Mutex::new(TimescaleMode::default()),
+            epoch: Mutex::new(Epoch::default()),
+            method: Mutex::new(None),
+            last_read: AtomicU64::new(0),
+            last_offset: AtomicI64::new(i64::MIN),
+            adjustment_hook: Mutex::new(None),
+            limited_ppb: Mutex::new(None),
+        }
+    }
+
+    /// Create a new PTP device from a path, opened read-only
+    ///
+    /// Use [`PtpDevice::builder`] to open the device with other flags.
+    pub fn new(path: PathBuf) -> Result<PtpDevice> {
+        PtpDevice::builder().open(path)
+    }
+
+    /// Create a new PTP device from a path, checking that it really is a PTP hardware clock
+    ///
+    /// The capabilities are read at open time, so that a path to something other than a PHC
+    /// fails immediately with [`PtpError::NotPtpDevice`] rather than on first use.
+    pub fn new_validated(path: PathBuf) -> Result<PtpDevice> {
+        PtpDevice::new(path.clone())?.validated(path)
+    }
+
+    /// Check that this device answers `PTP_CLOCK_GETCAPS`, reporting `path` if it does not
+    pub(crate) fn validated(self, path: PathBuf) -> Result<PtpDevice> {
+        match self.get_caps() {
+            Ok(_) => Ok(self),
+            Err(e) if matches!(PtpError::os_error(&e), Some(libc::ENOTTY) | Some(libc::EINVAL)) => {
+                Err(PtpError::NotPtpDevice { path }.into())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The dynamic POSIX clock id for this device, for use with `clock_gettime` and friends
+    pub(crate) fn clock_id(&self) -> libc::clockid_t {
+        // FD_TO_CLOCKID from the kernel's Documentation/ptp/testptp.c
+        ((!self.file.as_raw_fd()) << 3) | 3
+    }
+
+    /// Whether the device was opened for writing
+    pub(crate) fn is_writable(&self) -> Result<bool> {
+        // Safety: F_GETFL takes no argument and the fd is valid for the lifetime of self
+        match unsafe { libc::fcntl(self.file.as_raw_fd(), libc::F_GETFL) } {
+            -1 => Err(Error::last_os_error()),
+            flags => Ok(flags & libc::O_ACCMODE != libc::O_RDONLY),
+        }
+    }
+
+    /// Perform ioctl request and check result for possible errors
+    ///
+    /// Failures are reported as [`PtpError::Ioctl`] carrying `name`, the ioctl's symbolic name.
+    /// The PTP ioctls never block, so they behave the same on a file opened with `O_NONBLOCK`; a
+    /// driver returning `EAGAIN` is reported with `ErrorKind::WouldBlock` like any other errno.
+    pub(crate) unsafe fn ioctl<T>(&self, name: &'static str, request: c_ulong, value: &mut T) -> Result<()> {
+        match libc::ioctl(self.file.as_raw_fd(), request as _, value) {
+            0 => Ok(()),
+            _ => Err(ioctl_error(name)),
+        }
+    }
+
+    /// Perform an ioctl request which passes a structure to the kernel
+    ///
+    /// The structure must have been built with its reserved words zeroed; garbage in them makes
+    /// the kernel fail the request with `EINVAL`.
+    pub(crate) unsafe fn ioctl_request<T: Reserved>(
+        &self,
+        name: &'static str,
+        request: c_ulong,
+        value: &mut T,
+    ) -> Result<()> {
+        debug_assert!(value.reserved_is_zero(), "{name} request has non-zero reserved words");
+        self.ioctl(name, request, value)
+    }
+
+    /// Perform ioctl request with uninitialized memory
+    ///
+    /// Only for `_IOR` ioctls, which write their argument without reading it.
+    pub(crate) unsafe fn ioctl_uninit<T>(&self, name: &'static str, request: c_ulong) -> Result<T> {
+        let mut value: MaybeUninit<T> = MaybeUninit::uninit();
+        self.ioctl(name, request, &mut value)?;
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// Get the clock capabilities
+    pub fn get_caps(&self) -> Result<ptp_clock_caps> {
+        let _span = trace_span!("ptp.clock_getcaps");
+        // Safety: PTP_CLOCK_GETCAPS writes ptp_clock_caps, for which memory is allocated and returned by ioctl_uninit
+        unsafe { self.ioctl_uninit("PTP_CLOCK_GETCAPS", PTP_CLOCK_GETCAPS) }
+    }
+
+    /// Read the current time of the PHC
+    pub fn read_time(&self) -> Result<ptp_clock_time> {
+        let _span = trace_span!("ptp.read_time");
+        let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        // Safety: clock_gettime writes to a timespec, which lives for the duration of the call
+        if unsafe { libc::clock_gettime(self.clock_id(), &mut ts) } == -1 {
+            return Err(Error::last_os_error());
+        }
+        self.mark_read();
+        Ok(ptp_clock_time::from(ts))
+    }
+
+    /// Record that the device has just been read successfully
+    fn mark_read(&self) {
+        let since_epoch = READ_EPOCH.get_or_init(Instant::now).elapsed();
+        self.last_read.store(since_epoch.as_nanos() as u64 + 1, Ordering::Relaxed);
+    }
+
+    /// How long ago [`PtpDevice::read_time`] or [`PtpDevice::measure_offset`] last succeeded
+    ///
+    /// Returns `None` if neither has succeeded yet.  A supervisor can poll this to detect a
+    /// stalled measuring thread; recording the time costs one monotonic clock read, taken after
+    /// the measurement so it does not widen it.
+    pub fn time_since_last_read(&self) -> Option<Duration> {
+        let last = self.last_read.load(Ordering::Relaxed).checked_sub(1)?;
+        let since_epoch = READ_EPOCH.get_or_init(Instant::now).elapsed();
+        Some(since_epoch.saturating_sub(Duration::from_nanos(last)))
+    }
+
+    /// Read the device time together with a bound on how well it is tied to the system clock
+    ///
+    /// The time is the PHC reading of the least delayed sample of a `PTP_SYS_OFFSET` burst of
+    /// [`PtpDevice::default_samples`].  That reading was taken somewhere between the two system
+    /// clock readings bracketing it, so it belongs to their midpoint to within half the delay
+    /// between them, which is the uncertainty returned.  The uncertainty is never less than the
+    /// PHC's resolution as reported by `clock_getres`, since a reading cannot be more certain
+    /// than the clock's own granularity.
+    pub fn read_time_with_uncertainty(&self) -> Result<(ptp_clock_time, Duration)> {
+        let best = self
+            .get_sys_offset()?
+            .samples()
+            .min_by_key(|m| m.delay_ns)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "kernel returned no offset samples"))?;
+        let mut res = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        // Safety: clock_getres writes to a timespec, which lives for the duration of the call
+        if unsafe { libc::clock_getres(self.clock_id(), &mut res) } == -1 {
+            return Err(Error::last_os_error());
+        }
+        let resolution = Duration::new(res.tv_sec as u64, res.tv_nsec as u32);
+        let half_delay = Duration::from_nanos(best.half_delay_ns().max(0) as u64);
+        Ok((best.phc_time, half_delay.max(resolution)))
+    }
+
+    /// The next whole second of device time after the current time
+    ///
+    /// This is the natural start time for a 1PPS periodic output.
+    pub fn next_second(&self) -> Result<ptp_clock_time> {
+        let now = self.read_time()?;
+        Ok(ptp_clock_time {
+            sec: now.sec + 1,
+            nsec: 0,
+            reserved: 0,
+        })
+    }
+
+    /// How far the device time is into its current second
+    ///
+    /// This is the `nsec` part of [`PtpDevice::read_time`], for scheduling work at a given phase
+    /// of the PHC's seconds.
+    pub fn subsecond_phase(&self) -> Result<Duration> {
+        Ok(Duration::from_nanos(self.read_time()?.nsec.into()))
+    }
+
+    /// Get the clock capabilities, reading them from the device only on first use
+    pub(crate) fn cached_caps(&self) -> Result<ptp_clock_caps> {
+        if let Some(caps) = self.caps.get() {
+            return Ok(*caps);
+        }
+        let caps = self.get_caps()?;
+        Ok(*self.caps.get_or_init(|| caps))
+    }
+
+    /// The number of samples taken by offset methods called without an explicit count
+    ///
+    /// This is initially 10.
+    pub fn default_samples(&self) -> u32 {
+        self.default_samples.load(Ordering::Relaxed)
+    }
+
+    /// Set the number of samples taken by offset methods called without an explicit count
+    ///
+    /// This applies to [`PtpDevice::get_sys_offset`], [`PtpDevice::get_sys_offset_extended`] and
+    /// the sampled fallbacks of [`PtpDevice::measure_offset`].  Fails with
+    /// `ErrorKind::InvalidInput` unless `n` is between 1 and `PTP_MAX_SAMPLES` (25).
+    pub fn set_default_samples(&self, n: u32) -> Result<()> {
+        check_samples(n)?;
+        self.default_samples.store(n, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Get system offset measurements using [`PtpDevice::default_samples`] samples
+    pub fn get_sys_offset(&self) -> Result<ptp_sys_offset> {
+        self.get_sys_offset_n(self.default_samples())
+    }
+
+    /// Get precise system offset measurements
+    pub fn get_sys_offset_precise(&self) -> Result<ptp_sys_offset_precise> {
+        let span = trace_span!("ptp.sys_offset_precise", offset_ns, delay_ns);
+        let mut offset = ptp_sys_offset_precise::zeroed();
+        // Safety: PTP_SYS_OFFSET_PRECISE expects and writes to a ptp_sys_offset_precise, which lives for the duration of the call
+        unsafe { self.ioctl_request("PTP_SYS_OFFSET_PRECISE", PTP_SYS_OFFSET_PRECISE, &mut offset)? };
+        let m = offset.measurement();
+        span.record("offset_ns", m.offset_ns);
+        span.record("delay_ns", m.delay_ns);
+        Ok(offset)
+    }
+
+    /// The offset of the PHC from `CLOCK_REALTIME` in nanoseconds from one cross-timestamp
+    ///
+    /// This is the minimal measurement for a steady-state servo on cross-timestamping hardware:
+    /// a single `PTP_SYS_OFFSET_PRECISE` with no allocation, tracing or method probing.  Fails with
+    /// `ErrorKind::Unsupported` if the driver does not support the ioctl, so the caller can fall
+    /// back to [`PtpDevice::measure_offset`].
+    pub fn offset_ns_precise(&self) -> Result<i64> {
+        let mut offset = ptp_sys_offset_precise::zeroed();
+        // Safety: PTP_SYS_OFFSET_PRECISE expects and writes to a ptp_sys_offset_precise, which lives for the duration of the call
+        match unsafe { self.ioctl_request("PTP_SYS_OFFSET_PRECISE", PTP_SYS_OFFSET_PRECISE, &mut offset) } {
+            Ok(()) => Ok((offset.device.as_nanos() - offset.sys_realtime.as_nanos()) as i64),
+            Err(e) if is_unsupported(&e) => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("device does not support PTP_SYS_OFFSET_PRECISE ({e})"),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get extended system offset measurements using [`PtpDevice::default_samples`] samples
+    pub fn get_sys_offset_extended(&self) -> Result<ptp_sys_offset_extended> {
+        self.get_sys_offset_extended_n(self.default_samples())
+    }
+
+    /// Get system offset measurements using `n` samples
+    ///
+    /// Only the samples counted by the returned `n_samples` are valid; use
+    /// [`ptp_sys_offset::valid_samples`] or [`ptp_sys_offset::samples`] rather than `n`.
+    pub fn get_sys_offset_n(&self, n: u32) -> Result<ptp_sys_offset> {
+        let mut offset = ptp_sys_offset::zeroed();
+        self.get_sys_offset_into(&mut offset, n)?;
+        Ok(offset)
+    }
+
+    /// Take `n` samples with `PTP_SYS_OFFSET` into `buf`, reusing it rather than a fresh structure
+    ///
+    /// Only `n_samples` and the reserved words of `buf` are set before the ioctl; stale
+    /// timestamps beyond the returned `n_samples` are left in place, so read `buf` through
+    /// [`ptp_sys_offset::samples`] or [`ptp_sys_offset::valid_timestamps`].
+    pub fn get_sys_offset_into(&self, buf: &mut ptp_sys_offset, n: u32) -> Result<()> {
+        check_samples(n)?;
+        let span = trace_span!("ptp.sys_offset", n_samples);
+        buf.n_samples = n;
+        buf.clear_reserved();
+        // Safety: PTP_SYS_OFFSET expects and writes to a ptp_sys_offset, which lives for the duration of the call
+        unsafe { self.ioctl_request("PTP_SYS_OFFSET", PTP_SYS_OFFSET, buf)? };
+        span.record("n_samples", buf.n_samples);
+        if buf.n_samples != n {
+            log_debug!("PTP_SYS_OFFSET delivered {} of {n} samples", buf.n_samples);
+        }
+        Ok(())
+    }
+
+    /// Get system offset measurements using exactly `n` samples
+    ///
+    /// Like [`PtpDevice::get_sys_offset_n`], but fails with `ErrorKind::InvalidData` if the
+    /// kernel delivered fewer samples than requested, for callers which need an exact count.
+    pub fn get_sys_offset_n_strict(&self, n: u32) -> Result<ptp_sys_offset> {
+        let offset = self.get_sys_offset_n(n)?;
+        check_delivered("PTP_SYS_OFFSET", n, offset.n_samples)?;
+        Ok(offset)
+    }
+
+    /// Get extended system offset measurements using `n` samples
+    ///
+    /// Only the samples counted by the returned `n_samples` are valid; use
+    /// [`ptp_sys_offset_extended::valid_samples`] or [`ptp_sys_offset_extended::samples`] rather than `n`.
+    pub fn get_sys_offset_extended_n(&self, n: u32) -> Result<ptp_sys_offset_extended> {
+        check_samples(n)?;
+        let span = trace_span!("ptp.sys_offset_extended", n_samples);
+        let mut offset = ptp_sys_offset_extended {
+            n_samples: n,
+            ..ptp_sys_offset_extended::zeroed()
+        };
+        // Safety: PTP_SYS_OFFSET_EXTENDED expects and writes to a ptp_sys_offset_extended, which lives for the duration of the call
+        unsafe { self.ioctl_request("PTP_SYS_OFFSET_EXTENDED", PTP_SYS_OFFSET_EXTENDED, &mut offset)? };
+        span.record("n_samples", offset.n_samples);
+        if offset.n_samples != n {
+            log_debug!("PTP_SYS_OFFSET_EXTENDED delivered {} of {n} samples", offset.n_samples);
+        }
+        Ok(offset)
+    }
+
+    /// Get extended system offset measurements using exactly `n` samples
+    ///
+    /// Like [`PtpDevice::get_sys_offset_extended_n`], but fails with `ErrorKind::InvalidData` if
+    /// the kernel delivered fewer samples than requested.
+    pub fn get_sys_offset_extended_n_strict(&self, n: u32) -> Result<ptp_sys_offset_extended> {
+        let offset = self.get_sys_offset_extended_n(n)?;
+        check_delivered("PTP_SYS_OFFSET_EXTENDED", n, offset.n_samples)?;
+        Ok(offset)
+    }
+
+    /// Measure the offset between the PHC and the system clock using the best available method
+    ///
+    /// The precise (cross-timestamp) ioctl is tried first, falling back to the extended and then
+    /// the plain ioctl if the driver does not support it.  The method which works is remembered,
+    /// so later calls use it directly; see [`PtpDevice::preferred_method`].  For the sampled
+    /// methods, the sample with the smallest delay out of [`PtpDevice::default_samples`] is
+    /// returned.
+    pub fn measure_offset(&self) -> Result<OffsetMeasurement> {
+        let span = trace_span!("ptp.measure_offset", offset_ns, delay_ns, method);
+        let m = self.measure_offset_untraced()?;
+        self.mark_read();
+        self.last_offset.store(m.offset_ns, Ordering::Relaxed);
+        span.record("offset_ns", m.offset_ns);
+        span.record("delay_ns", m.delay_ns);
+        span.record("method", m.method.as_str());
+        Ok(m)
+    }
+
+    /// The offset of the PHC from the system clock in nanoseconds, using the best available method
+    ///
+    /// This is [`PtpDevice::measure_offset`] reduced to its `offset_ns`: PHC time minus system
+    /// time, positive when the PHC is ahead.
+    pub fn offset_ns(&self) -> Result<i64> {
+        self.measure_offset().map(|m| m.offset_ns)
+    }
+
+    /// The best offset method the driver supports: precise, then extended, then plain
+    ///
+    /// This is determined by the first call to [`PtpDevice::measure_offset`], or by taking a
+    /// measurement now if there has not been one, and then cached.
+    pub fn preferred_method(&self) -> Result<OffsetMethod> {
+        let cached = *lock(&self.method);
+        if let Some(method) = cached {
+            return Ok(method);
+        }
+        self.measure_offset_untraced().map(|m| m.method)
+    }
+
+    /// Forget the cached [`PtpDevice::preferred_method`], so the next measurement probes again
+    ///
+    /// This is only needed if the driver behind the file can change, e.g. after it is reloaded.
+    pub fn reset_method_cache(&self) {
+        *lock(&self.method) = None;
+    }
+
+    /// The body of [`PtpDevice::measure_offset`], separated so that its result can be traced
+    fn measure_offset_untraced(&self) -> Result<OffsetMeasurement> {
+        let cached = *lock(&self.method);
+        let best = match cached {
+            None => {
+                let m = self.probe_offset()?;
+                *lock(&self.method) = Some(m.method);
+                return Ok(m);
+            }
+            Some(OffsetMethod::Precise) => return Ok(self.get_sys_offset_precise()?.measurement()),
+            Some(OffsetMethod::Extended) => self.get_sys_offset_extended()?.samples().min_by_key(|m| m.delay_ns),
+            Some(OffsetMethod::Plain) => self.get_sys_offset()?.samples().min_by_key(|m| m.delay_ns),
+        };
+        best.ok_or_else(|| Error::new(ErrorKind::InvalidData, "kernel returned no offset samples"))
+    }
+
+    /// Measure the offset with each method in turn until one is supported
+    fn probe_offset(&self) -> Result<OffsetMeasurement> {
+        match self.get_sys_offset_precise() {
+            Ok(offset) => return Ok(offset.measurement()),
+            Err(e) if !is_unsupported(&e) => return Err(e),
+            Err(e) => log_debug!("precise offset unsupported ({e}), falling back to extended"),
+        }
+        let best = match self.get_sys_offset_extended() {
+            Ok(offset) => offset.samples().min_by_key(|m| m.delay_ns),
+            Err(e) if !is_unsupported(&e) => return Err(e),
+            Err(e) => {
+                log_debug!("extended offset unsupported ({e}), falling back to plain");
+                self.get_sys_offset()?
+                    .samples()
+                    .min_by_key(|m| m.delay_ns)
+            }
+        };
+        best.ok_or_else(|| Error::new(ErrorKind::InvalidData, "kernel returned no offset samples"))
+    }
+
+    /// Measure the frequency error of the system clock relative to the PHC in ppb
+    ///
+    /// Offsets are measured with [`PtpDevice::measure_offset`] before and after sleeping for
+    /// `duration`.  A positive result means the system clock runs fast.  The measurement error of
+    /// each offset is divided by the interval, so longer intervals give more accurate results;
+    /// the result is also meaningless if either clock is stepped during the interval.
+    pub fn measure_system_drift(&self, duration: Duration) -> Result<f64> {
+        if duration.is_zero() {
+            return Err(Error::new(ErrorKind::InvalidInput, "drift interval must be non-zero"));
+        }
+        let start = self.measure_offset()?;
+        std::thread::sleep(duration);
+        let end = self.measure_offset()?;
+        let phc_elapsed = (end.phc_time.as_nanos() - start.phc_time.as_nanos()) as f64;
+        if phc_elapsed <= 0.0 {
+            return Err(Error::new(ErrorKind::InvalidData, "PHC did not advance during drift measurement"));
+        }
+        // offset = phc - sys, so a system clock gaining on the PHC makes the offset fall
+        let offset_change = (end.offset_ns - start.offset_ns) as f64;
+        Ok(-offset_change / phc_elapsed * 1e9)
+    }
+
+    /// Call `op` with this device, retrying it if it fails with a transient error
+    ///
+    /// `EINTR`, `EAGAIN` and `EBUSY` are treated as transient: `op` is retried after 1ms, then
+    /// with the delay doubling up to 100ms, for at most `attempts` calls in total.  Any other
+    /// error, such as `EINVAL` or `ENOTTY`, is returned immediately, as is the last error once
+    /// the attempts are used up.  `op` is always called at least once.
+    pub fn with_retry<T>(&self, attempts: u32, op: impl Fn(&PtpDevice) -> Result<T>) -> Result<T> {
+        let mut backoff = RETRY_INITIAL_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match op(self) {
+                Err(e) if attempt < attempts && is_transient(&e) => {
+                    log_debug!("attempt {attempt} of {attempts} failed ({e}), retrying in {backoff:?}");
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(RETRY_MAX_BACKOFF);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Wait until the PHC is within `threshold_ns` of the system clock, for up to `timeout`
+    ///
+    /// The offset is measured every 250ms with [`PtpDevice::measure_offset`].  Returns `true` as
+    /// soon as its magnitude is below `threshold_ns`, or `false` if `timeout` expires first.  A
+    /// failed measurement is returned as an error rather than retried.
+    pub fn wait_until_synced(&self, threshold_ns: i64, timeout: Duration) -> Result<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.poll_synced(threshold_ns, deadline)? {
+                SyncPoll::Done(synced) => return Ok(synced),
+                SyncPoll::Wait(delay) => std::thread::sleep(delay),
+            }
+        }
+    }
+
+    /// One step of [`PtpDevice::wait_until_synced`], leaving the waiting to the caller
+    ///
+    /// This holds the convergence logic separately from the sleeping, so that
+    /// [`crate::AsyncPtpDevice::wait_until_synced`] can share it.
+    pub(crate) fn poll_synced(&self, threshold_ns: i64, deadline: Instant) -> Result<SyncPoll> {
+        if self.measure_offset()?.offset_ns.unsigned_abs() < threshold_ns.max(0) as u64 {
+            return Ok(SyncPoll::Done(true));
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(SyncPoll::Done(false));
+        }
+        Ok(SyncPoll::Wait(remaining.min(SYNC_POLL_INTERVAL)))
+    }
+
+    /// Whether the PHC advances by at least `min_advance` over an interval of `over`
+    ///
+    /// Reads the clock, sleeps for `over`, and reads it again.  A clock which has stopped, as can
+    /// happen after a NIC firmware crash, returns `false`.  A clock stepped backwards in between
+    /// also returns `false`.
+    pub fn is_alive(&self, min_advance: Duration, over: Duration) -> Result<bool> {
+        let start = self.read_time()?;
+        std::thread::sleep(over);
+        let end = self.read_time()?;
+        Ok(end.as_nanos() - start.as_nanos() >= min_advance.as_nanos() as i128)
+    }
+
+    /// Enable or disable an external timestamp channel
+    ///
+    /// `flags` is a combination of `PTP_ENABLE_FEATURE`, `PTP_RISING_EDGE`, `PTP_FALLING_EDGE` and
+    /// `PTP_STRICT_FLAGS`; the channel is disabled if `PTP_ENABLE_FEATURE` is not set.  With
+    /// `PTP_STRICT_FLAGS`, setting both edge flags captures both transitions, and a driver which
+    /// cannot honour the requested edges fails with `ErrorKind::Unsupported`.  Without it, drivers
+    /// may interpret the edge flags loosely.
+    pub fn enable_extts2(&self, index: u32, flags: u32) -> Result<()> {
+        let mut request = ptp_extts_request {
+            index,
+            flags,
+            ..Default::default()
+        };
+        let _span = trace_span!("ptp.extts_request");
+        // Safety: PTP_EXTTS_REQUEST2 reads a ptp_extts_request, which lives for the duration of the call
+        match unsafe { self.ioctl_request("PTP_EXTTS_REQUEST2", PTP_EXTTS_REQUEST2, &mut request) } {
+            Ok(()) => {}
+            Err(e)
+                if flags & PTP_STRICT_FLAGS != 0
+                    && flags & PTP_ENABLE_FEATURE != 0
+                    && matches!(PtpError::os_error(&e), Some(libc::EINVAL) | Some(libc::EOPNOTSUPP)) =>
+            {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("external timestamp channel {index} does not support the requested edges ({e})"),
+                ));
+            }
+            Err(e) => return Err(e),
+        }
+        let mut resources = lock(&self.resources);
+        if flags & PTP_ENABLE_FEATURE != 0 {
+            resources.extts.insert(index);
+        } else {
+            resources.extts.remove(&index);
+        }
+        Ok(())
+    }
+
+    /// Disable an external timestamp channel
+    pub fn disable_extts(&self, index: u32) -> Result<()> {
+        self.enable_extts2(index, 0)
+    }
+
+    /// External timestamp channels currently enabled through this handle, in ascending order
+    pub fn active_extts_channels(&self) -> Vec<u32> {
+        lock(&self.resources).extts.iter().copied().collect()
+    }
+
+    /// Disable every external timestamp channel enabled through this handle
+    ///
+    /// All channels are attempted even if some fail; the first error is returned.
+    pub fn disable_all_extts(&self) -> Result<()> {
+        let mut result = Ok(());
+        for index in self.active_extts_channels() {
+            if let Err(e) = self.disable_extts(index) {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    /// Release pins and disable channels configured through this handle when it is dropped
+    ///
+    /// This is off by default, so dropping a device leaves the hardware configured.  When enabled,
+    /// `Drop` disables any EXTTS and PEROUT channels enabled through this handle and returns the
+    /// pins it assigned to `PinFunction::None`.  Since `Drop` cannot return errors, any failures
+    /// during cleanup are ignored.
+    pub fn configure_cleanup_on_drop(&mut self, enabled: bool) {
+        self.resources.cleanup_fd = enabled.then(|| self.file.as_raw_fd());
+    }
+
+    /// Read the next external timestamp event, blocking until one is available
+    ///
+    /// Events from all enabled channels are delivered through the same file descriptor; use
+    /// [`ExttsEvent::channel`] to tell them apart and [`ExttsEvent::event_flags`] for the edge.  If
+    /// the device was opened non-blocking, this fails with `ErrorKind::WouldBlock` instead of
+    /// waiting when no event is queued.  Events which [`parse_extts_event`] rejects fail with
+    /// [`PtpError::AbiMismatch`].
+    pub fn read_extts_event(&self) -> Result<ExttsEvent> {
+        let _span = trace_span!("ptp.read_extts_event");
+        let mut buf = [0u8; size_of::<ptp_extts_event>()];
+        (&self.file).read_exact(&mut buf)?;
+        let event = parse_extts_event(&buf).ok_or_else(|| PtpError::AbiMismatch {
+            detail: "read an external timestamp event with nanoseconds out of range".to_string(),
+        })?;
+        Ok(event.into())
+    }
+
+    /// Disable the channels and reset the pins configured through this handle, for cleanup on drop
+    fn release_resources(&self) {
+        // Drop cannot report errors, so cleanup is best-effort
+        if let Err(e) = self.disable_all_extts() {
+            log_warn!("failed to disable external timestamp channels on drop: {e}");
+        }
+        if let Err(e) = self.disable_all_perout() {
+            log_warn!("failed to disable periodic outputs on drop: {e}");
+        }
+        if lock(&self.resources).pps {
+            if let Err(e) = self.enable_pps(false) {
+                log_warn!("failed to disable PPS on drop: {e}");
+            }
+        }
+        let pins: Vec<u32> = lock(&self.resources).pins.iter().copied().collect();
+        for index in pins {
+            match self.set_pin(index, PinFunction::None, 0) {
+                Ok(()) => log_debug!("reset pin {index} on drop"),
+                Err(e) => log_warn!("failed to reset pin {index} on drop: {e}"),
+            }
+        }
+    }
+}
+
+impl IntoRawFd for PtpDevice {
+    /// Give up ownership of the device file descriptor, e.g. to pass it to another process
+    ///
+    /// No cleanup is performed, whatever [`PtpDevice::configure_cleanup_on_drop`] was set to:
+    /// resources configured through this handle remain active for the new owner of the fd.
+    fn into_raw_fd(self) -> RawFd {
+        let PtpDevice { mut resources, file, .. } = self;
+        resources.cleanup_fd = None;
+        file.into_raw_fd()
+    }
+}
+
+impl FromRawFd for PtpDevice {
+    /// Take ownership of an open PTP device file descriptor, e.g. one received from another process
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open file descriptor for a PTP clock character device which is not owned
+    /// by anything else; it is closed when the returned `PtpDevice` is dropped.  Its close-on-exec
+    /// flag is left as it is; see [`PtpDevice::set_cloexec`].
+    unsafe fn from_raw_fd(fd: RawFd) -> PtpDevice {
+        // Safety: the caller guarantees fd is open and exclusively owned
+        PtpDevice::from_file(unsafe { File::from_raw_fd(fd) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[test]
+    fn device_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<PtpDevice>();
+    }
+
+    #[test]
+    fn retries_only_transient_errors() {
+        let device = null_device();
+        let calls = Cell::new(0);
+        let busy = |_: &PtpDevice| -> Result<()> {
+            calls.set(calls.get() + 1);
+            Err(Error::from_raw_os_error(libc::EBUSY))
+        };
+        assert!(device.with_retry(3, busy).is_err());
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let invalid = |_: &PtpDevice| -> Result<()> {
+            calls.set(calls.get() + 1);
+            Err(Error::from_raw_os_error(libc::EINVAL))
+        };
+        assert!(device.with_retry(3, invalid).is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn precise_offset_reports_unsupported() {
+        let device = null_device();
+        assert_eq!(device.offset_ns_precise().unwrap_err().kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn last_read_is_tracked() {
+        let device = null_device();
+        assert_eq!(device.time_since_last_read(), None);
+        device.mark_read();
+        assert!(device.time_since_last_read().unwrap() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn into_raw_fd_keeps_the_descriptor_open() {
+        let mut device = null_device();
+        device.configure_cleanup_on_drop(true);
+        lock(&device.resources).pps = true;
+        let fd = device.into_raw_fd();
+        // Safety: into_raw_fd gave up ownership of fd, so it is open and owned by nothing else
+        let device = unsafe { PtpDevice::from_raw_fd(fd) };
+        assert!(device.file.metadata().is_ok());
+    }
+
+    #[test]
+    fn clamped_sample_counts_are_detected() {
+        assert!(check_delivered("PTP_SYS_OFFSET", 10, 10).is_ok());
+        assert_eq!(
+            check_delivered("PTP_SYS_OFFSET", 10, 5).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn ioctl_sizes_match_structures() {
+        let encoded_size = |request: c_ulong| ((request >> 16) & 0x3fff) as usize;
+        assert_eq!(encoded_size(PTP_CLOCK_GETCAPS), std::mem::size_of::<ptp_clock_caps>());
+        assert_eq!(encoded_size(PTP_SYS_OFFSET), std::mem::size_of::<ptp_sys_offset>());
+        assert_eq!(encoded_size(PTP_SYS_OFFSET_PRECISE), std::mem::size_of::<ptp_sys_offset_precise>());
+        assert_eq!(encoded_size(PTP_SYS_OFFSET_EXTENDED), std::mem::size_of::<ptp_sys_offset_extended>());
+        assert_eq!(encoded_size(PTP_EXTTS_REQUEST2), std::mem::size_of::<ptp_extts_request>());
+        assert_eq!(encoded_size(PTP_PEROUT_REQUEST2), std::mem::size_of::<ptp_perout_request>());
+        assert_eq!(encoded_size(PTP_PIN_GETFUNC2), std::mem::size_of::<ptp_pin_desc>());
+        assert_eq!(encoded_size(PTP_PIN_SETFUNC2), std::mem::size_of::<ptp_pin_desc>());
+        assert_eq!(encoded_size(PTP_ENABLE_PPS2), std::mem::size_of::<libc::c_int>());
+    }
+}
@@ -0,0 +1,119 @@
+//! Errors specific to PTP devices
+//!
+//! All fallible operations in this crate return `std::io::Result`.  Where the crate can say more
+//! than the raw errno, the `io::Error` wraps a [`PtpError`] describing the problem, which can be
+//! recovered with [`PtpError::from_io_error`].  Failed ioctls are reported as [`PtpError::Ioctl`],
+//! so use [`PtpError::os_error`] rather than `io::Error::raw_os_error` to find their errno.
+
+use std::{fmt, io, path::PathBuf};
+
+/// A PTP-specific error, carried inside an `io::Error`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PtpError {
+    /// The device does not exist, usually because the PHC driver is not loaded
+    DeviceNotFound { path: PathBuf },
+    /// The caller is not allowed to open the device
+    PermissionDenied { path: PathBuf },
+    /// The device exists but is not a PTP hardware clock
+    NotPtpDevice { path: PathBuf },
+    /// An ioctl failed; `name` is its symbolic name, e.g. `PTP_SYS_OFFSET_PRECISE`
+    Ioctl { name: &'static str, source: io::Error },
+    /// `operation` changes the clock, which needs the device to be opened read-write
+    ReadOnly { operation: &'static str },
+    /// The kernel returned a structure which does not make sense with the layout this crate
+    /// assumes; `detail` says which field was implausible
+    AbiMismatch { detail: String },
+}
+
+impl PtpError {
+    /// The `io::ErrorKind` used when this error is converted to an `io::Error`
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            PtpError::DeviceNotFound { .. } => io::ErrorKind::NotFound,
+            PtpError::PermissionDenied { .. } => io::ErrorKind::PermissionDenied,
+            PtpError::NotPtpDevice { .. } => io::ErrorKind::Unsupported,
+            PtpError::Ioctl { source, .. } => source.kind(),
+            PtpError::ReadOnly { .. } => io::ErrorKind::PermissionDenied,
+            PtpError::AbiMismatch { .. } => io::ErrorKind::InvalidData,
+        }
+    }
+
+    /// The `PtpError` wrapped by an `io::Error` returned from this crate, if there is one
+    pub fn from_io_error(e: &io::Error) -> Option<&PtpError> {
+        e.get_ref()?.downcast_ref()
+    }
+
+    /// The OS error code behind an `io::Error` returned from this crate, if there is one
+    ///
+    /// Unlike `io::Error::raw_os_error`, this also finds the code of a failed ioctl, which is
+    /// wrapped in [`PtpError::Ioctl`] to report the ioctl's name.
+    pub fn os_error(e: &io::Error) -> Option<i32> {
+        match PtpError::from_io_error(e) {
+            Some(PtpError::Ioctl { source, .. }) => source.raw_os_error(),
+            _ => e.raw_os_error(),
+        }
+    }
+}
+
+impl fmt::Display for PtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PtpError::DeviceNotFound { path } => write!(
+                f,
+                "PTP device {} not found; check that the driver for the PHC is loaded",
+                path.display()
+            ),
+            PtpError::PermissionDenied { path } => write!(
+                f,
+                "permission denied opening PTP device {}; run as root or as a member of the group owning it",
+                path.display()
+            ),
+            PtpError::NotPtpDevice { path } => write!(f, "{} is not a PTP clock device", path.display()),
+            PtpError::Ioctl { name, source } => write!(f, "{name}: {source}"),
+            PtpError::ReadOnly { operation } => write!(
+                f,
+                "{operation} needs the PTP device opened read-write; open it with PtpDevice::builder().read_write(true)"
+            ),
+            PtpError::AbiMismatch { detail } => write!(
+                f,
+                "the kernel's PTP ABI does not match the one this crate was built for: {detail}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PtpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PtpError::Ioctl { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<PtpError> for io::Error {
+    fn from(e: PtpError) -> io::Error {
+        io::Error::new(e.kind(), e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ioctl_error_names_the_ioctl_and_keeps_errno() {
+        let e: io::Error = PtpError::Ioctl {
+            name: "PTP_SYS_OFFSET_PRECISE",
+            source: io::Error::from_raw_os_error(libc::ENOTTY),
+        }
+        .into();
+        assert_eq!(
+            e.to_string(),
+            "PTP_SYS_OFFSET_PRECISE: Inappropriate ioctl for device (os error 25)"
+        );
+        assert_eq!(PtpError::os_error(&e), Some(libc::ENOTTY));
+        assert_eq!(e.raw_os_error(), None);
+    }
+}
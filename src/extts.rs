@@ -0,0 +1,873 @@
+//! External timestamp (EXTTS) event helpers
+
+use std::{
+    collections::VecDeque,
+    io::{Error, ErrorKind, Result},
+    ops::ControlFlow,
+    os::fd::AsRawFd,
+    sync::atomic::{fence, AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{ptp::*, OffsetMeasurement, PtpDevice};
+
+/// How often [`PtpDevice::capture_extts_into`] refreshes the offset used to correlate events
+const EXTTS_OFFSET_REFRESH: Duration = Duration::from_secs(1);
+
+/// An edge of an external signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Edge {
+    /// Low to high transition
+    Rising,
+    /// High to low transition
+    Falling,
+}
+
+/// Flags reported by the kernel with an external timestamp event
+///
+/// Drivers which support it report the edge which triggered the capture, which is needed to tell
+/// rising from falling edges when both were requested.  Older kernels and drivers leave this zero.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventFlags(u32);
+
+impl EventFlags {
+    /// Wrap raw flag bits
+    pub fn from_bits(bits: u32) -> EventFlags {
+        EventFlags(bits)
+    }
+
+    /// The raw flag bits
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Whether the event was reported as a rising edge
+    pub fn rising_edge(&self) -> bool {
+        self.0 & PTP_RISING_EDGE != 0
+    }
+
+    /// Whether the event was reported as a falling edge
+    pub fn falling_edge(&self) -> bool {
+        self.0 & PTP_FALLING_EDGE != 0
+    }
+
+    /// The edge which triggered the event, if the driver reported exactly one
+    pub fn edge(&self) -> Option<Edge> {
+        match (self.rising_edge(), self.falling_edge()) {
+            (true, false) => Some(Edge::Rising),
+            (false, true) => Some(Edge::Falling),
+            _ => None,
+        }
+    }
+}
+
+impl ptp_extts_event {
+    /// Typed view of the event's flags
+    pub fn event_flags(&self) -> EventFlags {
+        EventFlags(self.flags)
+    }
+}
+
+/// Decode one `ptp_extts_event` from the bytes read from a PTP device
+///
+/// `buf` may start at any address, but must hold exactly one event; the kernel only ever writes
+/// whole events, so any other length means the read was short or the layout differs from the one
+/// this crate was built for.  Events with a nanosecond field of a second or more are rejected too,
+/// rather than passed on as timestamps which arithmetic on `ptp_clock_time` would get wrong.
+pub fn parse_extts_event(buf: &[u8]) -> Option<ptp_extts_event> {
+    if buf.len() != std::mem::size_of::<ptp_extts_event>() {
+        return None;
+    }
+    // Safety: buf holds size_of::<ptp_extts_event>() bytes, read_unaligned has no alignment
+    // requirement, and every bit pattern is a valid ptp_extts_event
+    let event = unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const ptp_extts_event) };
+    (event.t.nsec < 1_000_000_000).then_some(event)
+}
+
+/// An external timestamp event, as returned by [`PtpDevice::read_extts_event`]
+#[derive(Debug, Clone, Copy)]
+pub struct ExttsEvent(ptp_extts_event);
+
+impl ExttsEvent {
+    /// The external timestamp channel the event was captured on
+    pub fn channel(&self) -> u32 {
+        self.0.index
+    }
+
+    /// The time of the event on the PHC
+    pub fn device_time(&self) -> ptp_clock_time {
+        self.0.t
+    }
+
+    /// The time of the event on the system clock, given the offset of the PHC from it
+    ///
+    /// `offset_ns` is per [`crate::OFFSET_CONVENTION`], e.g. [`OffsetMeasurement::offset_ns`] from
+    /// a recent [`PtpDevice::measure_offset`], so it is subtracted from the device time.
+    pub fn system_time(&self, offset_ns: i64) -> ptp_clock_time {
+        ptp_clock_time::from_nanos(self.0.t.as_nanos() - offset_ns as i128)
+    }
+
+    /// Typed view of the event's flags
+    pub fn event_flags(&self) -> EventFlags {
+        self.0.event_flags()
+    }
+
+    /// The underlying kernel structure
+    pub fn raw(&self) -> &ptp_extts_event {
+        &self.0
+    }
+}
+
+impl From<ptp_extts_event> for ExttsEvent {
+    fn from(event: ptp_extts_event) -> ExttsEvent {
+        ExttsEvent(event)
+    }
+}
+
+/// Options for enabling an external timestamp channel
+///
+/// Created with [`ExttsBuilder::new`], this builds the flags for
+/// [`PtpDevice::enable_extts2`].  By default no edge is requested, leaving the choice to the
+/// driver (usually the rising edge), and strict flag checking is off.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExttsBuilder {
+    channel: u32,
+    rising: bool,
+    falling: bool,
+    strict: bool,
+}
+
+impl ExttsBuilder {
+    /// Options for channel 0 with the defaults described above
+    pub fn new() -> ExttsBuilder {
+        ExttsBuilder::default()
+    }
+
+    /// The external timestamp channel to configure
+    pub fn channel(mut self, channel: u32) -> ExttsBuilder {
+        self.channel = channel;
+        self
+    }
+
+    /// Timestamp rising edges
+    pub fn rising(mut self, rising: bool) -> ExttsBuilder {
+        self.rising = rising;
+        self
+    }
+
+    /// Timestamp falling edges
+    pub fn falling(mut self, falling: bool) -> ExttsBuilder {
+        self.falling = falling;
+        self
+    }
+
+    /// Set `PTP_STRICT_FLAGS`, so that a driver which cannot honour the requested edges fails
+    /// rather than interpreting them loosely
+    pub fn strict(mut self, strict: bool) -> ExttsBuilder {
+        self.strict = strict;
+        self
+    }
+
+    /// The request which [`ExttsBuilder::enable`] passes to the kernel
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if strict checking is requested without an edge,
+    /// which the kernel rejects.
+    pub fn request(&self) -> Result<ptp_extts_request> {
+        if self.strict && !self.rising && !self.falling {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "strict external timestamp flags require at least one edge",
+            ));
+        }
+        let mut flags = PTP_ENABLE_FEATURE;
+        for (set, flag) in [
+            (self.rising, PTP_RISING_EDGE),
+            (self.falling, PTP_FALLING_EDGE),
+            (self.strict, PTP_STRICT_FLAGS),
+        ] {
+            if set {
+                flags |= flag;
+            }
+        }
+        Ok(ptp_extts_request {
+            index: self.channel,
+            flags,
+            ..Default::default()
+        })
+    }
+
+    /// Enable the channel on `device` with these options
+    pub fn enable(&self, device: &PtpDevice) -> Result<()> {
+        let request = self.request()?;
+        device.enable_extts2(request.index, request.flags)
+    }
+
+    /// Disable the channel on `device`
+    pub fn disable(&self, device: &PtpDevice) -> Result<()> {
+        device.disable_extts(self.channel)
+    }
+}
+
+/// An external timestamp event with the corresponding system time
+#[derive(Debug, Clone, Copy)]
+pub struct CorrelatedEvent {
+    /// The event as read from the device
+    pub event: ptp_extts_event,
+    /// The `CLOCK_REALTIME` time of the event, from the device time and a recent offset
+    pub sys_time: ptp_clock_time,
+}
+
+/// One slot of an [`ExttsRingBuffer`], a seqlock over a [`CorrelatedEvent`] packed into words
+///
+/// `seq` is `2 * position + 1` while the event at `position` is being written and
+/// `2 * position + 2` once it is complete, so a reader can tell both a torn read and a slot which
+/// has since been reused for a later event.  The words are atomics so that a read racing a write
+/// is merely discarded rather than undefined behaviour.
+#[derive(Debug, Default)]
+struct RingSlot {
+    seq: AtomicU64,
+    words: [AtomicU64; 6],
+}
+
+impl RingSlot {
+    /// Store `event` as the event at `position`
+    fn write(&self, position: u64, event: &CorrelatedEvent) {
+        let (t, sys) = (event.event.t, event.sys_time);
+        let words = [
+            t.sec as u64,
+            t.nsec as u64 | (t.reserved as u64) << 32,
+            event.event.index as u64 | (event.event.flags as u64) << 32,
+            event.event.rsv[0] as u64 | (event.event.rsv[1] as u64) << 32,
+            sys.sec as u64,
+            sys.nsec as u64 | (sys.reserved as u64) << 32,
+        ];
+        self.seq.store(2 * position + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        for (slot, word) in self.words.iter().zip(words) {
+            slot.store(word, Ordering::Relaxed);
+        }
+        self.seq.store(2 * position + 2, Ordering::Release);
+    }
+
+    /// The event at `position`, unless the slot holds another or was written during the read
+    fn read(&self, position: u64) -> Option<CorrelatedEvent> {
+        let seq = self.seq.load(Ordering::Acquire);
+        if seq != 2 * position + 2 {
+            return None;
+        }
+        let w: [u64; 6] = std::array::from_fn(|i| self.words[i].load(Ordering::Relaxed));
+        fence(Ordering::Acquire);
+        if self.seq.load(Ordering::Relaxed) != seq {
+            return None;
+        }
+        let time = |sec: u64, rest: u64| ptp_clock_time {
+            sec: sec as i64,
+            nsec: rest as u32,
+            reserved: (rest >> 32) as u32,
+        };
+        Some(CorrelatedEvent {
+            event: ptp_extts_event {
+                t: time(w[0], w[1]),
+                index: w[2] as u32,
+                flags: (w[2] >> 32) as u32,
+                rsv: [w[3] as u32, (w[3] >> 32) as u32],
+            },
+            sys_time: time(w[4], w[5]),
+        })
+    }
+}
+
+/// A fixed-size, lock-free buffer of external timestamp events between a capture loop and a
+/// consumer
+///
+/// The capture loop, typically [`PtpDevice::capture_extts_into`] on its own thread, pushes
+/// events as they are read, and the consumer drains them at its own pace.  Neither side ever
+/// waits for the other: a push always completes in a fixed number of steps, however slow the
+/// consumer is.  If the consumer falls so far behind that the buffer fills, the oldest events are
+/// overwritten and counted in [`ExttsRingBuffer::overruns`].
+///
+/// The buffer is for a single producer and a single consumer.  Concurrent pushes, or concurrent
+/// pops, are memory safe but may lose or duplicate events.
+#[derive(Debug)]
+pub struct ExttsRingBuffer {
+    slots: Box<[RingSlot]>,
+    /// Number of events ever pushed, i.e. the position the next push writes
+    head: AtomicU64,
+    /// Position of the next event to pop
+    tail: AtomicU64,
+    /// Events the consumer found overwritten and skipped
+    skipped: AtomicU64,
+}
+
+impl ExttsRingBuffer {
+    /// An empty buffer holding up to `capacity` events, which must be non-zero
+    pub fn new(capacity: usize) -> Result<ExttsRingBuffer> {
+        if capacity == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "ring buffer capacity must be non-zero"));
+        }
+        Ok(ExttsRingBuffer {
+            slots: (0..capacity).map(|_| RingSlot::default()).collect(),
+            head: AtomicU64::new(0),
+            tail: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+        })
+    }
+
+    /// The slot holding the event at `position`
+    fn slot(&self, position: u64) -> &RingSlot {
+        &self.slots[(position % self.slots.len() as u64) as usize]
+    }
+
+    /// Add `event`, correlated to the system clock with `offset`, overwriting the oldest if full
+    pub fn push(&self, event: ptp_extts_event, offset: &OffsetMeasurement) {
+        let sys_time = ExttsEvent(event).system_time(offset.offset_ns);
+        let position = self.head.load(Ordering::Relaxed);
+        self.slot(position).write(position, &CorrelatedEvent { event, sys_time });
+        self.head.store(position + 1, Ordering::Release);
+    }
+
+    /// Remove and return the oldest event, if any
+    pub fn pop(&self) -> Option<CorrelatedEvent> {
+        let capacity = self.slots.len() as u64;
+        loop {
+            let head = self.head.load(Ordering::Acquire);
+            let mut tail = self.tail.load(Ordering::Relaxed);
+            if tail == head {
+                return None;
+            }
+            if head - tail > capacity {
+                self.skipped.fetch_add(head - capacity - tail, Ordering::Relaxed);
+                tail = head - capacity;
+                self.tail.store(tail, Ordering::Relaxed);
+            }
+            // A failed read means the producer has just reused the slot, so it is about to move
+            // head on and the retry will skip past the overwritten event
+            if let Some(event) = self.slot(tail).read(tail) {
+                self.tail.store(tail + 1, Ordering::Release);
+                return Some(event);
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Remove and return every buffered event, oldest first
+    ///
+    /// Events pushed while this runs are included, so a producer which keeps up with it can
+    /// keep it running indefinitely; use [`ExttsRingBuffer::pop`] to bound the work.
+    pub fn drain(&self) -> Vec<CorrelatedEvent> {
+        std::iter::from_fn(|| self.pop()).collect()
+    }
+
+    /// Number of events currently buffered
+    pub fn len(&self) -> usize {
+        // A consumer may move tail past the head loaded first, which just means nothing is unread
+        let unread = self.head.load(Ordering::Acquire).saturating_sub(self.tail.load(Ordering::Acquire));
+        unread.min(self.slots.len() as u64) as usize
+    }
+
+    /// Whether no events are buffered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The most events the buffer holds
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Number of events overwritten before they were consumed
+    pub fn overruns(&self) -> u64 {
+        let unread = self.head.load(Ordering::Acquire).saturating_sub(self.tail.load(Ordering::Acquire));
+        self.skipped.load(Ordering::Relaxed) + unread.saturating_sub(self.slots.len() as u64)
+    }
+}
+
+/// Predicts when the next event of a periodic external signal, such as a PPS, should arrive
+///
+/// Each observed event is numbered by how many periods it is after the previous one, so a missed
+/// pulse leaves a gap in the numbering rather than doubling the estimated period.  The period and
+/// phase are a least-squares line through the numbered events in the window.  With
+/// [`ExttsPredictor::timeout`] feeding [`PtpDevice::read_extts_event_timeout`], a missing signal
+/// then shows up as a timeout shortly after the expected pulse rather than a long silence.
+#[derive(Debug, Clone)]
+pub struct ExttsPredictor {
+    /// Pulse numbers and device times in nanoseconds of the most recent events, oldest first
+    events: VecDeque<(u64, i128)>,
+    window: usize,
+}
+
+impl ExttsPredictor {
+    /// A predictor fitting the last `window` events, which must be at least 2
+    pub fn new(window: usize) -> Result<ExttsPredictor> {
+        if window < 2 {
+            return Err(Error::new(ErrorKind::InvalidInput, "predictor window must be at least 2 events"));
+        }
+        Ok(ExttsPredictor {
+            events: VecDeque::with_capacity(window),
+            window,
+        })
+    }
+
+    /// Add the device time of an event, ignoring it if it is not after the previous one
+    pub fn observe(&mut self, time: ptp_clock_time) {
+        let nanos = time.as_nanos();
+        let pulse = match (self.events.back(), self.period_ns()) {
+            (Some(&(_, last)), _) if nanos <= last => return,
+            (Some(&(pulse, last)), Some(period)) => pulse + ((nanos - last) as f64 / period).round().max(1.0) as u64,
+            (Some(&(pulse, _)), None) => pulse + 1,
+            (None, _) => 0,
+        };
+        if self.events.len() == self.window {
+            self.events.pop_front();
+        }
+        self.events.push_back((pulse, nanos));
+    }
+
+    /// Forget every observed event, e.g. after the signal source changes
+    pub fn reset(&mut self) {
+        self.events.clear();
+    }
+
+    /// The fitted line through the window as (nanoseconds per pulse, nanoseconds at the first
+    /// pulse), relative to the first event in the window
+    fn fit(&self) -> Option<(f64, f64)> {
+        let &(pulse0, time0) = self.events.front()?;
+        if self.events.len() < 2 {
+            return None;
+        }
+        let n = self.events.len() as f64;
+        let points = || self.events.iter().map(|&(pulse, time)| ((pulse - pulse0) as f64, (time - time0) as f64));
+        let (mean_x, mean_y) = points().fold((0.0, 0.0), |(x, y), (px, py)| (x + px / n, y + py / n));
+        let (sxy, sxx) = points().fold((0.0, 0.0), |(sxy, sxx), (x, y)| {
+            (sxy + (x - mean_x) * (y - mean_y), sxx + (x - mean_x) * (x - mean_x))
+        });
+        let slope = sxy / sxx;
+        Some((slope, mean_y - slope * mean_x))
+    }
+
+    fn period_ns(&self) -> Option<f64> {
+        self.fit().map(|(slope, _)| slope)
+    }
+
+    /// The estimated period of the signal, once at least two events have been observed
+    pub fn period(&self) -> Option<Duration> {
+        self.period_ns().map(|ns| Duration::from_nanos(ns.max(0.0).round() as u64))
+    }
+
+    /// The estimated device time of the next event, once at least two events have been observed
+    pub fn predict_next(&self) -> Option<ptp_clock_time> {
+        let (slope, intercept) = self.fit()?;
+        let &(pulse0, time0) = self.events.front()?;
+        let &(last, _) = self.events.back()?;
+        let next = (last + 1 - pulse0) as f64;
+        Some(ptp_clock_time::from_nanos(time0 + (intercept + slope * next).round() as i128))
+    }
+
+    /// How long to wait, from the device time `now`, for the next event to arrive
+    ///
+    /// This is the time until the predicted event plus `margin`, or zero if that has already
+    /// passed, which means the pulse was missed.
+    pub fn timeout(&self, now: ptp_clock_time, margin: Duration) -> Option<Duration> {
+        let remaining = self.predict_next()?.as_nanos() - now.as_nanos() + margin.as_nanos() as i128;
+        Some(Duration::from_nanos(remaining.clamp(0, u64::MAX as i128) as u64))
+    }
+}
+
+/// Disables an external timestamp channel when dropped
+struct ExttsGuard<'a> {
+    device: &'a PtpDevice,
+    channel: u32,
+}
+
+impl Drop for ExttsGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.device.disable_extts(self.channel) {
+            log_warn!("failed to disable external timestamp channel {}: {e}", self.channel);
+        }
+    }
+}
+
+/// External timestamp events from several channels, from [`PtpDevice::capture_multi_extts`]
+///
+/// Each call to `next` blocks until an event arrives on one of the channels, including on a
+/// non-blocking device; use [`ExttsEvent::channel`] to tell them apart.  Events from other
+/// channels are skipped, and a failed read is returned without ending the iteration.  Every
+/// channel is disabled again when this is dropped.
+pub struct MultiExttsCapture<'a> {
+    device: &'a PtpDevice,
+    channels: Vec<u32>,
+    _guards: Vec<ExttsGuard<'a>>,
+}
+
+impl MultiExttsCapture<'_> {
+    /// The channels being captured
+    pub fn channels(&self) -> &[u32] {
+        &self.channels
+    }
+}
+
+impl Iterator for MultiExttsCapture<'_> {
+    type Item = Result<ExttsEvent>;
+
+    fn next(&mut self) -> Option<Result<ExttsEvent>> {
+        loop {
+            match self.device.read_extts_event() {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if let Err(e) = self.device.wait_readable(None) {
+                        return Some(Err(e));
+                    }
+                }
+                Ok(event) if !self.channels.contains(&event.channel()) => {}
+                result => return Some(result),
+            }
+        }
+    }
+}
+
+impl PtpDevice {
+    /// Wait until an event can be read, returning false if `timeout` expires first
+    ///
+    /// With no timeout this blocks indefinitely.  Interrupted waits are retried with the
+    /// remaining time.
+    pub(crate) fn wait_readable(&self, timeout: Option<Duration>) -> Result<bool> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let timeout_ms = match deadline {
+                // Round up so that a sub-millisecond remainder does not busy-wait
+                Some(d) => d
+                    .saturating_duration_since(Instant::now())
+                    .as_nanos()
+                    .div_ceil(1_000_000)
+                    .min(libc::c_int::MAX as u128) as libc::c_int,
+                None => -1,
+            };
+            let mut pfd = libc::pollfd {
+                fd: self.file.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // Safety: pfd is a single valid pollfd for the duration of the call
+            match unsafe { libc::poll(&mut pfd, 1, timeout_ms) } {
+                -1 => {
+                    let e = Error::last_os_error();
+                    if e.kind() != ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                    log_debug!("poll interrupted, retrying");
+                }
+                0 => return Ok(false),
+                _ => return Ok(true),
+            }
+        }
+    }
+
+    /// Enable external timestamp channel `index` to capture both rising and falling edges
+    ///
+    /// This requests both edges with `PTP_STRICT_FLAGS`, failing with `ErrorKind::Unsupported` if
+    /// the driver cannot capture both.  Use [`EventFlags::edge`] on each event to tell the edges
+    /// apart, e.g. to measure pulse width; drivers which do not report the edge leave it `None`.
+    pub fn enable_extts_both_edges(&self, index: u32) -> Result<()> {
+        self.enable_extts2(
+            index,
+            PTP_ENABLE_FEATURE | PTP_RISING_EDGE | PTP_FALLING_EDGE | PTP_STRICT_FLAGS,
+        )
+    }
+
+    /// Capture rising edges on external timestamp `channel`, passing each event to `cb` until it
+    /// returns `ControlFlow::Break`
+    ///
+    /// The channel is enabled for the duration of the call and disabled again on return, whether
+    /// the callback stopped the capture, a read failed, or the callback panicked.  Events from
+    /// other channels are skipped.  This blocks between events, including on a non-blocking
+    /// device.
+    pub fn capture_extts<F: FnMut(ExttsEvent) -> ControlFlow<()>>(&self, channel: u32, mut cb: F) -> Result<()> {
+        self.enable_extts2(channel, PTP_ENABLE_FEATURE | PTP_RISING_EDGE)?;
+        let _guard = ExttsGuard { device: self, channel };
+        loop {
+            let event = match self.read_extts_event() {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    self.wait_readable(None)?;
+                    continue;
+                }
+                result => result?,
+            };
+            if event.channel() != channel {
+                continue;
+            }
+            if cb(event).is_break() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Read the next external timestamp event, waiting at most `timeout` for one to arrive
+    ///
+    /// Returns `None` if no event arrives in time.  The wait uses `poll`, so the file's flags are
+    /// left unchanged and [`PtpDevice::read_extts_event`] keeps blocking as before.  If another
+    /// thread reads from the same device, it may take the event between the poll and the read,
+    /// in which case a blocking device waits for the following event.
+    pub fn read_extts_event_timeout(&self, timeout: Duration) -> Result<Option<ExttsEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if !self.wait_readable(Some(deadline.saturating_duration_since(Instant::now())))? {
+                return Ok(None);
+            }
+            match self.read_extts_event() {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
+                result => return result.map(Some),
+            }
+        }
+    }
+
+    /// Enable each of the external timestamp `channels` and capture events from all of them
+    ///
+    /// `flags` are as for [`PtpDevice::enable_extts2`], with `PTP_ENABLE_FEATURE` implied, and
+    /// apply to every channel.  If enabling a channel fails, those already enabled are disabled
+    /// again before the error is returned.  Fails with `ErrorKind::InvalidInput` if `channels` is
+    /// empty or names a channel twice.
+    pub fn capture_multi_extts(&self, channels: &[u32], flags: u32) -> Result<MultiExttsCapture<'_>> {
+        if channels.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "no external timestamp channels to capture"));
+        }
+        if let Some((i, channel)) = channels.iter().enumerate().find(|(i, c)| channels[..*i].contains(c)) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("external timestamp channel {channel} is listed twice, at position {i}"),
+            ));
+        }
+        let mut guards = Vec::with_capacity(channels.len());
+        for &channel in channels {
+            self.enable_extts2(channel, flags | PTP_ENABLE_FEATURE)?;
+            guards.push(ExttsGuard { device: self, channel });
+        }
+        Ok(MultiExttsCapture {
+            device: self,
+            channels: channels.to_vec(),
+            _guards: guards,
+        })
+    }
+
+    /// Capture rising edges on external timestamp `channel` into `ring` until `stop` is set
+    ///
+    /// This is meant to run on a thread of its own, with a consumer draining `ring`.  Each event
+    /// is correlated with the system clock using an offset measured with
+    /// [`PtpDevice::measure_offset`] at most a second earlier.  The channel is enabled for the
+    /// duration of the call and disabled on return, and `stop` is checked at least once a second
+    /// even if no events arrive.  Events from other channels are skipped.
+    pub fn capture_extts_into(&self, channel: u32, ring: &ExttsRingBuffer, stop: &AtomicBool) -> Result<()> {
+        self.enable_extts2(channel, PTP_ENABLE_FEATURE | PTP_RISING_EDGE)?;
+        let _guard = ExttsGuard { device: self, channel };
+        let mut offset = self.measure_offset()?;
+        let mut measured = Instant::now();
+        while !stop.load(Ordering::Relaxed) {
+            let event = self.read_extts_event_timeout(EXTTS_OFFSET_REFRESH)?;
+            if measured.elapsed() >= EXTTS_OFFSET_REFRESH {
+                offset = self.measure_offset()?;
+                measured = Instant::now();
+            }
+            if let Some(event) = event.filter(|event| event.channel() == channel) {
+                ring.push(*event.raw(), &offset);
+            }
+        }
+        Ok(())
+    }
+
+    /// The time of the next event on external timestamp channel `a` minus that on channel `b`, in
+    /// nanoseconds
+    ///
+    /// Both channels are enabled for rising edges, and the first event on each within `timeout`
+    /// is used, so for two PPS sources this measures their skew as long as it is well under half
+    /// a second.  Both channels are disabled again on return, including on failure.  Fails with
+    /// `ErrorKind::InvalidInput` if `a` and `b` are the same channel, and `ErrorKind::TimedOut` if
+    /// either channel has no event in time.
+    pub fn compare_extts_channels(&self, a: u32, b: u32, timeout: Duration) -> Result<i64> {
+        if a == b {
+            return Err(Error::new(ErrorKind::InvalidInput, "cannot compare a channel with itself"));
+        }
+        let deadline = Instant::now() + timeout;
+        self.enable_extts2(a, PTP_ENABLE_FEATURE | PTP_RISING_EDGE)?;
+        let _guard_a = ExttsGuard { device: self, channel: a };
+        self.enable_extts2(b, PTP_ENABLE_FEATURE | PTP_RISING_EDGE)?;
+        let _guard_b = ExttsGuard { device: self, channel: b };
+
+        let (mut time_a, mut time_b): (Option<ptp_clock_time>, Option<ptp_clock_time>) = (None, None);
+        loop {
+            if let (Some(time_a), Some(time_b)) = (time_a, time_b) {
+                return Ok((time_a.as_nanos() - time_b.as_nanos()) as i64);
+            }
+            let Some(event) = self.read_extts_event_timeout(deadline.saturating_duration_since(Instant::now()))? else {
+                let missing = if time_a.is_none() { a } else { b };
+                return Err(Error::new(
+                    ErrorKind::TimedOut,
+                    format!("no event on external timestamp channel {missing}"),
+                ));
+            };
+            if event.channel() == a {
+                time_a.get_or_insert(event.device_time());
+            } else if event.channel() == b {
+                time_b.get_or_insert(event.device_time());
+            }
+        }
+    }
+
+    /// Wait for the next pulse on external timestamp `channel` and return its PHC timestamp
+    ///
+    /// The channel must already be enabled, e.g. with [`PtpDevice::enable_extts2`].  Events from
+    /// other channels arriving in the meantime are read and discarded, so do not use this while
+    /// another part of the program is consuming events from the same device.  Returns
+    /// `ErrorKind::TimedOut` if no matching event arrives within `timeout`; `None` waits forever.
+    pub fn next_pps(&self, channel: u32, timeout: Option<Duration>) -> Result<ptp_clock_time> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if !self.wait_readable(remaining)? {
+                return Err(Error::new(
+                    ErrorKind::TimedOut,
+                    format!("no event on external timestamp channel {channel}"),
+                ));
+            }
+            // On a non-blocking fd another reader may have taken the event since the poll
+            let event = match self.read_extts_event() {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
+                result => result?,
+            };
+            if event.channel() == channel {
+                return Ok(event.device_time());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{measurement, null_device};
+
+    #[test]
+    fn builder_sets_flags_and_rejects_strict_without_edges() {
+        let request = ExttsBuilder::new().channel(2).falling(true).strict(true).request().unwrap();
+        assert_eq!(request.index, 2);
+        assert_eq!(request.flags, PTP_ENABLE_FEATURE | PTP_FALLING_EDGE | PTP_STRICT_FLAGS);
+
+        let e = ExttsBuilder::new().strict(true).request().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn multi_capture_rejects_empty_and_repeated_channels() {
+        let device = null_device();
+        for channels in [&[][..], &[0, 1, 0]] {
+            let e = device.capture_multi_extts(channels, PTP_RISING_EDGE).err().unwrap();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        }
+        assert!(device.capture_multi_extts(&[0, 1], PTP_RISING_EDGE).is_err());
+    }
+
+    #[test]
+    fn comparing_a_channel_with_itself_is_rejected() {
+        let device = null_device();
+        let e = device.compare_extts_channels(1, 1, Duration::from_secs(1)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn predictor_fits_period_across_missed_pulses() {
+        let mut predictor = ExttsPredictor::new(4).unwrap();
+        assert!(predictor.predict_next().is_none());
+        // A 1 Hz signal running 10 ppm fast, with the pulse at 103 missed
+        for pulse in [100, 101, 102, 104, 105] {
+            predictor.observe(ptp_clock_time::from_nanos(pulse as i128 * 999_990_000));
+        }
+        assert_eq!(predictor.period(), Some(Duration::from_nanos(999_990_000)));
+        assert_eq!(predictor.predict_next().unwrap().as_nanos(), 106 * 999_990_000);
+
+        let now = ptp_clock_time::from_nanos(105 * 999_990_000 + 500_000_000);
+        let timeout = predictor.timeout(now, Duration::from_millis(10)).unwrap();
+        assert_eq!(timeout, Duration::from_nanos(499_990_000 + 10_000_000));
+        assert!(ExttsPredictor::new(1).is_err());
+    }
+
+    #[test]
+    fn ring_buffer_overwrites_oldest_and_correlates() {
+        let ring = ExttsRingBuffer::new(2).unwrap();
+        let offset = measurement(0, 37_000_000_000);
+        for sec in [100, 101, 102] {
+            let event = ptp_extts_event {
+                t: ptp_clock_time { sec, ..Default::default() },
+                ..Default::default()
+            };
+            ring.push(event, &offset);
+        }
+        assert_eq!(ring.overruns(), 1);
+        let events = ring.drain();
+        assert_eq!(events.iter().map(|e| e.sys_time.sec).collect::<Vec<_>>(), vec![64, 65]);
+        assert!(ring.is_empty());
+        assert!(ExttsRingBuffer::new(0).is_err());
+    }
+
+    #[test]
+    fn ring_buffer_never_reorders_events_across_threads() {
+        const EVENTS: i64 = 100_000;
+        let ring = ExttsRingBuffer::new(16).unwrap();
+        let offset = measurement(0, 0);
+        let done = AtomicBool::new(false);
+        let popped = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for sec in 0..EVENTS {
+                    let event = ptp_extts_event {
+                        t: ptp_clock_time { sec, nsec: sec as u32, ..Default::default() },
+                        ..Default::default()
+                    };
+                    ring.push(event, &offset);
+                }
+                done.store(true, Ordering::Release);
+            });
+            let mut popped = Vec::new();
+            while !done.load(Ordering::Acquire) || !ring.is_empty() {
+                popped.extend(ring.pop());
+            }
+            popped
+        });
+        assert!(popped.windows(2).all(|w| w[0].event.t.sec < w[1].event.t.sec));
+        // A torn read would mix the fields of two events
+        let consistent = |e: &CorrelatedEvent| e.event.t.nsec as i64 == e.event.t.sec && e.sys_time.sec == e.event.t.sec;
+        assert!(popped.iter().all(consistent));
+        assert_eq!(popped.len() as u64 + ring.overruns(), EVENTS as u64);
+    }
+
+    #[test]
+    fn parser_rejects_malformed_buffers() {
+        let size = std::mem::size_of::<ptp_extts_event>();
+        let mut buf = vec![0u8; size * 2 + 1];
+        buf[1..9].copy_from_slice(&7i64.to_ne_bytes());
+        buf[17] = 2; // index, one byte into the buffer so the event is misaligned
+        let event = parse_extts_event(&buf[1..size + 1]).unwrap();
+        assert_eq!((event.t.sec, event.index), (7, 2));
+
+        assert!(parse_extts_event(&[]).is_none());
+        assert!(parse_extts_event(&buf[1..size]).is_none());
+        assert!(parse_extts_event(&buf[..size + 1]).is_none());
+        assert!(parse_extts_event(&buf[..size * 2]).is_none());
+        buf[9..13].copy_from_slice(&1_000_000_000u32.to_ne_bytes());
+        assert!(parse_extts_event(&buf[1..size + 1]).is_none());
+    }
+
+    #[test]
+    fn timed_read_returns_none_then_event() {
+        use std::{fs::File, io::Write, os::fd::FromRawFd};
+
+        let mut fds = [0; 2];
+        // Safety: fds has room for the two descriptors pipe2 returns
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+        // Safety: both descriptors were just created and are owned by nothing else
+        let (read, mut write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        let device = PtpDevice::from_file(read);
+        assert!(device.read_extts_event_timeout(Duration::from_millis(10)).unwrap().is_none());
+
+        let mut event = [0u8; std::mem::size_of::<ptp_extts_event>()];
+        event[16] = 3; // index
+        write.write_all(&event).unwrap();
+        let event = device.read_extts_event_timeout(Duration::from_millis(10)).unwrap().unwrap();
+        assert_eq!(event.channel(), 3);
+    }
+}
@@ -0,0 +1,309 @@
+//! Discovery of the PHCs belonging to network interfaces
+
+use std::{
+    fs::{self, File},
+    io::{Error, ErrorKind, Result},
+    os::fd::{AsRawFd, FromRawFd},
+    path::Path,
+    time::Duration,
+};
+
+use crate::{netlink::tx_timestamp_counters, sysfs::parse_ptp_index, PtpDevice, PtpError};
+
+/// `ETHTOOL_GET_TS_INFO` from linux/ethtool.h
+const ETHTOOL_GET_TS_INFO: u32 = 0x41;
+
+/// `struct ethtool_ts_info` from linux/ethtool.h
+#[repr(C)]
+#[derive(Debug, Default)]
+struct ethtool_ts_info {
+    cmd: u32,
+    so_timestamping: u32,
+    phc_index: i32,
+    tx_types: u32,
+    tx_reserved: [u32; 3],
+    rx_filters: u32,
+    rx_reserved: [u32; 3],
+}
+
+/// Check that `ifname` can name a network interface, so it is safe to use in a sysfs path
+fn check_ifname(ifname: &str) -> Result<()> {
+    if ifname.is_empty() || ifname.len() >= libc::IFNAMSIZ || ifname.contains('/') || ifname == "." || ifname == ".." {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("invalid interface name {ifname:?}"),
+        ));
+    }
+    Ok(())
+}
+
+/// The timestamping information reported by `ETHTOOL_GET_TS_INFO`, if the driver reports it
+fn ts_info(ifname: &str) -> Result<Option<ethtool_ts_info>> {
+    // Safety: socket takes no pointers; the result is checked before use
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if fd == -1 {
+        return Err(Error::last_os_error());
+    }
+    // Safety: fd is a newly created socket owned by nothing else
+    let socket = unsafe { File::from_raw_fd(fd) };
+
+    let mut info = ethtool_ts_info {
+        cmd: ETHTOOL_GET_TS_INFO,
+        ..Default::default()
+    };
+    // Safety: ifreq is a plain C struct, for which all zeroes is a valid value
+    let mut ifr: libc::ifreq = unsafe { std::mem::zeroed() };
+    for (dst, &src) in ifr.ifr_name.iter_mut().zip(ifname.as_bytes()) {
+        *dst = src as libc::c_char;
+    }
+    ifr.ifr_ifru.ifru_data = &mut info as *mut ethtool_ts_info as *mut libc::c_char;
+    // Safety: SIOCETHTOOL reads ifr, whose data points to an ethtool_ts_info; both outlive the call
+    if unsafe { libc::ioctl(socket.as_raw_fd(), libc::SIOCETHTOOL as _, &mut ifr) } == -1 {
+        let e = Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::EOPNOTSUPP) => Ok(None),
+            _ => Err(e),
+        };
+    }
+    Ok(Some(info))
+}
+
+/// The PHC index reported by `ETHTOOL_GET_TS_INFO`, if the driver reports one
+fn ts_info_phc_index(ifname: &str) -> Result<Option<u32>> {
+    Ok(ts_info(ifname)?.and_then(|info| u32::try_from(info.phc_index).ok()))
+}
+
+/// Names of the `HWTSTAMP_TX_*` types, indexed by type, as printed by `ethtool -T`
+const TX_TYPE_NAMES: [&str; 4] = ["off", "on", "onestep-sync", "onestep-p2p"];
+
+/// Names of the `HWTSTAMP_FILTER_*` filters, indexed by filter, as printed by `ethtool -T`
+const RX_FILTER_NAMES: [&str; 16] = [
+    "none",
+    "all",
+    "some",
+    "ptpv1-l4-event",
+    "ptpv1-l4-sync",
+    "ptpv1-l4-delay-req",
+    "ptpv2-l4-event",
+    "ptpv2-l4-sync",
+    "ptpv2-l4-delay-req",
+    "ptpv2-l2-event",
+    "ptpv2-l2-sync",
+    "ptpv2-l2-delay-req",
+    "ptpv2-event",
+    "ptpv2-sync",
+    "ptpv2-delay-req",
+    "ntp-all",
+];
+
+/// The names of the bits set in `bits`, or the bit number for bits without a name
+fn bit_names(bits: u32, names: &[&'static str]) -> Vec<String> {
+    (0..u32::BITS)
+        .filter(|bit| bits & (1 << bit) != 0)
+        .map(|bit| names.get(bit as usize).map_or_else(|| format!("bit{bit}"), |name| name.to_string()))
+        .collect()
+}
+
+/// The packet timestamping an interface supports, from [`PtpDevice::timestamp_stats`]
+///
+/// This is what `ethtool -T` prints: the flags are those of `struct ethtool_ts_info`, and the
+/// counters those of the ethtool netlink `ETHTOOL_A_TSINFO_STATS` attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampStats {
+    /// The network interface the information is for
+    pub interface: String,
+    /// The index of the PHC which timestamps the interface's packets, if it has one
+    pub phc_index: Option<u32>,
+    /// The `SOF_TIMESTAMPING_*` flags the interface supports
+    pub so_timestamping: u32,
+    /// The `HWTSTAMP_TX_*` types the interface supports, as a bitmask of `1 << type`
+    pub tx_types: u32,
+    /// The `HWTSTAMP_FILTER_*` filters the interface supports, as a bitmask of `1 << filter`
+    pub rx_filters: u32,
+    /// Packets whose transmit timestamp was delivered, if the driver counts them
+    pub tx_packets: Option<u64>,
+    /// Packets whose transmit timestamp the hardware never produced, e.g. because it was busy
+    /// with an earlier one, if the driver counts them
+    pub tx_lost: Option<u64>,
+    /// Packets whose transmit timestamp failed to be read from the hardware, if the driver
+    /// counts them
+    pub tx_errors: Option<u64>,
+}
+
+impl TimestampStats {
+    /// Whether the interface can timestamp transmitted packets in hardware
+    pub fn hardware_tx(&self) -> bool {
+        self.so_timestamping & libc::SOF_TIMESTAMPING_TX_HARDWARE != 0
+    }
+
+    /// Whether the interface can timestamp received packets in hardware
+    pub fn hardware_rx(&self) -> bool {
+        self.so_timestamping & libc::SOF_TIMESTAMPING_RX_HARDWARE != 0
+    }
+
+    /// The names of the supported transmit timestamp types, e.g. `on`
+    pub fn tx_type_names(&self) -> Vec<String> {
+        bit_names(self.tx_types, &TX_TYPE_NAMES)
+    }
+
+    /// The names of the supported receive filters, e.g. `ptpv2-event`
+    pub fn rx_filter_names(&self) -> Vec<String> {
+        bit_names(self.rx_filters, &RX_FILTER_NAMES)
+    }
+}
+
+/// The indices of every PHC associated with the network interface `ifname`
+///
+/// The first index is the one the driver reports through `ETHTOOL_GET_TS_INFO`: the clock that
+/// timestamps the interface's packets, as used by `ptp4l`.  It is followed, in index order, by
+/// any secondary clocks registered by the same hardware device under
+/// `/sys/class/net/<ifname>/device/ptp`, such as the other clocks of a card exposing both a
+/// free-running and a synchronised PHC, or the shared clock of another port.  Virtual clocks
+/// are not included; see [`PtpDevice::vclock`].  The result is empty if the interface has no
+/// PHC.  Fails with `ErrorKind::InvalidInput` for a name which cannot be an interface, and with
+/// the error of the ethtool request, e.g. `ENODEV`, if there is no such interface.
+pub fn phc_indices_for_interface(ifname: &str) -> Result<Vec<u32>> {
+    check_ifname(ifname)?;
+    let primary = ts_info_phc_index(ifname)?;
+
+    let mut secondary = Vec::new();
+    match fs::read_dir(Path::new("/sys/class/net").join(ifname).join("device/ptp")) {
+        Ok(entries) => {
+            for entry in entries {
+                let name = entry?.file_name();
+                if let Some(index) = name.to_str().and_then(parse_ptp_index) {
+                    secondary.push(index);
+                }
+            }
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    secondary.sort_unstable();
+
+    let mut indices: Vec<u32> = primary.into_iter().collect();
+    indices.extend(secondary.into_iter().filter(|&index| Some(index) != primary));
+    indices.dedup();
+    Ok(indices)
+}
+
+impl PtpDevice {
+    /// Open the PHC which timestamps packets on the network interface `ifname`, read-only
+    ///
+    /// This is the first of [`phc_indices_for_interface`].  Fails with
+    /// [`PtpError::DeviceNotFound`] if the interface has no PHC.
+    pub fn from_interface(ifname: &str) -> Result<PtpDevice> {
+        match phc_indices_for_interface(ifname)?.first() {
+            Some(index) => PtpDevice::builder().open_index(*index),
+            None => Err(PtpError::DeviceNotFound {
+                path: format!("PHC of interface {ifname}").into(),
+            }
+            .into()),
+        }
+    }
+
+    /// The packet timestamping supported by the network interface of this clock's hardware
+    ///
+    /// The interface is the first under the device's sysfs directory, as for
+    /// [`describe_device`](crate::describe_device).  Fails with `ErrorKind::Unsupported` if the
+    /// clock has no network interface or its driver does not report timestamping information.
+    ///
+    /// The transmit counters are read over ethtool netlink, and are `None` on kernels before
+    /// 6.10, for drivers which do not keep them, or if netlink cannot be queried.  They count
+    /// from when the driver was loaded: the kernel offers no way to reset them, so to count over
+    /// an interval, take the difference of two readings.
+    pub fn timestamp_stats(&self) -> Result<TimestampStats> {
+        let interface = self
+            .interface_name()
+            .ok_or_else(|| Error::new(ErrorKind::Unsupported, "clock has no associated network interface"))?;
+        let info = ts_info(&interface)?.ok_or_else(|| {
+            Error::new(ErrorKind::Unsupported, format!("{interface} does not report timestamping information"))
+        })?;
+        let counters = tx_timestamp_counters(&interface).unwrap_or_else(|e| {
+            log_debug!("no timestamping counters for {interface}: {e}");
+            Default::default()
+        });
+        Ok(TimestampStats {
+            interface,
+            phc_index: u32::try_from(info.phc_index).ok(),
+            so_timestamping: info.so_timestamping,
+            tx_types: info.tx_types,
+            rx_filters: info.rx_filters,
+            tx_packets: counters.packets,
+            tx_lost: counters.lost,
+            tx_errors: counters.errors,
+        })
+    }
+
+    /// The fixed delay between a packet crossing the wire and being timestamped which the driver
+    /// of this clock's network interface reports, if it reports one
+    ///
+    /// Returns `None` rather than guessing when the information is not available, which is
+    /// currently always: `ETHTOOL_GET_TS_INFO` has no field for it and drivers do not expose it in
+    /// sysfs, so those that know their latency already correct their timestamps for it.  Newer
+    /// kernels may report it through the `ETHTOOL_MSG_TSINFO_GET` netlink reply, which is where a
+    /// source for it belongs.  Also `None` if the clock has no network interface.
+    pub fn hardware_timestamp_delay(&self) -> Result<Option<Duration>> {
+        match self.interface_name() {
+            Some(interface) => reported_timestamp_delay(&interface),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The timestamping delay the driver of `ifname` reports, from the sources the kernel offers
+///
+/// None of the interfaces read by this crate carry it yet; see
+/// [`PtpDevice::hardware_timestamp_delay`].
+fn reported_timestamp_delay(_ifname: &str) -> Result<Option<Duration>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[test]
+    fn ethtool_ts_info_matches_kernel_layout() {
+        assert_eq!(std::mem::size_of::<ethtool_ts_info>(), 44);
+    }
+
+    #[test]
+    fn timestamp_stats_need_an_interface() {
+        let device = null_device();
+        assert_eq!(device.timestamp_stats().unwrap_err().kind(), ErrorKind::Unsupported);
+
+        let stats = TimestampStats {
+            interface: "eth0".to_string(),
+            phc_index: Some(0),
+            so_timestamping: libc::SOF_TIMESTAMPING_RX_HARDWARE,
+            tx_types: 1 << 1,
+            rx_filters: (1 << 12) | (1 << 20),
+            tx_packets: None,
+            tx_lost: None,
+            tx_errors: None,
+        };
+        assert!(stats.hardware_rx() && !stats.hardware_tx());
+        assert_eq!(stats.tx_type_names(), vec!["on"]);
+        assert_eq!(stats.rx_filter_names(), vec!["ptpv2-event", "bit20"]);
+    }
+
+    #[test]
+    fn timestamp_delay_is_none_without_a_source() {
+        let device = null_device();
+        assert_eq!(device.hardware_timestamp_delay().unwrap(), None);
+    }
+
+    #[test]
+    fn loopback_has_no_phc() {
+        assert_eq!(phc_indices_for_interface("lo").unwrap(), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        for name in ["", "..", "a/b", "sixteen-chars-xx"] {
+            assert_eq!(phc_indices_for_interface(name).unwrap_err().kind(), ErrorKind::InvalidInput);
+        }
+    }
+}
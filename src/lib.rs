@@ -6,89 +6,194 @@
 //! - `ptp_sys_offset` - Get system offset measurements
 //! - `ptp_sys_offset_precise` - Get precise system offset measurements
 //! - `ptp_sys_offset_extended` - Get extended system offset measurements
+//! - `ptp_extts_request` - Enable or disable external timestamp channels
+//! - `ptp_perout_request` - Configure periodic output channels
+//! - `ptp_pin_desc` - Get and set programmable pin functions
+//! - `PTP_ENABLE_PPS2` - Enable or disable PPS event reporting
+//!
+//! [`PpsDevice`] also reads the resulting events from the kernel PPS source with `PPS_FETCH`.
+//!
+//! [`PtpDevice::measure_offset`] combines these into a single [`OffsetMeasurement`] using the best
+//! method the device supports.
+//!
+//! Errors are reported as `std::io::Error`; see [`PtpError`] for the conditions the crate
+//! describes in more detail than the raw errno.
+//!
+//! The `test-util` feature adds `ReplayDevice` and `PtpBackend` for testing servos against
+//! recorded offsets, and the `timecard` feature adds `TimeCard` for the metadata of OCP Time
+//! Cards.
+//!
+//! The device API needs the default `std` feature.  Without it, only the [`ptp`] structures and
+//! the `ptp_clock_time` conversions and arithmetic are available, which build with `no_std` for
+//! analysis of captured data where system calls are not available.
 
-use std::{
-    fs::File,
-    io::{Error, Result},
-    mem::MaybeUninit,
-    os::{
-        fd::AsRawFd,
-        raw::c_ulong,
-    },
-    path::PathBuf,
-};
-
-pub mod ptp;
-use ptp::*;
-
-// PTP ioctl constants - These are standard Linux PTP driver ioctls
-// Based on linux/ptp_clock.h: PTP_CLK_MAGIC = '=' = 0x3D
-
-// Correct ioctl values partially calculated from the header file and partially
-// worked out by stracing chrony. If anyone can explain where the top 16 bits
-// come from I'd be grateful.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-// _IOR(PTP_CLK_MAGIC, 1, struct ptp_clock_caps) -> 0x80503d01
-// _IOW(PTP_CLK_MAGIC, 5, struct ptp_sys_offset) -> 0x43403d05
-// _IOWR(PTP_CLK_MAGIC, 8, struct ptp_sys_offset_precise) -> 0xc0403d08
-// _IOWR(PTP_CLK_MAGIC, 9, struct ptp_sys_offset_extended) -> 0xc4c03d09
+/// Log a debug message through the `log` crate, compiled out unless the `log` feature is enabled
+#[cfg(feature = "std")]
+macro_rules! log_debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        log::debug!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    }};
+}
 
-const PTP_CLOCK_GETCAPS: c_ulong = 0x80503d01; // _IOR(PTP_CLK_MAGIC, 1, struct ptp_clock_caps)
-const PTP_SYS_OFFSET: c_ulong = 0x43403d05;   // _IOW(PTP_CLK_MAGIC, 5, struct ptp_sys_offset)
-const PTP_SYS_OFFSET_PRECISE: c_ulong = 0xc0403d08; // _IOWR(PTP_CLK_MAGIC, 8, struct ptp_sys_offset_precise)
-const PTP_SYS_OFFSET_EXTENDED: c_ulong = 0xc4c03d09; // _IOWR(PTP_CLK_MAGIC, 9, struct ptp_sys_offset_extended)
+/// Log a warning through the `log` crate, compiled out unless the `log` feature is enabled
+#[cfg(feature = "std")]
+macro_rules! log_warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        log::warn!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    }};
+}
 
-/// A safe wrapper for PTP hardware clock devices
-pub struct PtpDevice(File);
+/// Enter a `tracing` span named `$name` with the given fields initially empty, returning its guard
+///
+/// Without the `tracing` feature this is a `NoSpan`, which compiles to nothing.
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($name:literal $(, $field:ident)*) => {
+        tracing::debug_span!($name $(, $field = tracing::field::Empty)*).entered()
+    };
+}
 
-impl PtpDevice {
-    /// Create a new PTP device from a path
-    pub fn new(path: PathBuf) -> Result<PtpDevice> {
-        Ok(PtpDevice(File::open(path)?))
-    }
+#[cfg(all(feature = "std", not(feature = "tracing")))]
+macro_rules! trace_span {
+    ($name:literal $(, $field:ident)*) => {
+        crate::NoSpan
+    };
+}
 
-    /// Perform ioctl request and check result for possible errors
-    unsafe fn ioctl<T>(&self, request: c_ulong, value: &mut T) -> Result<()> {
-        match libc::ioctl(self.0.as_raw_fd(), request as _, value) {
-            0 => Ok(()),
-            _ => Err(Error::last_os_error()),
-        }
-    }
+/// Stand-in for an entered span when the `tracing` feature is disabled
+#[cfg(all(feature = "std", not(feature = "tracing")))]
+struct NoSpan;
 
-    /// Perform ioctl request with uninitialized memory
-    unsafe fn ioctl_uninit<T>(&self, request: c_ulong) -> Result<T> {
-        let mut value: MaybeUninit<T> = MaybeUninit::uninit();
-        self.ioctl(request, &mut value)?;
-        Ok(unsafe { value.assume_init() })
-    }
+#[cfg(all(feature = "std", not(feature = "tracing")))]
+impl NoSpan {
+    /// Discard a span field
+    #[inline(always)]
+    fn record<V>(&self, _field: &str, _value: V) {}
+}
 
-    /// Get the clock capabilities
-    pub fn get_caps(&self) -> Result<ptp_clock_caps> {
-        // Safety: PTP_CLOCK_GETCAPS writes ptp_clock_caps, for which memory is allocated and returned by ioctl_uninit
-        unsafe { self.ioctl_uninit(PTP_CLOCK_GETCAPS) }
-    }
+pub mod ptp;
 
-    /// Get system offset measurements
-    pub fn get_sys_offset(&self) -> Result<ptp_sys_offset> {
-        let mut offset = ptp_sys_offset::default();
-        // Safety: PTP_SYS_OFFSET expects and writes to a ptp_sys_offset, which lives for the duration of the call
-        unsafe { self.ioctl(PTP_SYS_OFFSET, &mut offset)? };
-        Ok(offset)
-    }
+mod time;
 
-    /// Get precise system offset measurements
-    pub fn get_sys_offset_precise(&self) -> Result<ptp_sys_offset_precise> {
-        let mut offset = ptp_sys_offset_precise::default();
-        // Safety: PTP_SYS_OFFSET_PRECISE expects and writes to a ptp_sys_offset_precise, which lives for the duration of the call
-        unsafe { self.ioctl(PTP_SYS_OFFSET_PRECISE, &mut offset)? };
-        Ok(offset)
-    }
+#[cfg(feature = "std")]
+mod adjust;
+#[cfg(feature = "tokio")]
+mod async_device;
+#[cfg(feature = "std")]
+mod caps;
+#[cfg(feature = "std")]
+mod device;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+mod extts;
+#[cfg(feature = "std")]
+mod interface;
+#[cfg(feature = "std")]
+mod logger;
+#[cfg(feature = "std")]
+mod measurement;
+#[cfg(feature = "std")]
+mod netlink;
+#[cfg(feature = "std")]
+mod open;
+#[cfg(feature = "std")]
+mod perout;
+#[cfg(feature = "std")]
+mod pin;
+#[cfg(feature = "std")]
+mod pps;
+#[cfg(feature = "test-util")]
+mod replay;
+#[cfg(feature = "std")]
+mod sampling;
+#[cfg(feature = "std")]
+pub mod servo;
+#[cfg(feature = "std")]
+mod snapshot;
+#[cfg(feature = "std")]
+mod soak;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+mod sysfs;
+#[cfg(all(test, feature = "std"))]
+mod test_support;
+#[cfg(feature = "timecard")]
+pub mod timecard;
+#[cfg(feature = "std")]
+mod timescale;
+#[cfg(feature = "serde")]
+pub mod wire;
 
-    /// Get extended system offset measurements
-    pub fn get_sys_offset_extended(&self) -> Result<ptp_sys_offset_extended> {
-        let mut offset = ptp_sys_offset_extended::default();
-        // Safety: PTP_SYS_OFFSET_EXTENDED expects and writes to a ptp_sys_offset_extended, which lives for the duration of the call
-        unsafe { self.ioctl(PTP_SYS_OFFSET_EXTENDED, &mut offset)? };
-        Ok(offset)
-    }
-}
+#[cfg(feature = "std")]
+pub use adjust::{
+    ppb_to_scaled_ppm, scaled_ppm_to_ppb, AdjustAction, AdjustmentRecord, ClockState, ClockStatus, LeapStatus,
+    StatusFlags,
+};
+#[cfg(feature = "tokio")]
+pub use async_device::AsyncPtpDevice;
+#[cfg(feature = "std")]
+pub use caps::{CapFlags, ClockCaps};
+#[cfg(feature = "std")]
+pub use device::PtpDevice;
+#[cfg(feature = "std")]
+use device::{ioctl_error, is_unsupported, lock, Reserved, PTP_ENABLE_PPS2, PTP_PEROUT_REQUEST2, PTP_PIN_GETFUNC2, PTP_PIN_SETFUNC2};
+#[cfg(feature = "std")]
+pub use error::PtpError;
+#[cfg(feature = "std")]
+pub use extts::{
+    parse_extts_event, CorrelatedEvent, Edge, EventFlags, ExttsBuilder, ExttsEvent, ExttsPredictor, ExttsRingBuffer,
+    MultiExttsCapture,
+};
+#[cfg(feature = "std")]
+pub use interface::{phc_indices_for_interface, TimestampStats};
+#[cfg(feature = "serde")]
+pub use logger::MeasurementLogger;
+#[cfg(feature = "std")]
+pub use logger::{CsvLogger, CSV_HEADER};
+#[cfg(feature = "std")]
+pub use measurement::{
+    collect_offsets, interpolate_device_time, CrossTimestamp, ExtendedOffset, ExtendedSample, MethodComparison,
+    OffsetMeasurement, OffsetMethod, PreciseOffset, RobustEstimate, SysClock, SysOffset, DEFAULT_MAX_DELAY_NS,
+    OFFSET_CONVENTION,
+};
+#[cfg(feature = "std")]
+pub use open::OpenBuilder;
+#[cfg(feature = "std")]
+pub use perout::PerOutConfig;
+#[cfg(feature = "std")]
+pub use pin::{PinCapabilities, PinDescriptor, PinFunction};
+#[cfg(feature = "std")]
+pub use pps::{PpsData, PpsDevice, PpsPolarity};
+#[cfg(all(feature = "test-util", feature = "serde"))]
+pub use replay::{read_recording, write_recording};
+#[cfg(feature = "test-util")]
+pub use replay::{record_offsets, PtpBackend, ReplayDevice};
+#[cfg(feature = "std")]
+pub use sampling::{OffsetSamples, SampleSchedule};
+#[cfg(feature = "std")]
+pub use servo::{Discipline, DisciplineConfig, DisciplineStatus, FrequencyCorrection, PiServo};
+#[cfg(feature = "std")]
+pub use snapshot::DeviceSnapshot;
+#[cfg(feature = "std")]
+pub use soak::{Anomaly, SoakConfig, SoakReport, SoakTest};
+#[cfg(feature = "std")]
+pub use sysfs::{describe_device, find_device, list_clock_report, list_devices_with_caps, ClockReport, DriverInfo};
+pub use time::UtcParts;
+#[cfg(feature = "timecard")]
+pub use timecard::TimeCard;
+#[cfg(feature = "std")]
+pub use timescale::{Epoch, TimescaleMode};
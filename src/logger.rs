@@ -0,0 +1,151 @@
+//! Streaming of offset measurements to files and pipes
+
+use std::io::{Result, Write};
+
+use crate::OffsetMeasurement;
+
+/// Writes offset measurements as newline-delimited JSON, one object per line
+///
+/// Each object is a measurement in the form described in [`crate::wire`], preceded by a `device`
+/// field if one was set with [`MeasurementLogger::with_device`].  Each line is flushed as it is
+/// written, so the output can be followed with `tail -f` and processed with tools like `jq`.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct MeasurementLogger<W: Write> {
+    writer: W,
+    device: Option<String>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct LogRecord<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device: Option<&'a str>,
+    #[serde(flatten)]
+    measurement: &'a OffsetMeasurement,
+}
+
+#[cfg(feature = "serde")]
+impl<W: Write> MeasurementLogger<W> {
+    /// Create a logger writing to `writer`
+    pub fn new(writer: W) -> MeasurementLogger<W> {
+        MeasurementLogger { writer, device: None }
+    }
+
+    /// Include the given device path or clock name in every record
+    pub fn with_device(mut self, device: impl Into<String>) -> MeasurementLogger<W> {
+        self.device = Some(device.into());
+        self
+    }
+
+    /// Write one measurement as a line of JSON and flush it
+    pub fn log(&mut self, m: &OffsetMeasurement) -> Result<()> {
+        let record = LogRecord {
+            device: self.device.as_deref(),
+            measurement: m,
+        };
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+
+    /// Consume the logger, returning the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Header row written by [`CsvLogger`]
+pub const CSV_HEADER: &str = "unix_sec,unix_nsec,phc_sec,phc_nsec,offset_ns,delay_ns,method";
+
+/// Writes offset measurements as CSV, one row per measurement
+///
+/// The header row ([`CSV_HEADER`]) is written before the first measurement.  The `unix_*`
+/// columns are the measurement's system time.  Output is buffered by the underlying writer;
+/// call [`CsvLogger::flush`] to push it out.
+#[derive(Debug)]
+pub struct CsvLogger<W: Write> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> CsvLogger<W> {
+    /// Create a logger writing to `writer`
+    pub fn new(writer: W) -> CsvLogger<W> {
+        CsvLogger {
+            writer,
+            header_written: false,
+        }
+    }
+
+    /// Write one measurement as a CSV row, preceded by the header row if this is the first
+    pub fn log(&mut self, m: &OffsetMeasurement) -> Result<()> {
+        if !self.header_written {
+            writeln!(self.writer, "{CSV_HEADER}")?;
+            self.header_written = true;
+        }
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{}",
+            m.sys_time.sec,
+            m.sys_time.nsec,
+            m.phc_time.sec,
+            m.phc_time.nsec,
+            m.offset_ns,
+            m.delay_ns,
+            m.method.as_str()
+        )
+    }
+
+    /// Flush buffered output to the underlying writer
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    /// Consume the logger, returning the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ptp::ptp_clock_time, OffsetMethod};
+
+    fn sample() -> OffsetMeasurement {
+        OffsetMeasurement {
+            phc_time: ptp_clock_time { sec: 10, nsec: 5, reserved: 0 },
+            sys_time: ptp_clock_time { sec: 9, nsec: 7, reserved: 0 },
+            offset_ns: 999_999_998,
+            delay_ns: 40,
+            method: OffsetMethod::Extended,
+            mono: None,
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn writes_one_json_object_per_line() {
+        let mut logger = MeasurementLogger::new(Vec::new()).with_device("/dev/ptp0");
+        logger.log(&sample()).unwrap();
+        logger.log(&sample()).unwrap();
+        let output = String::from_utf8(logger.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"device":"/dev/ptp0","schema_version":1,"phc_time":{"sec":10,"nsec":5},"sys_time":{"sec":9,"nsec":7},"offset_ns":999999998,"delay_ns":40,"method":"extended"}"#
+        );
+    }
+
+    #[test]
+    fn writes_header_then_rows() {
+        let mut logger = CsvLogger::new(Vec::new());
+        logger.log(&sample()).unwrap();
+        logger.log(&sample()).unwrap();
+        let output = String::from_utf8(logger.into_inner()).unwrap();
+        let row = "9,7,10,5,999999998,40,extended";
+        assert_eq!(output, format!("{CSV_HEADER}\n{row}\n{row}\n"));
+    }
+}
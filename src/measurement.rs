@@ -0,0 +1,1316 @@
+//! Offset measurements between a PTP hardware clock and the system clock
+//!
+//! All three offset ioctls report PHC times on whatever timescale the PHC runs (usually TAI when
+//! synchronised by PTP).  The system times are `CLOCK_REALTIME` (UTC), with the precise ioctl
+//! additionally reporting `CLOCK_MONOTONIC_RAW`.  An offset measured against a PHC on TAI therefore
+//! includes the TAI - UTC offset; see [`ptp_clock_time::tai_to_utc`].
+//!
+//! Every offset in this crate follows [`OFFSET_CONVENTION`]: it is the PHC time minus the system
+//! time, so a positive offset means the PHC is ahead of the system clock.
+//!
+//! The raw kernel structures returned by the `get_sys_offset*` methods are also available wrapped
+//! as [`SysOffset`], [`ExtendedOffset`] and [`PreciseOffset`], which hide the ABI layout.
+
+use std::{
+    cmp::Ordering,
+    io::{Error, ErrorKind, Result},
+    sync::atomic::{compiler_fence, fence, Ordering as AtomicOrdering},
+    time::Duration,
+};
+
+use crate::{is_unsupported, ptp::*, PtpDevice};
+
+/// The sign convention used for every offset reported by this crate
+///
+/// Offsets are `phc_time - sys_time`: positive when the PHC is ahead of the system clock.  A servo
+/// correcting the PHC towards the system clock must therefore apply the negated offset, as
+/// [`crate::servo::PiServo`] does.  Some tools (for example `phc2sys` when disciplining the system
+/// clock) use the opposite sign.
+pub const OFFSET_CONVENTION: &str = "phc_minus_system";
+
+/// A conservative delay threshold for [`PtpDevice::measure_offset_confident_with`], in nanoseconds
+///
+/// An uncontended PCIe read of a PHC takes a few microseconds; samples delayed by more than this
+/// were very likely disturbed by an interrupt or preemption.
+pub const DEFAULT_MAX_DELAY_NS: i64 = 20_000;
+
+/// The ioctl which produced an offset measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OffsetMethod {
+    /// `PTP_SYS_OFFSET_PRECISE`: a cross-timestamp captured by the hardware
+    Precise,
+    /// `PTP_SYS_OFFSET_EXTENDED`: system timestamps taken by the driver immediately around the PHC read
+    Extended,
+    /// `PTP_SYS_OFFSET`: system timestamps taken by the PTP core around the driver's gettime call
+    Plain,
+}
+
+impl OffsetMethod {
+    /// Lower-case name of the method, as used in log output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OffsetMethod::Precise => "precise",
+            OffsetMethod::Extended => "extended",
+            OffsetMethod::Plain => "plain",
+        }
+    }
+}
+
+/// The system clock an offset is measured against
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SysClock {
+    /// `CLOCK_REALTIME`, the (possibly disciplined) wall clock
+    #[default]
+    Realtime,
+    /// `CLOCK_MONOTONIC_RAW`, the undisciplined hardware clock, useful for frequency analysis
+    MonotonicRaw,
+}
+
+/// A single offset measurement between the PHC and the system clock
+///
+/// With the `serde` feature this is serialized in the stable form described in the `wire` module.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(into = "crate::wire::MeasurementV1", try_from = "crate::wire::MeasurementV1")
+)]
+pub struct OffsetMeasurement {
+    /// Time read from the PHC
+    pub phc_time: ptp_clock_time,
+    /// System time corresponding to `phc_time`, from `CLOCK_REALTIME` unless otherwise requested
+    pub sys_time: ptp_clock_time,
+    /// Offset of the PHC from the system clock in nanoseconds, per [`OFFSET_CONVENTION`]
+    pub offset_ns: i64,
+    /// Width in nanoseconds of the system time window bracketing the PHC read
+    pub delay_ns: i64,
+    /// Method which produced this measurement
+    pub method: OffsetMethod,
+    /// `CLOCK_MONOTONIC` time of the measurement, if requested
+    ///
+    /// Only filled in by [`PtpDevice::measure_offset_mono`], to save a system call otherwise.
+    /// Unlike `sys_time`, this is unaffected by steps of the system clock, which makes it a
+    /// suitable x-axis for plotting offsets over time.
+    pub mono: Option<Duration>,
+}
+
+impl OffsetMeasurement {
+    /// Half the round-trip delay in nanoseconds, the maximum error of the offset
+    pub fn half_delay_ns(&self) -> i64 {
+        self.delay_ns / 2
+    }
+
+    /// The measurement as numeric fields for a metrics client, without needing serde
+    ///
+    /// The names and order are stable: `offset_ns`, `delay_ns`, `phc_unix_seconds` and
+    /// `sys_unix_seconds`.  The last two are `phc_time` and `sys_time` as fractional seconds since
+    /// their epochs, which for a PHC on TAI is the TAI epoch.  As `f64`, present-day times are
+    /// only resolved to about a quarter of a microsecond; use the offset for finer detail.
+    pub fn to_fields(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("offset_ns", self.offset_ns as f64),
+            ("delay_ns", self.delay_ns as f64),
+            ("phc_unix_seconds", self.phc_time.as_nanos() as f64 / 1e9),
+            ("sys_unix_seconds", self.sys_time.as_nanos() as f64 / 1e9),
+        ]
+    }
+
+    /// The offset corrected using a read latency from [`PtpDevice::calibrate_read_latency`]
+    ///
+    /// A plain measurement assumes the PHC was read at the midpoint of its system time window.
+    /// When the window is wider than usual, the extra time is mostly spent returning from the
+    /// ioctl, which pulls the midpoint late and the offset low.  This instead places the PHC read
+    /// half the calibrated `latency` after the start of the window.  Measurements from the precise
+    /// and extended methods are bracketed tightly by the kernel or driver and are returned
+    /// unchanged; they need no calibration.
+    pub fn latency_corrected_ns(&self, latency: Duration) -> i64 {
+        if self.method != OffsetMethod::Plain {
+            return self.offset_ns;
+        }
+        let latency_ns = latency.as_nanos().min(i64::MAX as u128) as i64;
+        self.offset_ns + (self.delay_ns - latency_ns) / 2
+    }
+
+    /// The offset as a direction and magnitude
+    ///
+    /// The direction compares the PHC with the system clock: `Ordering::Greater` when the PHC is
+    /// ahead, following [`OFFSET_CONVENTION`].
+    pub fn offset_signed(&self) -> (Ordering, Duration) {
+        (self.offset_ns.cmp(&0), Duration::from_nanos(self.offset_ns.unsigned_abs()))
+    }
+
+    /// Build a measurement from a PHC reading bracketed by two system clock readings
+    pub(crate) fn from_bracket(
+        pre: &ptp_clock_time,
+        phc: &ptp_clock_time,
+        post: &ptp_clock_time,
+        method: OffsetMethod,
+    ) -> OffsetMeasurement {
+        let sys_time = pre.midpoint(post);
+        OffsetMeasurement {
+            phc_time: *phc,
+            sys_time,
+            offset_ns: (phc.as_nanos() - sys_time.as_nanos()) as i64,
+            delay_ns: (post.as_nanos() - pre.as_nanos()) as i64,
+            method,
+            mono: None,
+        }
+    }
+}
+
+/// Estimate the device time at system time `at_sys` by interpolating between two measurements
+///
+/// The device time is assumed to advance linearly relative to the system clock between the two
+/// measurements.  If `at_sys` falls outside `m_before.sys_time..=m_after.sys_time` the same line
+/// is extrapolated, with error growing with the distance from the bracket.  If both measurements
+/// share a system time, the offset of `m_before` is applied unchanged.
+pub fn interpolate_device_time(
+    m_before: &OffsetMeasurement,
+    m_after: &OffsetMeasurement,
+    at_sys: ptp_clock_time,
+) -> ptp_clock_time {
+    let sys_before = m_before.sys_time.as_nanos();
+    let sys_span = m_after.sys_time.as_nanos() - sys_before;
+    let elapsed = at_sys.as_nanos() - sys_before;
+    let phc_before = m_before.phc_time.as_nanos();
+    let phc_elapsed = if sys_span == 0 {
+        elapsed
+    } else {
+        let phc_span = m_after.phc_time.as_nanos() - phc_before;
+        elapsed * phc_span / sys_span
+    };
+    ptp_clock_time::from_nanos(phc_before + phc_elapsed)
+}
+
+/// Take one [`PtpDevice::measure_offset`] measurement of each of `devices`, back to back
+///
+/// The measurements are made in order with nothing in between, so the system clock they are
+/// referenced to moves as little as possible from one device to the next, and offsets of
+/// different devices can be compared with each other.  A failure on one device is returned in its
+/// place and does not stop the others being measured.
+pub fn collect_offsets(devices: &[&PtpDevice]) -> Vec<Result<OffsetMeasurement>> {
+    devices.iter().map(|device| device.measure_offset()).collect()
+}
+
+/// A median-based offset estimate which is insensitive to outlying samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RobustEstimate {
+    /// Median offset of the retained samples in nanoseconds
+    pub median_offset_ns: i64,
+    /// Interquartile range of the retained samples' offsets in nanoseconds
+    pub iqr_ns: i64,
+    /// Number of samples retained after discarding those with the largest delays
+    pub samples: usize,
+}
+
+/// The `k`th quartile (0 to 4) of a sorted, non-empty slice, interpolating linearly between ranks
+fn quartile(sorted: &[i64], k: usize) -> i64 {
+    let pos = k * (sorted.len() - 1);
+    let (i, frac) = (pos / 4, (pos % 4) as i64);
+    match frac {
+        0 => sorted[i],
+        _ => sorted[i] + (sorted[i + 1] - sorted[i]) * frac / 4,
+    }
+}
+
+/// A time of zero, for building all-zero request structures in const context
+const ZERO_TIME: ptp_clock_time = ptp_clock_time { sec: 0, nsec: 0, reserved: 0 };
+
+impl ptp_sys_offset {
+    /// An all-zero request, including the reserved words, ready for `n_samples` to be set
+    ///
+    /// The kernel rejects requests with non-zero reserved words, so requests are built from this
+    /// rather than relying on `Default` happening to zero them.
+    pub const fn zeroed() -> ptp_sys_offset {
+        ptp_sys_offset {
+            n_samples: 0,
+            rsv: [0; 3],
+            ts: [ZERO_TIME; 2 * PTP_MAX_SAMPLES as usize + 1],
+        }
+    }
+
+    /// Number of samples filled in by the kernel
+    ///
+    /// This is the `n_samples` written back by the ioctl, which is authoritative even if it
+    /// differs from the count requested, bounded to the size of the sample array.
+    pub fn valid_samples(&self) -> usize {
+        (self.n_samples as usize).min(PTP_MAX_SAMPLES as usize)
+    }
+
+    /// The timestamps filled in by the kernel, as `sys, phc, sys, phc, ..., sys`
+    ///
+    /// The slice holds `2 * n_samples + 1` timestamps: each sample's system and PHC readings,
+    /// followed by the closing system reading which brackets the last PHC reading.
+    pub fn valid_timestamps(&self) -> &[ptp_clock_time] {
+        &self.ts[..2 * self.valid_samples() + 1]
+    }
+
+    /// Offset measurements for each sample filled in by the kernel
+    ///
+    /// The kernel lays the timestamps out as `sys, phc, sys, phc, ..., sys`, so each PHC reading
+    /// is bracketed by the system readings either side of it.
+    pub fn samples(&self) -> impl Iterator<Item = OffsetMeasurement> + '_ {
+        (0..self.valid_samples()).map(move |i| {
+            let [pre, phc, post] = [&self.ts[2 * i], &self.ts[2 * i + 1], &self.ts[2 * i + 2]];
+            OffsetMeasurement::from_bracket(pre, phc, post, OffsetMethod::Plain)
+        })
+    }
+
+    /// A 0-100 indication of how consistent the sample delays were, higher being better
+    ///
+    /// The score is `100 * min_delay / mean_delay` over the valid samples, rounded to the nearest
+    /// integer.  Samples which all took the same time score 100; a batch where most reads were
+    /// delayed by preemption or bus contention relative to the best one scores low.  Returns `None`
+    /// if there are no valid samples or the mean delay is not positive.
+    pub fn quality_score(&self) -> Option<u8> {
+        let delays: Vec<i64> = self.samples().map(|m| m.delay_ns).collect();
+        let min = *delays.iter().min()?;
+        let mean = delays.iter().map(|&d| d as f64).sum::<f64>() / delays.len() as f64;
+        if mean <= 0.0 || min < 0 {
+            return None;
+        }
+        Some((100.0 * min as f64 / mean).round() as u8)
+    }
+
+    /// The median and interquartile range of the offsets of the less delayed samples
+    ///
+    /// Samples whose delay is above the upper quartile of all delays are discarded as likely to
+    /// have been disturbed, then the median and interquartile range of the remaining offsets are
+    /// computed.  Quartiles interpolate linearly between ranks.  Returns `None` if there are no
+    /// valid samples.
+    pub fn robust_offset(&self) -> Option<RobustEstimate> {
+        let mut delays = [0i64; PTP_MAX_SAMPLES as usize];
+        let mut offsets = [0i64; PTP_MAX_SAMPLES as usize];
+        let n = self.valid_samples();
+        if n == 0 {
+            return None;
+        }
+        for (i, m) in self.samples().enumerate() {
+            delays[i] = m.delay_ns;
+        }
+        delays[..n].sort_unstable();
+        let max_delay = quartile(&delays[..n], 3);
+
+        let mut kept = 0;
+        for m in self.samples().filter(|m| m.delay_ns <= max_delay) {
+            offsets[kept] = m.offset_ns;
+            kept += 1;
+        }
+        let offsets = &mut offsets[..kept];
+        offsets.sort_unstable();
+        Some(RobustEstimate {
+            median_offset_ns: quartile(offsets, 2),
+            iqr_ns: quartile(offsets, 3) - quartile(offsets, 1),
+            samples: kept,
+        })
+    }
+}
+
+impl ptp_sys_offset_extended {
+    /// An all-zero request, including the reserved words; see [`ptp_sys_offset::zeroed`]
+    pub const fn zeroed() -> ptp_sys_offset_extended {
+        ptp_sys_offset_extended {
+            n_samples: 0,
+            rsv: [0; 3],
+            ts: [[ZERO_TIME; 3]; PTP_MAX_SAMPLES as usize],
+        }
+    }
+
+    /// Number of samples filled in by the kernel
+    ///
+    /// This is the `n_samples` written back by the ioctl, which is authoritative even if it
+    /// differs from the count requested, bounded to the size of the sample array.
+    pub fn valid_samples(&self) -> usize {
+        (self.n_samples as usize).min(PTP_MAX_SAMPLES as usize)
+    }
+
+    /// The `[sys, phc, sys]` rows filled in by the kernel
+    pub fn valid_rows(&self) -> &[[ptp_clock_time; 3]] {
+        &self.ts[..self.valid_samples()]
+    }
+
+    /// Offset measurements for each `[sys, phc, sys]` sample filled in by the kernel
+    pub fn samples(&self) -> impl Iterator<Item = OffsetMeasurement> + '_ {
+        self.valid_rows()
+            .iter()
+            .map(|[pre, phc, post]| OffsetMeasurement::from_bracket(pre, phc, post, OffsetMethod::Extended))
+    }
+}
+
+impl ptp_sys_offset_precise {
+    /// An all-zero request, including the reserved words; see [`ptp_sys_offset::zeroed`]
+    pub const fn zeroed() -> ptp_sys_offset_precise {
+        ptp_sys_offset_precise {
+            device: ZERO_TIME,
+            sys_realtime: ZERO_TIME,
+            sys_monoraw: ZERO_TIME,
+            rsv: [0; 4],
+        }
+    }
+
+    /// Round-trip delay of the measurement in nanoseconds, which is always 0
+    ///
+    /// The device and system times in a cross-timestamp are captured simultaneously by the
+    /// hardware, so there is no bracketing window.  The kernel reports no uncertainty for the
+    /// capture; this exists so precise results can be handled like the sampled methods.
+    pub fn delay_ns(&self) -> i64 {
+        0
+    }
+
+    /// Half the round-trip delay in nanoseconds, the maximum error of the offset
+    pub fn half_delay_ns(&self) -> i64 {
+        self.delay_ns() / 2
+    }
+
+    /// Offset measurement against `CLOCK_REALTIME` for the cross-timestamp returned by the kernel
+    pub fn measurement(&self) -> OffsetMeasurement {
+        self.measurement_against(SysClock::Realtime)
+    }
+
+    /// Check that the cross-timestamp is plausible, failing with `ErrorKind::InvalidData` if not
+    ///
+    /// A real cross-timestamp has three non-zero, normalised times, and a realtime later than the
+    /// monotonic raw time, which only counts from boot.  A driver which fails to fill the
+    /// structure in, or fakes it, usually breaks one of these.  `monoraw_now` is a
+    /// `CLOCK_MONOTONIC_RAW` reading taken after the ioctl, which the cross-timestamp must not be
+    /// later than; it is not checked against a lower bound, since drivers may correlate against
+    /// a recent hardware snapshot rather than the moment of the call.
+    pub fn validate(&self, monoraw_now: ptp_clock_time) -> Result<()> {
+        let invalid = |reason: &str| {
+            Err(Error::new(ErrorKind::InvalidData, format!("bogus cross-timestamp: {reason}")))
+        };
+        for (name, t) in [("device", &self.device), ("realtime", &self.sys_realtime), ("monoraw", &self.sys_monoraw)] {
+            if t.sec == 0 && t.nsec == 0 {
+                return invalid(&format!("{name} time is zero"));
+            }
+            if t.sec < 0 || t.nsec >= 1_000_000_000 {
+                return invalid(&format!("{name} time {}.{:09} is not normalised", t.sec, t.nsec));
+            }
+        }
+        if self.sys_realtime.as_nanos() < self.sys_monoraw.as_nanos() {
+            return invalid("realtime is before boot");
+        }
+        if self.sys_monoraw.as_nanos() > monoraw_now.as_nanos() {
+            return invalid("monoraw time is in the future");
+        }
+        Ok(())
+    }
+
+    /// Offset measurement against the given system clock for the cross-timestamp returned by the kernel
+    pub fn measurement_against(&self, clock: SysClock) -> OffsetMeasurement {
+        let sys_time = match clock {
+            SysClock::Realtime => self.sys_realtime,
+            SysClock::MonotonicRaw => self.sys_monoraw,
+        };
+        OffsetMeasurement {
+            phc_time: self.device,
+            sys_time,
+            offset_ns: (self.device.as_nanos() - sys_time.as_nanos()) as i64,
+            delay_ns: self.delay_ns(),
+            method: OffsetMethod::Precise,
+            mono: None,
+        }
+    }
+}
+
+/// The result of a `PTP_SYS_OFFSET` burst
+#[derive(Debug, Clone, Copy)]
+pub struct SysOffset(ptp_sys_offset);
+
+impl SysOffset {
+    /// Number of samples filled in by the kernel
+    pub fn len(&self) -> usize {
+        self.0.valid_samples()
+    }
+
+    /// Whether the kernel filled in no samples
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Offset measurements for each sample
+    pub fn samples(&self) -> impl Iterator<Item = OffsetMeasurement> + '_ {
+        self.0.samples()
+    }
+
+    /// The sample with the smallest delay, if there is one
+    pub fn best(&self) -> Option<OffsetMeasurement> {
+        self.samples().min_by_key(|m| m.delay_ns)
+    }
+
+    /// The asymmetry of each sample's bracket, in the same order as [`SysOffset::samples`]
+    ///
+    /// This is [`ExtendedSample::asymmetry_ns`] for the system readings either side of each PHC
+    /// reading, with the same caveat that it includes twice the sample's offset.
+    pub fn asymmetries(&self) -> impl Iterator<Item = i64> + '_ {
+        let ts = self.0.valid_timestamps();
+        (0..self.len()).map(move |i| bracket_asymmetry(&ts[2 * i], &ts[2 * i + 1], &ts[2 * i + 2]))
+    }
+
+    /// See [`ptp_sys_offset::quality_score`]
+    pub fn quality_score(&self) -> Option<u8> {
+        self.0.quality_score()
+    }
+
+    /// The underlying kernel structure
+    pub fn raw(&self) -> &ptp_sys_offset {
+        &self.0
+    }
+}
+
+impl From<ptp_sys_offset> for SysOffset {
+    fn from(offset: ptp_sys_offset) -> SysOffset {
+        SysOffset(offset)
+    }
+}
+
+/// The result of a `PTP_SYS_OFFSET_EXTENDED` burst
+#[derive(Debug, Clone, Copy)]
+pub struct ExtendedOffset(ptp_sys_offset_extended);
+
+/// One `[pre, phc, post]` row of a `PTP_SYS_OFFSET_EXTENDED` result
+#[derive(Debug, Clone, Copy)]
+pub struct ExtendedSample {
+    /// System time read immediately before the PHC
+    pub pre: ptp_clock_time,
+    /// The PHC reading
+    pub phc: ptp_clock_time,
+    /// System time read immediately after the PHC
+    pub post: ptp_clock_time,
+}
+
+impl ExtendedSample {
+    /// The best estimate of the system time at the PHC read, halfway between `pre` and `post`
+    ///
+    /// The offset of the sample is `phc` minus this.
+    pub fn system_midpoint(&self) -> ptp_clock_time {
+        self.pre.midpoint(&self.post)
+    }
+
+    /// The time from `pre` to the PHC read minus the time from the PHC read to `post`, in
+    /// nanoseconds
+    ///
+    /// The PHC and the system clock count different times, so this is twice the sample's offset
+    /// plus the true asymmetry, and on its own mostly reflects the offset.  Differences between
+    /// the samples of one burst, or the value less twice a trusted offset such as that of the
+    /// burst's best sample, show how far each sample strays from the midpoint assumption.
+    pub fn asymmetry_ns(&self) -> i64 {
+        bracket_asymmetry(&self.pre, &self.phc, &self.post)
+    }
+
+    /// The offset of the PHC from the system clock in nanoseconds, `phc` minus the midpoint
+    pub fn offset_ns(&self) -> i64 {
+        self.measurement().offset_ns
+    }
+
+    /// The time from `pre` to `post` in nanoseconds, which bounds the error of the offset
+    pub fn delay_ns(&self) -> i64 {
+        (self.post.as_nanos() - self.pre.as_nanos()) as i64
+    }
+
+    /// The offset measurement for this sample
+    pub fn measurement(&self) -> OffsetMeasurement {
+        OffsetMeasurement::from_bracket(&self.pre, &self.phc, &self.post, OffsetMethod::Extended)
+    }
+}
+
+/// `(phc - pre) - (post - phc)` in nanoseconds; see [`ExtendedSample::asymmetry_ns`]
+fn bracket_asymmetry(pre: &ptp_clock_time, phc: &ptp_clock_time, post: &ptp_clock_time) -> i64 {
+    let phc = phc.as_nanos();
+    ((phc - pre.as_nanos()) - (post.as_nanos() - phc)) as i64
+}
+
+impl From<[ptp_clock_time; 3]> for ExtendedSample {
+    fn from([pre, phc, post]: [ptp_clock_time; 3]) -> ExtendedSample {
+        ExtendedSample { pre, phc, post }
+    }
+}
+
+impl ExtendedOffset {
+    /// Number of samples filled in by the kernel
+    pub fn len(&self) -> usize {
+        self.0.valid_samples()
+    }
+
+    /// Whether the kernel filled in no samples
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Offset measurements for each sample
+    pub fn samples(&self) -> impl Iterator<Item = OffsetMeasurement> + '_ {
+        self.0.samples()
+    }
+
+    /// The timestamps of each sample
+    pub fn raw_samples(&self) -> impl Iterator<Item = ExtendedSample> + '_ {
+        self.0.valid_rows().iter().copied().map(ExtendedSample::from)
+    }
+
+    /// The sample with the smallest delay, if there is one
+    pub fn best(&self) -> Option<OffsetMeasurement> {
+        self.samples().min_by_key(|m| m.delay_ns)
+    }
+
+    /// The underlying kernel structure
+    pub fn raw(&self) -> &ptp_sys_offset_extended {
+        &self.0
+    }
+}
+
+impl From<ptp_sys_offset_extended> for ExtendedOffset {
+    fn from(offset: ptp_sys_offset_extended) -> ExtendedOffset {
+        ExtendedOffset(offset)
+    }
+}
+
+/// The result of a `PTP_SYS_OFFSET_PRECISE` cross-timestamp
+#[derive(Debug, Clone, Copy)]
+pub struct PreciseOffset(ptp_sys_offset_precise);
+
+impl PreciseOffset {
+    /// The device time of the cross-timestamp
+    pub fn device_time(&self) -> ptp_clock_time {
+        self.0.device
+    }
+
+    /// The `CLOCK_REALTIME` time of the cross-timestamp
+    pub fn realtime(&self) -> ptp_clock_time {
+        self.0.sys_realtime
+    }
+
+    /// The `CLOCK_MONOTONIC_RAW` time of the cross-timestamp
+    pub fn monotonic_raw(&self) -> ptp_clock_time {
+        self.0.sys_monoraw
+    }
+
+    /// Offset measurement against `CLOCK_REALTIME`
+    pub fn measurement(&self) -> OffsetMeasurement {
+        self.0.measurement()
+    }
+
+    /// Offset measurement against the given system clock
+    pub fn measurement_against(&self, clock: SysClock) -> OffsetMeasurement {
+        self.0.measurement_against(clock)
+    }
+
+    /// The underlying kernel structure
+    pub fn raw(&self) -> &ptp_sys_offset_precise {
+        &self.0
+    }
+}
+
+impl From<ptp_sys_offset_precise> for PreciseOffset {
+    fn from(offset: ptp_sys_offset_precise) -> PreciseOffset {
+        PreciseOffset(offset)
+    }
+}
+
+/// The current `CLOCK_MONOTONIC` time
+fn monotonic_now() -> Result<Duration> {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // Safety: clock_gettime writes to a timespec, which lives for the duration of the call
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) } == -1 {
+        return Err(Error::last_os_error());
+    }
+    Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+/// Number of back-to-back reads from which [`PtpDevice::offset_against`] and
+/// [`PtpDevice::offset_symmetric`] pick the tightest
+const SOFTWARE_OFFSET_SAMPLES: u32 = 10;
+
+impl SysClock {
+    /// The POSIX clock id of this clock
+    fn clock_id(&self) -> libc::clockid_t {
+        match self {
+            SysClock::Realtime => libc::CLOCK_REALTIME,
+            SysClock::MonotonicRaw => libc::CLOCK_MONOTONIC_RAW,
+        }
+    }
+}
+
+/// The current time of the system clock `clock` in nanoseconds
+fn clock_now_ns(clock: libc::clockid_t) -> Result<i128> {
+    let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // Safety: clock_gettime writes to a timespec, which lives for the duration of the call
+    if unsafe { libc::clock_gettime(clock, &mut ts) } == -1 {
+        return Err(Error::last_os_error());
+    }
+    Ok(ts.tv_sec as i128 * 1_000_000_000 + ts.tv_nsec as i128)
+}
+
+/// Keep the clock reads of a user-space bracket in program order
+///
+/// The compiler fence stops the reads being reordered at compile time, and the full fence
+/// orders the memory accesses of the vDSO's clock reads on weakly ordered CPUs such as ARM.  The
+/// vDSO already serialises its own counter reads on x86 (`rdtsc_ordered`), and the PHC read is a
+/// system call, so this only tightens the bracket where nothing else orders it.
+#[inline(always)]
+fn bracket_fence() {
+    compiler_fence(AtomicOrdering::SeqCst);
+    fence(AtomicOrdering::SeqCst);
+}
+
+/// Simultaneous device, `CLOCK_REALTIME` and `CLOCK_MONOTONIC_RAW` times from
+/// [`PtpDevice::get_crosststamp`]
+#[derive(Debug, Clone, Copy)]
+pub struct CrossTimestamp {
+    /// The device time
+    pub device: ptp_clock_time,
+    /// The `CLOCK_REALTIME` time
+    pub realtime: ptp_clock_time,
+    /// The `CLOCK_MONOTONIC_RAW` time
+    pub monoraw: ptp_clock_time,
+    /// Whether the driver captured the times together in hardware
+    ///
+    /// This is inferred from the `cross_timestamping` capability, which the kernel sets when the
+    /// driver implements `getcrosststamp` (for example through PCIe PTM or ART).  When it is
+    /// false, the times were approximated in software and are only as consistent as the delay of
+    /// the read.
+    pub hardware: bool,
+}
+
+/// The results of measuring the offset with each method, from [`PtpDevice::compare_methods`]
+///
+/// A method's result is `None` if the driver does not support it, in which case it is also listed
+/// in `unsupported`.  The sampled methods report their sample with the smallest delay.
+#[derive(Debug, Clone)]
+pub struct MethodComparison {
+    /// The result of `PTP_SYS_OFFSET_PRECISE`
+    pub precise: Option<OffsetMeasurement>,
+    /// The best sample of `PTP_SYS_OFFSET_EXTENDED`
+    pub extended: Option<OffsetMeasurement>,
+    /// The best sample of `PTP_SYS_OFFSET`
+    pub plain: Option<OffsetMeasurement>,
+    /// The methods the driver does not support
+    pub unsupported: Vec<OffsetMethod>,
+}
+
+/// Restores the calling thread's CPU affinity when dropped
+struct AffinityGuard {
+    saved: libc::cpu_set_t,
+}
+
+impl AffinityGuard {
+    /// Restrict the calling thread to `cpu`, saving its current affinity
+    fn pin(cpu: usize) -> Result<AffinityGuard> {
+        if cpu >= libc::CPU_SETSIZE as usize {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("CPU {cpu} out of range")));
+        }
+        // Safety: cpu_set_t is a plain bit array, for which all zeroes is a valid (empty) set
+        let mut saved: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        // Safety: pid 0 is the calling thread, and saved is a cpu_set_t of the size passed
+        if unsafe { libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut saved) } == -1 {
+            return Err(Error::last_os_error());
+        }
+        // Safety: as for saved
+        let mut pinned: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        // Safety: cpu was checked to be within the set
+        unsafe { libc::CPU_SET(cpu, &mut pinned) };
+        // Safety: pid 0 is the calling thread, and pinned is a cpu_set_t of the size passed
+        if unsafe { libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &pinned) } == -1 {
+            return Err(Error::last_os_error());
+        }
+        Ok(AffinityGuard { saved })
+    }
+}
+
+impl Drop for AffinityGuard {
+    fn drop(&mut self) {
+        // Safety: pid 0 is the calling thread, and saved is a cpu_set_t of the size passed
+        if unsafe { libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &self.saved) } == -1 {
+            log_warn!("failed to restore CPU affinity: {}", Error::last_os_error());
+        }
+    }
+}
+
+/// The CPUs the calling thread may run on, which the kernel limits to those online
+fn allowed_cpus() -> Result<Vec<usize>> {
+    // Safety: as in AffinityGuard::pin
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    // Safety: pid 0 is the calling thread, and set is a cpu_set_t of the size passed
+    if unsafe { libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut set) } == -1 {
+        return Err(Error::last_os_error());
+    }
+    // Safety: the CPU index is within the set
+    Ok((0..libc::CPU_SETSIZE as usize).filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) }).collect())
+}
+
+/// `ns` rounded to the nearest whole second
+fn round_to_seconds(ns: i128) -> i64 {
+    (ns + 500_000_000).div_euclid(1_000_000_000) as i64
+}
+
+impl PtpDevice {
+    /// How many whole seconds the PHC is ahead of UTC, to tell whether it runs on UTC or TAI
+    ///
+    /// The PHC is compared with `CLOCK_REALTIME`, which is assumed to be UTC and synchronised to
+    /// well within half a second, and the result rounded to the nearest second.  A PHC on UTC
+    /// gives 0, and one on TAI gives the current TAI-UTC offset (37 seconds since 2017).  The
+    /// result is also compared with the system's own TAI-UTC offset, `CLOCK_TAI` minus
+    /// `CLOCK_REALTIME`, and a match logged at debug level.  That check is only meaningful when
+    /// something (such as `chronyd` or `ptp4l`) has set the kernel's TAI offset; otherwise
+    /// `CLOCK_TAI` equals `CLOCK_REALTIME`.  Other values suggest a PHC which has not been
+    /// synchronised at all.
+    pub fn timescale_offset_to_utc(&self) -> Result<i64> {
+        let tai_minus_utc = clock_now_ns(libc::CLOCK_TAI)? - clock_now_ns(libc::CLOCK_REALTIME)?;
+        let tai_offset = round_to_seconds(tai_minus_utc);
+        let offset = round_to_seconds(self.measure_offset()?.offset_ns as i128);
+        if offset == 0 {
+            log_debug!("PHC appears to be on UTC");
+        } else if offset == tai_offset {
+            log_debug!("PHC appears to be on TAI, {offset}s ahead of UTC");
+        } else {
+            log_debug!("PHC is {offset}s from UTC, but the system TAI offset is {tai_offset}s");
+        }
+        Ok(offset)
+    }
+
+    /// Take `n` samples with `PTP_SYS_OFFSET`; see [`PtpDevice::get_sys_offset_n`]
+    pub fn sys_offset(&self, n: u32) -> Result<SysOffset> {
+        self.get_sys_offset_n(n).map(SysOffset)
+    }
+
+    /// Fill `out` with the offsets in nanoseconds from a single `PTP_SYS_OFFSET` burst
+    ///
+    /// Up to `PTP_MAX_SAMPLES` samples are taken, no more than fit in `out`, and the number written
+    /// is returned.  Nothing is allocated, making this suitable for hot paths and benchmarks.
+    pub fn fill_offsets(&self, out: &mut [i64]) -> Result<usize> {
+        let n = out.len().min(PTP_MAX_SAMPLES as usize);
+        if n == 0 {
+            return Ok(0);
+        }
+        let offset = self.get_sys_offset_n(n as u32)?;
+        let mut written = 0;
+        for (slot, m) in out.iter_mut().zip(offset.samples()) {
+            *slot = m.offset_ns;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Take `n` samples with `PTP_SYS_OFFSET_EXTENDED`; see [`PtpDevice::get_sys_offset_extended_n`]
+    pub fn extended_offset(&self, n: u32) -> Result<ExtendedOffset> {
+        self.get_sys_offset_extended_n(n).map(ExtendedOffset)
+    }
+
+    /// Take `n` samples with `PTP_SYS_OFFSET_EXTENDED`, returning only the rows the kernel filled in
+    pub fn get_extended_samples(&self, n: u32) -> Result<Vec<ExtendedSample>> {
+        let offset = self.get_sys_offset_extended_n(n)?;
+        Ok(offset.valid_rows().iter().copied().map(ExtendedSample::from).collect())
+    }
+
+    /// The plain sample with the smallest delay from `bursts` bursts of `samples_per_burst` each
+    ///
+    /// A single burst can be entirely delayed by preemption or interrupts on a loaded host;
+    /// several bursts make it more likely that at least one sample was taken undisturbed.
+    pub fn best_offset_over(&self, bursts: u32, samples_per_burst: u32) -> Result<OffsetMeasurement> {
+        if bursts == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "burst count must be non-zero"));
+        }
+        let mut best: Option<OffsetMeasurement> = None;
+        for _ in 0..bursts {
+            if let Some(m) = self.sys_offset(samples_per_burst)?.best() {
+                if best.is_none_or(|b| m.delay_ns < b.delay_ns) {
+                    best = Some(m);
+                }
+            }
+        }
+        best.ok_or_else(|| Error::new(ErrorKind::InvalidData, "kernel returned no offset samples"))
+    }
+
+    /// The least delayed of a burst of `samples` samples, if at least half had a delay within
+    /// `max_delay_ns`
+    ///
+    /// See [`PtpDevice::measure_offset_confident_with`], which this calls with a minimum of half the
+    /// requested samples, rounded up.
+    pub fn measure_offset_confident(&self, samples: u32, max_delay_ns: i64) -> Result<Option<OffsetMeasurement>> {
+        self.measure_offset_confident_with(samples, max_delay_ns, samples.div_ceil(2))
+    }
+
+    /// The least delayed of a burst of `samples` samples, if at least `min_survivors` had a delay
+    /// within `max_delay_ns`
+    ///
+    /// The burst is taken with `PTP_SYS_OFFSET_EXTENDED`, or `PTP_SYS_OFFSET` where the driver
+    /// does not support it.  A burst in which too many samples were delayed was probably taken
+    /// while the host was disturbed, so even its best sample is not trusted; `None` means it
+    /// should be retried later.  [`DEFAULT_MAX_DELAY_NS`] is a threshold suitable for most PCIe
+    /// devices.  Fails with `ErrorKind::InvalidInput` if `min_survivors` is 0 or more than
+    /// `samples`.
+    pub fn measure_offset_confident_with(
+        &self,
+        samples: u32,
+        max_delay_ns: i64,
+        min_survivors: u32,
+    ) -> Result<Option<OffsetMeasurement>> {
+        if min_survivors == 0 || min_survivors > samples {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("minimum survivors must be between 1 and the sample count {samples}"),
+            ));
+        }
+        let burst: Vec<OffsetMeasurement> = match self.extended_offset(samples) {
+            Ok(offset) => offset.samples().collect(),
+            Err(e) if is_unsupported(&e) => self.sys_offset(samples)?.samples().collect(),
+            Err(e) => return Err(e),
+        };
+        let survivors: Vec<&OffsetMeasurement> = burst.iter().filter(|m| m.delay_ns <= max_delay_ns).collect();
+        if survivors.len() < min_survivors as usize {
+            log_debug!(
+                "only {} of {} samples within {max_delay_ns}ns delay, wanted {min_survivors}",
+                survivors.len(),
+                burst.len()
+            );
+            return Ok(None);
+        }
+        Ok(survivors.into_iter().min_by_key(|m| m.delay_ns).copied())
+    }
+
+    /// Measure the offset as [`PtpDevice::measure_offset`] does, tagged with `CLOCK_MONOTONIC`
+    ///
+    /// The monotonic time is the midpoint of readings taken immediately before and after the
+    /// measurement.
+    pub fn measure_offset_mono(&self) -> Result<OffsetMeasurement> {
+        let before = monotonic_now()?;
+        let mut m = self.measure_offset()?;
+        let after = monotonic_now()?;
+        m.mono = Some(before + (after - before) / 2);
+        Ok(m)
+    }
+
+    /// Measure the offset as [`PtpDevice::measure_offset`] does, with the calling thread pinned to
+    /// `cpu`
+    ///
+    /// Keeping the thread on one CPU for the measurement stops it being migrated in the middle of
+    /// an ioctl, which tightens the delays; on NUMA systems, a CPU local to the NIC also shortens
+    /// the PCIe reads themselves.  The thread's affinity is restored afterwards, even if the
+    /// measurement fails.  Fails with `EINVAL` if `cpu` is offline or outside the thread's cpuset,
+    /// and `EPERM` where a security policy forbids changing affinity.
+    pub fn measure_offset_pinned(&self, cpu: usize) -> Result<OffsetMeasurement> {
+        let _guard = AffinityGuard::pin(cpu)?;
+        self.measure_offset()
+    }
+
+    /// The least delayed plain offset measured from each CPU the calling thread may run on
+    ///
+    /// Returns `(cpu, offset_ns)` pairs in CPU order, from one [`PtpDevice::default_samples`] burst
+    /// on each CPU.  Differences between CPUs show the bias of measuring from a CPU far from the
+    /// NIC, to help choose where to run a measuring thread.  Offline CPUs, and those outside the
+    /// thread's cpuset, are skipped, as is any CPU which goes offline during the scan.  The
+    /// thread's affinity is restored afterwards.
+    pub fn offset_by_core(&self) -> Result<Vec<(usize, i64)>> {
+        let mut offsets = Vec::new();
+        for cpu in allowed_cpus()? {
+            let _guard = match AffinityGuard::pin(cpu) {
+                Ok(guard) => guard,
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+                    log_debug!("skipping CPU {cpu}, which is no longer available");
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            offsets.push((cpu, self.best_offset_over(1, self.default_samples())?.offset_ns));
+        }
+        Ok(offsets)
+    }
+
+    /// Offsets of the PHC from `CLOCK_REALTIME` and `CLOCK_MONOTONIC_RAW` from one cross-timestamp
+    ///
+    /// Returns `(device - realtime, device - monoraw)` in nanoseconds.  Both share the same device
+    /// timestamp, so e.g. a servo tracking realtime and a log of raw drift see the same capture.
+    pub fn get_precise_offsets(&self) -> Result<(i64, i64)> {
+        let offset = self.get_sys_offset_precise()?;
+        Ok((
+            offset.measurement_against(SysClock::Realtime).offset_ns,
+            offset.measurement_against(SysClock::MonotonicRaw).offset_ns,
+        ))
+    }
+
+    /// Estimate the characteristic system time window of a `PTP_SYS_OFFSET` read
+    ///
+    /// This is the median delay over a burst of `samples` plain measurements, which should be
+    /// taken while the host is quiet.  Store it and pass it to
+    /// [`OffsetMeasurement::latency_corrected_ns`] to reduce the bias of later plain
+    /// measurements taken on a busy host.
+    pub fn calibrate_read_latency(&self, samples: u32) -> Result<Duration> {
+        let offset = self.get_sys_offset_n(samples)?;
+        let mut delays: Vec<i64> = offset.samples().map(|m| m.delay_ns).collect();
+        if delays.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "kernel returned no offset samples"));
+        }
+        delays.sort_unstable();
+        Ok(Duration::from_nanos(quartile(&delays, 2).max(0) as u64))
+    }
+
+    /// The offset in nanoseconds of this PHC from `reference`, another PHC
+    ///
+    /// Both clocks are measured against `CLOCK_REALTIME` with [`PtpDevice::measure_offset`], which
+    /// uses the precise cross-timestamp where the driver supports it, and the difference is this
+    /// device's time minus the reference's.  The error is up to the sum of the two measurements'
+    /// half delays, which are zero for precise ones.  The system clock itself cancels out, apart
+    /// from its drift relative to the PHCs during the short interval between the measurements,
+    /// typically a few microseconds: a system clock off frequency by 100 ppm adds well under a
+    /// nanosecond.  For the best accuracy, measure in the order this device, reference, this
+    /// device, and compare the reference's offset with the mean of the other two, which cancels
+    /// the drift.
+    pub fn offset_to_reference(&self, reference: &PtpDevice) -> Result<i64> {
+        let own = self.measure_offset()?;
+        let other = reference.measure_offset()?;
+        Ok(own.offset_ns - other.offset_ns)
+    }
+
+    /// The offset in nanoseconds of the PHC from the system clock `clock`
+    ///
+    /// Against `CLOCK_REALTIME` this is [`PtpDevice::offset_ns`].  Against `CLOCK_MONOTONIC_RAW`
+    /// the `PTP_SYS_OFFSET_PRECISE` cross-timestamp is used where the driver supports it.
+    /// Otherwise, since the other offset ioctls only sample `CLOCK_REALTIME`, this falls back to
+    /// reading the system clock, the PHC, and the system clock again from user space, and takes the
+    /// tightest of several such reads.  Each of those reads is a separate system call, so the
+    /// fallback is considerably noisier than a measurement captured by the kernel, and is affected
+    /// by scheduling delays between the calls.  The reads are separated by memory fences so that
+    /// neither the compiler nor a weakly ordered CPU can move them into or out of the bracket.
+    pub fn offset_against(&self, clock: SysClock) -> Result<i64> {
+        if clock == SysClock::Realtime {
+            return self.offset_ns();
+        }
+        match self.get_sys_offset_precise() {
+            Ok(offset) => return Ok(offset.measurement_against(clock).offset_ns),
+            Err(e) if !is_unsupported(&e) => return Err(e),
+            Err(e) => log_debug!("precise offset unsupported ({e}), reading clocks from user space"),
+        }
+        let mut best: Option<(i128, i128)> = None;
+        for _ in 0..SOFTWARE_OFFSET_SAMPLES {
+            let before = clock_now_ns(clock.clock_id())?;
+            bracket_fence();
+            let phc = self.read_time()?.as_nanos();
+            bracket_fence();
+            let after = clock_now_ns(clock.clock_id())?;
+            let delay = after - before;
+            if best.is_none_or(|(best_delay, _)| delay < best_delay) {
+                best = Some((delay, phc - (before + delay / 2)));
+            }
+        }
+        // SOFTWARE_OFFSET_SAMPLES is non-zero, so there is always a best read
+        Ok(best.map_or(0, |(_, offset)| offset as i64))
+    }
+
+    /// The offset in nanoseconds of the PHC from `CLOCK_REALTIME`, from reads in both orders
+    ///
+    /// A bracket of three reads, system→PHC→system, estimates the offset assuming the middle
+    /// read happened halfway between the outer two.  If the gap before the middle read is longer
+    /// than the gap after it by `d`, for instance because the first call of a bracket runs with
+    /// cold caches, the estimate is off by `d / 2`.  The reverse bracket, PHC→system→PHC, is off
+    /// by `-d / 2` for the same `d`, so this takes the tightest of several brackets in each order
+    /// and returns the mean of the two, cancelling any gap asymmetry which does not depend on
+    /// which clock is read in the middle.  One which does, such as the PHC latching its time late
+    /// in its read, shifts both estimates the same way and is not cancelled.  All the reads are
+    /// made from user space, so as for the fallback of [`PtpDevice::offset_against`] this is
+    /// noisier than a measurement captured by the kernel; it is for drivers without
+    /// cross-timestamping, whose sampled methods suffer the same asymmetry.
+    pub fn offset_symmetric(&self) -> Result<i64> {
+        let realtime = SysClock::Realtime.clock_id();
+        let tightest = |best: &mut Option<(i128, i128)>, delay: i128, offset: i128| {
+            if best.is_none_or(|(best_delay, _)| delay < best_delay) {
+                *best = Some((delay, offset));
+            }
+        };
+        let (mut forward, mut reverse) = (None, None);
+        for _ in 0..SOFTWARE_OFFSET_SAMPLES {
+            let before = clock_now_ns(realtime)?;
+            bracket_fence();
+            let phc = self.read_time()?.as_nanos();
+            bracket_fence();
+            let after = clock_now_ns(realtime)?;
+            tightest(&mut forward, after - before, phc - (before + (after - before) / 2));
+
+            let phc_before = self.read_time()?.as_nanos();
+            bracket_fence();
+            let sys = clock_now_ns(realtime)?;
+            bracket_fence();
+            let phc_after = self.read_time()?.as_nanos();
+            tightest(&mut reverse, phc_after - phc_before, phc_before + (phc_after - phc_before) / 2 - sys);
+        }
+        // SOFTWARE_OFFSET_SAMPLES is non-zero, so there is always a best read in each order
+        let (forward, reverse) = (forward.map_or(0, |(_, offset)| offset), reverse.map_or(0, |(_, offset)| offset));
+        Ok(((forward + reverse) / 2) as i64)
+    }
+
+    /// Read the PHC twice, back to back, returning both times in order
+    ///
+    /// This is the software analog of the interleaved capture of `PTP_SYS_OFFSET`, with the PHC
+    /// on both sides of the bracket: the midpoint of the pair is the device time of anything
+    /// observed while the pair was being read, to within half their difference.  That difference
+    /// is roughly the cost of one PHC read, and so also the resolution with which calling this
+    /// before and after a software operation can time it on the device timescale.  The reads are
+    /// separated by a memory fence so neither may be moved across the other.
+    pub fn read_time_pair(&self) -> Result<(ptp_clock_time, ptp_clock_time)> {
+        let first = self.read_time()?;
+        bracket_fence();
+        let second = self.read_time()?;
+        Ok((first, second))
+    }
+
+    /// Take a cross-timestamp with `PTP_SYS_OFFSET_PRECISE`
+    pub fn precise_offset(&self) -> Result<PreciseOffset> {
+        self.get_sys_offset_precise().map(PreciseOffset)
+    }
+
+    /// Measure the offset with every method the driver supports, for comparison
+    ///
+    /// The methods run one after another, so some of the difference between their offsets comes
+    /// from the clocks drifting between them; this is negligible unless the clocks are far apart
+    /// in frequency.  A consistent gap between the sampled methods and the precise one usually
+    /// reveals an asymmetric read latency.  Errors other than a method being unsupported are
+    /// returned.
+    pub fn compare_methods(&self) -> Result<MethodComparison> {
+        let mut unsupported = Vec::new();
+        let mut supported = |method: OffsetMethod, result: Result<Option<OffsetMeasurement>>| match result {
+            Ok(m) => Ok(m),
+            Err(e) if is_unsupported(&e) => {
+                log_debug!("{} offset unsupported ({e})", method.as_str());
+                unsupported.push(method);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        };
+        let precise = supported(
+            OffsetMethod::Precise,
+            self.get_sys_offset_precise().map(|offset| Some(offset.measurement())),
+        )?;
+        let extended = supported(
+            OffsetMethod::Extended,
+            self.get_sys_offset_extended().map(|offset| offset.samples().min_by_key(|m| m.delay_ns)),
+        )?;
+        let plain = supported(
+            OffsetMethod::Plain,
+            self.get_sys_offset().map(|offset| offset.samples().min_by_key(|m| m.delay_ns)),
+        )?;
+        Ok(MethodComparison {
+            precise,
+            extended,
+            plain,
+            unsupported,
+        })
+    }
+
+    /// Get a precise cross-timestamp, rejecting implausible results
+    ///
+    /// This is [`PtpDevice::get_sys_offset_precise`] checked with
+    /// [`ptp_sys_offset_precise::validate`], so that a broken driver's output fails with
+    /// `ErrorKind::InvalidData` instead of reaching a servo.
+    pub fn get_sys_offset_precise_validated(&self) -> Result<ptp_sys_offset_precise> {
+        let offset = self.get_sys_offset_precise()?;
+        let monoraw_now = ptp_clock_time::from_nanos(clock_now_ns(libc::CLOCK_MONOTONIC_RAW)?);
+        if let Err(e) = offset.validate(monoraw_now) {
+            log_warn!("{e}");
+            return Err(e);
+        }
+        Ok(offset)
+    }
+
+    /// Read the device, `CLOCK_REALTIME` and `CLOCK_MONOTONIC_RAW` times together, noting whether
+    /// they were correlated in hardware
+    ///
+    /// Devices reporting `cross_timestamping` in their capabilities are read with
+    /// `PTP_SYS_OFFSET_PRECISE`.  Others are read with [`PtpDevice::measure_offset`], taking the
+    /// midpoint of the best sample as the realtime, and the monotonic raw time is derived from it
+    /// using the current difference between the two system clocks.
+    pub fn get_crosststamp(&self) -> Result<CrossTimestamp> {
+        if self.cached_caps()?.cross_timestamping != 0 {
+            let precise = self.get_sys_offset_precise()?;
+            return Ok(CrossTimestamp {
+                device: precise.device,
+                realtime: precise.sys_realtime,
+                monoraw: precise.sys_monoraw,
+                hardware: true,
+            });
+        }
+        let m = self.measure_offset()?;
+        // Bracket the realtime read between two monotonic raw reads to centre the difference
+        let raw_before = clock_now_ns(libc::CLOCK_MONOTONIC_RAW)?;
+        let realtime = clock_now_ns(libc::CLOCK_REALTIME)?;
+        let raw_after = clock_now_ns(libc::CLOCK_MONOTONIC_RAW)?;
+        let raw_minus_realtime = raw_before + (raw_after - raw_before) / 2 - realtime;
+        Ok(CrossTimestamp {
+            device: m.phc_time,
+            realtime: m.sys_time,
+            monoraw: ptp_clock_time::from_nanos(m.sys_time.as_nanos() + raw_minus_realtime),
+            hardware: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[test]
+    fn collected_offsets_keep_per_device_errors() {
+        let (a, b) = (null_device(), null_device());
+        let results = collect_offsets(&[&a, &b]);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.is_err()));
+    }
+
+    #[test]
+    fn pinning_restores_affinity() {
+        let affinity = || allowed_cpus().unwrap();
+        let before = affinity();
+        {
+            let _guard = AffinityGuard::pin(before[0]).unwrap();
+            assert_eq!(affinity(), vec![before[0]]);
+        }
+        assert_eq!(affinity(), before);
+        assert!(AffinityGuard::pin(libc::CPU_SETSIZE as usize).is_err());
+
+        let device = null_device();
+        assert!(device.offset_by_core().is_err());
+        assert_eq!(affinity(), before);
+    }
+
+    #[test]
+    fn symmetric_offset_needs_a_readable_clock() {
+        let device = null_device();
+        assert!(device.offset_symmetric().is_err());
+    }
+
+    #[test]
+    fn asymmetry_is_relative_to_the_midpoint() {
+        let t = ptp_clock_time::from_nanos;
+        let sample = ExtendedSample { pre: t(1_000), phc: t(1_030), post: t(1_100) };
+        assert_eq!(sample.asymmetry_ns(), -40);
+        assert_eq!(sample.asymmetry_ns(), 2 * sample.offset_ns());
+        assert_eq!(sample.delay_ns(), 100);
+    }
+
+    #[test]
+    fn confident_offset_checks_survivor_count() {
+        let device = null_device();
+        for min_survivors in [0, 6] {
+            let e = device.measure_offset_confident_with(5, DEFAULT_MAX_DELAY_NS, min_survivors).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn bogus_cross_timestamps_are_rejected() {
+        let t = |sec, nsec| ptp_clock_time { sec, nsec, reserved: 0 };
+        let good = ptp_sys_offset_precise {
+            device: t(1_700_000_037, 5),
+            sys_realtime: t(1_700_000_000, 0),
+            sys_monoraw: t(100, 0),
+            ..Default::default()
+        };
+        assert!(good.validate(t(101, 0)).is_ok());
+        for bad in [
+            ptp_sys_offset_precise { device: t(0, 0), ..good },
+            ptp_sys_offset_precise { sys_realtime: t(50, 0), ..good },
+            ptp_sys_offset_precise { sys_monoraw: t(100, 1_000_000_000), ..good },
+        ] {
+            assert_eq!(bad.validate(t(101, 0)).unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+        assert!(good.validate(t(99, 0)).is_err());
+        let negative = ptp_sys_offset_precise { device: t(-5, 0), ..good };
+        assert!(negative.validate(t(101, 0)).unwrap_err().to_string().contains("-5.000000000 is not normalised"));
+    }
+
+    const LEAD_NS: i128 = 1_000_000;
+
+    fn t(ns: i128) -> ptp_clock_time {
+        ptp_clock_time::from_nanos(ns)
+    }
+
+    #[test]
+    fn phc_lead_gives_positive_offset() {
+        let sys = 1_700_000_000_000_000_000;
+
+        // sys, phc, sys, phc, sys with the PHC read midway between each pair of system reads
+        let mut plain = ptp_sys_offset::zeroed();
+        plain.n_samples = 2;
+        for i in 0..5 {
+            let ns = sys + 500 * i as i128;
+            plain.ts[i] = if i % 2 == 0 { t(ns) } else { t(ns + LEAD_NS) };
+        }
+        for m in plain.samples() {
+            assert_eq!(m.offset_ns, LEAD_NS as i64);
+            assert_eq!(m.delay_ns, 1000);
+        }
+
+        let mut extended = ptp_sys_offset_extended::zeroed();
+        extended.n_samples = 1;
+        extended.ts[0] = [t(sys), t(sys + 500 + LEAD_NS), t(sys + 1000)];
+        assert_eq!(extended.samples().next().unwrap().offset_ns, LEAD_NS as i64);
+
+        let mut precise = ptp_sys_offset_precise::zeroed();
+        precise.device = t(sys + LEAD_NS);
+        precise.sys_realtime = t(sys);
+        assert_eq!(precise.measurement().offset_ns, LEAD_NS as i64);
+        assert_eq!(
+            precise.measurement().offset_signed(),
+            (Ordering::Greater, Duration::from_millis(1))
+        );
+    }
+
+    #[test]
+    fn robust_offset_discards_delayed_samples() {
+        let mut plain = ptp_sys_offset::zeroed();
+        assert_eq!(plain.robust_offset(), None);
+
+        // (delay, offset) pairs; the last sample is heavily delayed with a wild offset
+        let samples = [(100, 10), (100, 20), (100, 30), (100, 40), (10_000, 50_000)];
+        let mut sys = 0;
+        plain.ts[0] = t(sys);
+        for (i, (delay, offset)) in samples.into_iter().enumerate() {
+            plain.ts[2 * i + 1] = t(sys + delay / 2 + offset);
+            sys += delay;
+            plain.ts[2 * i + 2] = t(sys);
+        }
+        plain.n_samples = samples.len() as u32;
+        assert_eq!(
+            plain.robust_offset(),
+            Some(RobustEstimate {
+                median_offset_ns: 25,
+                iqr_ns: 15,
+                samples: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn quality_score_reflects_delay_spread() {
+        let mut plain = ptp_sys_offset::zeroed();
+        assert_eq!(plain.quality_score(), None);
+
+        // Delays of 100, 100, 400: min 100, mean 200
+        plain.n_samples = 3;
+        for (i, ns) in [0, 50, 100, 150, 200, 400, 600].into_iter().enumerate() {
+            plain.ts[i] = t(ns);
+        }
+        assert_eq!(plain.quality_score(), Some(50));
+
+        plain.n_samples = 2;
+        assert_eq!(plain.quality_score(), Some(100));
+    }
+
+    #[test]
+    fn latency_correction_only_applies_to_plain_measurements() {
+        // PHC read 100ns into a window widened to 1000ns by a slow return
+        let mut m = OffsetMeasurement {
+            phc_time: t(LEAD_NS + 100),
+            sys_time: t(500),
+            offset_ns: LEAD_NS as i64 - 400,
+            delay_ns: 1000,
+            method: OffsetMethod::Plain,
+            mono: None,
+        };
+        assert_eq!(m.latency_corrected_ns(Duration::from_nanos(200)), LEAD_NS as i64);
+
+        m.method = OffsetMethod::Extended;
+        assert_eq!(m.latency_corrected_ns(Duration::from_nanos(200)), m.offset_ns);
+    }
+}
@@ -0,0 +1,213 @@
+//! A minimal generic netlink client for the ethtool queries which have no ioctl equivalent
+//!
+//! Only what [`crate::TimestampStats`] needs is implemented: resolving the `ethtool` family and
+//! one `ETHTOOL_MSG_TSINFO_GET` request.  Messages are built and parsed by hand in host byte
+//! order, as netlink uses, and every length in a reply is checked before it is used.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+/// `GENL_ID_CTRL` from linux/genetlink.h, the family which resolves family names
+const GENL_ID_CTRL: u16 = 0x10;
+/// `CTRL_CMD_GETFAMILY` from linux/genetlink.h
+const CTRL_CMD_GETFAMILY: u8 = 3;
+/// `CTRL_ATTR_FAMILY_ID` from linux/genetlink.h
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+/// `CTRL_ATTR_FAMILY_NAME` from linux/genetlink.h
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+
+/// `ETHTOOL_GENL_NAME` and `ETHTOOL_GENL_VERSION` from linux/ethtool_netlink.h
+const ETHTOOL_GENL_NAME: &[u8] = b"ethtool\0";
+const ETHTOOL_GENL_VERSION: u8 = 1;
+/// `ETHTOOL_MSG_TSINFO_GET` from linux/ethtool_netlink.h
+const ETHTOOL_MSG_TSINFO_GET: u8 = 25;
+/// `ETHTOOL_A_TSINFO_HEADER` and `ETHTOOL_A_TSINFO_STATS` from linux/ethtool_netlink.h; the
+/// latter was added in Linux 6.10
+const ETHTOOL_A_TSINFO_HEADER: u16 = 1;
+const ETHTOOL_A_TSINFO_STATS: u16 = 6;
+/// `ETHTOOL_A_HEADER_DEV_NAME` and `ETHTOOL_A_HEADER_FLAGS` from linux/ethtool_netlink.h
+const ETHTOOL_A_HEADER_DEV_NAME: u16 = 2;
+const ETHTOOL_A_HEADER_FLAGS: u16 = 3;
+/// `ETHTOOL_FLAG_STATS` from linux/ethtool_netlink.h, asking for statistics in the reply
+const ETHTOOL_FLAG_STATS: u32 = 1 << 2;
+/// `ETHTOOL_A_TS_STAT_*` from linux/ethtool_netlink.h
+const ETHTOOL_A_TS_STAT_TX_PKTS: u16 = 1;
+const ETHTOOL_A_TS_STAT_TX_LOST: u16 = 2;
+const ETHTOOL_A_TS_STAT_TX_ERR: u16 = 3;
+
+/// `NLA_F_NESTED` from linux/netlink.h, and the mask which strips it and `NLA_F_NET_BYTEORDER`
+const NLA_F_NESTED: u16 = 1 << 15;
+const NLA_TYPE_MASK: u16 = !(3 << 14);
+
+/// Size of `struct nlmsghdr`, `struct genlmsghdr` and `struct nlattr`
+const NLMSG_HDRLEN: usize = 16;
+const GENL_HDRLEN: usize = 4;
+const NLA_HDRLEN: usize = 4;
+
+/// Room for any reply to the requests made here
+const RECV_BUFFER: usize = 16 * 1024;
+
+/// Transmit timestamping counters of an interface, from `ETHTOOL_A_TSINFO_STATS`
+///
+/// Each counter is `None` if the driver does not maintain it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TxTimestampCounters {
+    pub(crate) packets: Option<u64>,
+    pub(crate) lost: Option<u64>,
+    pub(crate) errors: Option<u64>,
+}
+
+/// Round `len` up to the 4-byte alignment of netlink messages and attributes
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Append an attribute of type `kind` holding `payload`
+fn push_attr(buf: &mut Vec<u8>, kind: u16, payload: &[u8]) {
+    buf.extend_from_slice(&((NLA_HDRLEN + payload.len()) as u16).to_ne_bytes());
+    buf.extend_from_slice(&kind.to_ne_bytes());
+    buf.extend_from_slice(payload);
+    buf.resize(align(buf.len()), 0);
+}
+
+/// The attributes in `buf` as `(type, payload)`, stopping at the first malformed one
+fn attrs(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        let len = u16::from_ne_bytes(buf.get(..2)?.try_into().ok()?) as usize;
+        let kind = u16::from_ne_bytes(buf.get(2..4)?.try_into().ok()?);
+        let payload = buf.get(NLA_HDRLEN..len)?;
+        buf = buf.get(align(len)..).unwrap_or_default();
+        Some((kind & NLA_TYPE_MASK, payload))
+    })
+}
+
+/// The payload of the first attribute of type `kind` in `buf`
+fn find_attr(buf: &[u8], kind: u16) -> Option<&[u8]> {
+    attrs(buf).find(|&(k, _)| k == kind).map(|(_, payload)| payload)
+}
+
+/// A netlink unsigned integer attribute, which the kernel sends as 32 or 64 bits
+fn uint(payload: &[u8]) -> Option<u64> {
+    match payload.len() {
+        4 => Some(u32::from_ne_bytes(payload.try_into().ok()?).into()),
+        8 => Some(u64::from_ne_bytes(payload.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+/// Read the transmit timestamping counters from the attributes of a `TSINFO_GET` reply
+fn parse_tx_counters(reply: &[u8]) -> TxTimestampCounters {
+    let Some(stats) = find_attr(reply, ETHTOOL_A_TSINFO_STATS) else {
+        return TxTimestampCounters::default();
+    };
+    let counter = |kind| find_attr(stats, kind).and_then(uint);
+    TxTimestampCounters {
+        packets: counter(ETHTOOL_A_TS_STAT_TX_PKTS),
+        lost: counter(ETHTOOL_A_TS_STAT_TX_LOST),
+        errors: counter(ETHTOOL_A_TS_STAT_TX_ERR),
+    }
+}
+
+/// A generic netlink socket
+struct GenlSocket(OwnedFd);
+
+impl GenlSocket {
+    fn open() -> Result<GenlSocket> {
+        // Safety: socket takes no pointers; the result is checked before use
+        let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_GENERIC) };
+        if fd == -1 {
+            return Err(Error::last_os_error());
+        }
+        // Safety: fd is a newly created socket owned by nothing else
+        Ok(GenlSocket(unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+
+    /// Send command `cmd` of `family` with `attrs`, and return the attributes of the reply
+    fn request(&self, family: u16, cmd: u8, version: u8, attrs: &[u8]) -> Result<Vec<u8>> {
+        let len = NLMSG_HDRLEN + GENL_HDRLEN + attrs.len();
+        let mut msg = Vec::with_capacity(len);
+        msg.extend_from_slice(&(len as u32).to_ne_bytes());
+        msg.extend_from_slice(&family.to_ne_bytes());
+        msg.extend_from_slice(&(libc::NLM_F_REQUEST as u16).to_ne_bytes());
+        msg.extend_from_slice(&1u32.to_ne_bytes()); // sequence number
+        msg.extend_from_slice(&0u32.to_ne_bytes()); // port id, filled in by the kernel
+        msg.extend_from_slice(&[cmd, version, 0, 0]);
+        msg.extend_from_slice(attrs);
+        // Safety: msg is valid for msg.len() bytes for the duration of the call
+        if unsafe { libc::send(self.0.as_raw_fd(), msg.as_ptr().cast(), msg.len(), 0) } == -1 {
+            return Err(Error::last_os_error());
+        }
+
+        let mut reply = vec![0u8; RECV_BUFFER];
+        // Safety: reply is valid for reply.len() writable bytes for the duration of the call
+        let n = unsafe { libc::recv(self.0.as_raw_fd(), reply.as_mut_ptr().cast(), reply.len(), 0) };
+        if n == -1 {
+            return Err(Error::last_os_error());
+        }
+        let reply = &reply[..n as usize];
+        let malformed = || Error::new(ErrorKind::InvalidData, "malformed netlink reply");
+        let header = reply.get(..NLMSG_HDRLEN).ok_or_else(malformed)?;
+        let msg_len = u32::from_ne_bytes(header[..4].try_into().unwrap()) as usize;
+        let msg_type = u16::from_ne_bytes(header[4..6].try_into().unwrap());
+        let body = reply.get(NLMSG_HDRLEN..msg_len).ok_or_else(malformed)?;
+        if msg_type == libc::NLMSG_ERROR as u16 {
+            let errno = i32::from_ne_bytes(body.get(..4).ok_or_else(malformed)?.try_into().unwrap());
+            return Err(Error::from_raw_os_error(-errno));
+        }
+        Ok(body.get(GENL_HDRLEN..).ok_or_else(malformed)?.to_vec())
+    }
+
+    /// The id of the `ethtool` generic netlink family
+    fn ethtool_family(&self) -> Result<u16> {
+        let mut attrs = Vec::new();
+        push_attr(&mut attrs, CTRL_ATTR_FAMILY_NAME, ETHTOOL_GENL_NAME);
+        let reply = self.request(GENL_ID_CTRL, CTRL_CMD_GETFAMILY, 1, &attrs)?;
+        find_attr(&reply, CTRL_ATTR_FAMILY_ID)
+            .and_then(|id| id.try_into().ok())
+            .map(u16::from_ne_bytes)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "ethtool family reply has no id"))
+    }
+}
+
+/// The transmit timestamping counters of `ifname`, from an `ETHTOOL_MSG_TSINFO_GET` request
+/// with statistics
+///
+/// Kernels before 6.10, and drivers which keep no counters, leave them all `None`.  Fails if
+/// ethtool netlink is unavailable or rejects the request.
+pub(crate) fn tx_timestamp_counters(ifname: &str) -> Result<TxTimestampCounters> {
+    let socket = GenlSocket::open()?;
+    let family = socket.ethtool_family()?;
+    let mut name = ifname.as_bytes().to_vec();
+    name.push(0);
+    let mut header = Vec::new();
+    push_attr(&mut header, ETHTOOL_A_HEADER_DEV_NAME, &name);
+    push_attr(&mut header, ETHTOOL_A_HEADER_FLAGS, &ETHTOOL_FLAG_STATS.to_ne_bytes());
+    let mut attrs = Vec::new();
+    push_attr(&mut attrs, ETHTOOL_A_TSINFO_HEADER | NLA_F_NESTED, &header);
+    let reply = socket.request(family, ETHTOOL_MSG_TSINFO_GET, ETHTOOL_GENL_VERSION, &attrs)?;
+    Ok(parse_tx_counters(&reply))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_are_read_from_the_stats_nest() {
+        let mut stats = Vec::new();
+        push_attr(&mut stats, ETHTOOL_A_TS_STAT_TX_PKTS, &7u64.to_ne_bytes());
+        push_attr(&mut stats, ETHTOOL_A_TS_STAT_TX_ERR, &2u32.to_ne_bytes());
+        let mut reply = Vec::new();
+        push_attr(&mut reply, 5, &3u32.to_ne_bytes());
+        push_attr(&mut reply, ETHTOOL_A_TSINFO_STATS | NLA_F_NESTED, &stats);
+        let counters = parse_tx_counters(&reply);
+        assert_eq!(counters, TxTimestampCounters { packets: Some(7), lost: None, errors: Some(2) });
+
+        assert_eq!(parse_tx_counters(&reply[..4]), TxTimestampCounters::default());
+        // An attribute claiming to be longer than the buffer ends the parse rather than panicking
+        reply[0] = 0xff;
+        assert_eq!(attrs(&reply).count(), 0);
+    }
+}
@@ -0,0 +1,185 @@
+//! Options for opening PTP devices
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Error, Result},
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+use crate::{PtpDevice, PtpError};
+
+/// Options controlling how a PTP device is opened
+///
+/// Created with [`PtpDevice::builder`].  By default devices are opened read-only, blocking, and
+/// close-on-exec.  Configuring pins, channels, or adjusting the clock requires read-write access.
+#[derive(Debug, Clone)]
+pub struct OpenBuilder {
+    read_write: bool,
+    nonblocking: bool,
+    cloexec: bool,
+    check_abi: bool,
+}
+
+impl Default for OpenBuilder {
+    fn default() -> Self {
+        OpenBuilder {
+            read_write: false,
+            nonblocking: false,
+            cloexec: true,
+            check_abi: false,
+        }
+    }
+}
+
+impl OpenBuilder {
+    /// Open the device for writing as well as reading
+    pub fn read_write(mut self, read_write: bool) -> OpenBuilder {
+        self.read_write = read_write;
+        self
+    }
+
+    /// Open the device in non-blocking mode, so event reads fail with `WouldBlock` rather than wait
+    pub fn nonblocking(mut self, nonblocking: bool) -> OpenBuilder {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Close the device automatically in child processes after `exec`
+    pub fn cloexec(mut self, cloexec: bool) -> OpenBuilder {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Check the device with [`PtpDevice::check_abi`] once it is open, failing the open if the
+    /// check fails
+    pub fn check_abi(mut self, check_abi: bool) -> OpenBuilder {
+        self.check_abi = check_abi;
+        self
+    }
+
+    /// Open the device at `path` with these options
+    ///
+    /// A missing device is reported as [`PtpError::DeviceNotFound`] and lack of access as
+    /// [`PtpError::PermissionDenied`].
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<PtpDevice> {
+        let mut options = OpenOptions::new();
+        options.read(true).write(self.read_write);
+        if self.nonblocking {
+            options.custom_flags(libc::O_NONBLOCK);
+        }
+        // std always opens files with O_CLOEXEC
+        let path = path.as_ref();
+        let file = options.open(path).map_err(|e| match e.raw_os_error() {
+            Some(libc::ENOENT) | Some(libc::ENODEV) | Some(libc::ENXIO) => PtpError::DeviceNotFound {
+                path: path.to_path_buf(),
+            }
+            .into(),
+            Some(libc::EACCES) | Some(libc::EPERM) => PtpError::PermissionDenied {
+                path: path.to_path_buf(),
+            }
+            .into(),
+            _ => e,
+        })?;
+        let device = PtpDevice::from_file(file);
+        if !self.cloexec {
+            device.set_cloexec(false)?;
+        }
+        if self.check_abi {
+            device.check_abi()?;
+        }
+        Ok(device)
+    }
+
+    /// Open `/dev/ptpN` with these options
+    pub fn open_index(&self, index: u32) -> Result<PtpDevice> {
+        self.open(format!("/dev/ptp{index}"))
+    }
+}
+
+impl PtpDevice {
+    /// Options for opening a PTP device with non-default flags
+    pub fn builder() -> OpenBuilder {
+        OpenBuilder::default()
+    }
+
+    /// Set or clear close-on-exec, so that child processes do or do not inherit the device
+    ///
+    /// Devices opened by path, through [`PtpDevice::new`] or an [`OpenBuilder`], are
+    /// close-on-exec unless [`OpenBuilder::cloexec`] says otherwise.  Those created with
+    /// `TryFrom<File>` or `FromRawFd` keep whatever the descriptor had: files opened by std are
+    /// close-on-exec, but descriptors received from another process or created by C code often
+    /// are not, so daemons which spawn children should set it explicitly.
+    pub fn set_cloexec(&self, enabled: bool) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+        // Safety: F_GETFD takes no argument and the fd is valid for the lifetime of self
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+        if flags == -1 {
+            return Err(Error::last_os_error());
+        }
+        let flags = if enabled { flags | libc::FD_CLOEXEC } else { flags & !libc::FD_CLOEXEC };
+        // Safety: F_SETFD takes an integer argument and the fd is valid for the lifetime of self
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, flags) } == -1 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<File> for PtpDevice {
+    type Error = Error;
+
+    /// Use an already open PTP device, e.g. one opened with flags [`OpenBuilder`] does not offer
+    ///
+    /// The file is checked with `PTP_CLOCK_GETCAPS` and rejected with [`PtpError::NotPtpDevice`]
+    /// if it is not a PTP clock.  Any open flags are honoured: on a file opened with
+    /// `O_NONBLOCK`, ioctls work as usual and event reads fail with `ErrorKind::WouldBlock`
+    /// rather than wait.
+    fn try_from(file: File) -> Result<PtpDevice> {
+        let fd = file.as_raw_fd();
+        let path = fs::read_link(format!("/proc/self/fd/{fd}")).unwrap_or_else(|_| PathBuf::from(format!("fd {fd}")));
+        PtpDevice::from_file(file).validated(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::ErrorKind, os::fd::FromRawFd};
+
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[test]
+    fn non_ptp_file_is_rejected() {
+        let Err(e) = PtpDevice::try_from(File::open("/dev/null").unwrap()) else {
+            panic!("/dev/null accepted as a PTP device");
+        };
+        assert!(matches!(
+            PtpError::from_io_error(&e),
+            Some(PtpError::NotPtpDevice { path }) if path == Path::new("/dev/null")
+        ));
+    }
+
+    #[test]
+    fn nonblocking_read_would_block() {
+        let mut fds = [0; 2];
+        // Safety: fds has room for the two descriptors pipe2 returns
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) }, 0);
+        // Safety: both descriptors were just created and are owned by nothing else
+        let (read, _write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        let device = PtpDevice::from_file(read);
+        assert_eq!(device.read_extts_event().unwrap_err().kind(), ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn cloexec_can_be_toggled() {
+        let device = null_device();
+        // Safety: F_GETFD takes no argument and the fd is valid for the lifetime of device
+        let cloexec = || unsafe { libc::fcntl(device.file.as_raw_fd(), libc::F_GETFD) } & libc::FD_CLOEXEC != 0;
+        assert!(cloexec());
+        device.set_cloexec(false).unwrap();
+        assert!(!cloexec());
+        device.set_cloexec(true).unwrap();
+        assert!(cloexec());
+    }
+}
@@ -0,0 +1,282 @@
+//! Periodic output (PEROUT) configuration
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    time::{Duration, SystemTime},
+};
+
+use crate::{lock, ptp::*, PtpDevice, Reserved, PTP_PEROUT_REQUEST2};
+
+/// Minimum lead time between reading the clock and the start of a periodic output
+const PEROUT_START_LEAD_NS: i128 = 1_000_000_000;
+
+/// Settings for one periodic output channel
+#[derive(Debug, Clone, Copy)]
+pub struct PerOutConfig {
+    /// Periodic output channel
+    pub channel: u32,
+    /// Time between the starts of successive pulses
+    pub period: Duration,
+    /// Device time of the first pulse; see [`PtpDevice::request_perout_every`] for the default
+    pub start: Option<ptp_clock_time>,
+    /// Length of each pulse, using `PTP_PEROUT_DUTY_CYCLE`; the driver's default if `None`
+    pub on_time: Option<Duration>,
+}
+
+impl PerOutConfig {
+    /// Output with the given period, default start time and default pulse width
+    pub fn new(channel: u32, period: Duration) -> PerOutConfig {
+        PerOutConfig {
+            channel,
+            period,
+            start: None,
+            on_time: None,
+        }
+    }
+}
+
+impl From<&ptp_perout_request> for PerOutConfig {
+    /// The configuration a request sets up
+    ///
+    /// With `PTP_PEROUT_PHASE` the request holds a phase rather than a start time, so `start` is
+    /// `None`; `on_time` is only set with `PTP_PEROUT_DUTY_CYCLE`.
+    fn from(request: &ptp_perout_request) -> PerOutConfig {
+        // Safety: every variant of both unions is a ptp_clock_time, so any of them can be read
+        let (start, on) = unsafe { (request.__bindgen_anon_1.start, request.__bindgen_anon_2.on) };
+        PerOutConfig {
+            channel: request.index,
+            period: request.period.to_duration().unwrap_or_default(),
+            start: (request.flags & PTP_PEROUT_PHASE == 0).then_some(start),
+            on_time: (request.flags & PTP_PEROUT_DUTY_CYCLE != 0).then(|| on.to_duration().unwrap_or_default()),
+        }
+    }
+}
+
+/// The first multiple of `period` at least [`PEROUT_START_LEAD_NS`] after device time `now`
+fn default_start(now: &ptp_clock_time, period: Duration) -> ptp_clock_time {
+    let period_ns = period.as_nanos() as i128;
+    let earliest = now.as_nanos() + PEROUT_START_LEAD_NS;
+    ptp_clock_time::from_nanos((earliest + period_ns - 1).div_euclid(period_ns) * period_ns)
+}
+
+impl PtpDevice {
+    /// Configure a periodic output channel from a raw request
+    ///
+    /// A request with a zero period disables the channel.  Any reserved words in the request are
+    /// zeroed before it is passed to the kernel.
+    pub fn request_perout(&self, request: &ptp_perout_request) -> Result<()> {
+        let mut request = *request;
+        request.clear_reserved();
+        let _span = trace_span!("ptp.perout_request");
+        // Safety: PTP_PEROUT_REQUEST2 reads a ptp_perout_request, which lives for the duration of the call
+        unsafe { self.ioctl_request("PTP_PEROUT_REQUEST2", PTP_PEROUT_REQUEST2, &mut request)? };
+        self.record_perout(&request);
+        Ok(())
+    }
+
+    /// Track the output `request` has just set up, for [`PtpDevice::get_perout`] and cleanup
+    ///
+    /// A one-shot pulse replaces any periodic output on the channel and leaves it idle once it
+    /// has fired, so it is tracked like a disable: its period is only a placeholder.
+    fn record_perout(&self, request: &ptp_perout_request) {
+        let mut resources = lock(&self.resources);
+        let disabled = request.period.sec == 0 && request.period.nsec == 0;
+        if disabled || request.flags & PTP_PEROUT_ONE_SHOT != 0 {
+            resources.perout.remove(&request.index);
+        } else {
+            resources.perout.insert(request.index, PerOutConfig::from(request));
+        }
+    }
+
+    /// Disable the periodic output on channel `chan`
+    pub fn disable_perout(&self, chan: u32) -> Result<()> {
+        self.request_perout(&ptp_perout_request {
+            index: chan,
+            ..Default::default()
+        })
+    }
+
+    /// Periodic output channels currently enabled through this handle, in ascending order
+    pub fn active_perout_channels(&self) -> Vec<u32> {
+        lock(&self.resources).perout.keys().copied().collect()
+    }
+
+    /// The configuration of periodic output channel `chan`, if it was enabled through this handle
+    ///
+    /// The kernel has no way to read back a periodic output: the `period` attribute in sysfs is
+    /// write-only, and there is no ioctl.  This is therefore the configuration last applied
+    /// through this handle, with the start time that was requested, and knows nothing of outputs
+    /// configured by other processes or handles, or of drivers adjusting what was requested.
+    /// One-shot pulses are not reported.  It currently never fails, but returns a `Result` so
+    /// that a readback from drivers which gain one can be added.
+    pub fn get_perout(&self, chan: u32) -> Result<Option<PerOutConfig>> {
+        Ok(lock(&self.resources).perout.get(&chan).copied())
+    }
+
+    /// Disable every periodic output channel enabled through this handle
+    ///
+    /// All channels are attempted even if some fail; the first error is returned.
+    pub fn disable_all_perout(&self) -> Result<()> {
+        let mut result = Ok(());
+        for chan in self.active_perout_channels() {
+            if let Err(e) = self.disable_perout(chan) {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    /// Start a periodic output on channel `pin_chan` with the given period
+    ///
+    /// Sub-second periods are supported.  If `start` is `None`, the output starts at the first
+    /// multiple of `period` (counted from the clock's epoch) at least one second after the
+    /// current device time, so outputs with the same period line up with each other.
+    pub fn request_perout_every(&self, pin_chan: u32, period: Duration, start: Option<ptp_clock_time>) -> Result<()> {
+        self.configure_perout(&PerOutConfig {
+            start,
+            ..PerOutConfig::new(pin_chan, period)
+        })
+    }
+
+    /// Start a periodic output as described by `config`
+    pub fn configure_perout(&self, config: &PerOutConfig) -> Result<()> {
+        self.apply_perout(config, None)
+    }
+
+    /// Start several periodic outputs, returning the result for each
+    ///
+    /// Every configuration is attempted even if earlier ones fail.  The kernel applies them one
+    /// at a time, so the outputs do not change simultaneously and a failure part way through
+    /// leaves the earlier channels running.  Outputs without a start time share a single reading
+    /// of the device time, so those with the same period are aligned.  The outer error is only
+    /// returned if that reading fails, in which case nothing is applied.
+    pub fn configure_perouts(&self, configs: &[PerOutConfig]) -> Result<Vec<Result<()>>> {
+        let now = if configs.iter().any(|c| c.start.is_none()) {
+            Some(self.read_time()?)
+        } else {
+            None
+        };
+        Ok(configs.iter().map(|c| self.apply_perout(c, now.as_ref())).collect())
+    }
+
+    /// Apply `config`, with any default start time computed from device time `now`, read now if `None`
+    fn apply_perout(&self, config: &PerOutConfig, now: Option<&ptp_clock_time>) -> Result<()> {
+        if config.period.is_zero() {
+            return Err(Error::new(ErrorKind::InvalidInput, "periodic output period must be non-zero"));
+        }
+        let start = match (config.start, now) {
+            (Some(start), _) => start,
+            (None, Some(now)) => default_start(now, config.period),
+            (None, None) => default_start(&self.read_time()?, config.period),
+        };
+        let mut request = ptp_perout_request {
+            period: ptp_clock_time::from_duration(config.period),
+            index: config.channel,
+            ..Default::default()
+        };
+        request.__bindgen_anon_1.start = start;
+        if let Some(on_time) = config.on_time {
+            request.flags |= PTP_PEROUT_DUTY_CYCLE;
+            request.__bindgen_anon_2.on = ptp_clock_time::from_duration(on_time);
+        }
+        self.request_perout(&request)
+    }
+
+    /// Start a periodic output on channel `chan` with its first pulse at system time `start_utc`
+    ///
+    /// The start is converted to device time using a fresh [`PtpDevice::measure_offset`], so
+    /// outputs started this way on several devices line up with each other on the system clock's
+    /// timescale.  The alignment is only as good as the offset measurement, typically within its
+    /// half delay, plus any drift between the clocks after the measurement; for a PHC on TAI the
+    /// pulses follow UTC seconds, not TAI seconds.
+    pub fn request_perout_at_utc(&self, chan: u32, start_utc: SystemTime, period: Duration) -> Result<()> {
+        let since_epoch = start_utc
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "start time is before the Unix epoch"))?;
+        let offset = self.measure_offset()?;
+        let start = ptp_clock_time::from_nanos(since_epoch.as_nanos() as i128 + offset.offset_ns as i128);
+        self.request_perout_every(chan, period, Some(start))
+    }
+
+    /// Emit a single pulse on channel `chan` at device time `at`
+    ///
+    /// Uses the `PTP_PEROUT_ONE_SHOT` flag, which not all drivers support.  `at` must be later
+    /// than the current device time.
+    pub fn request_perout_oneshot(&self, chan: u32, at: ptp_clock_time) -> Result<()> {
+        if at.as_nanos() <= self.read_time()?.as_nanos() {
+            return Err(Error::new(ErrorKind::InvalidInput, "one-shot pulse time is not in the future"));
+        }
+        let mut request = ptp_perout_request {
+            // The kernel treats a zero period as a request to disable the output
+            period: ptp_clock_time::from_duration(Duration::from_secs(1)),
+            index: chan,
+            flags: PTP_PEROUT_ONE_SHOT,
+            ..Default::default()
+        };
+        request.__bindgen_anon_1.start = at;
+        self.request_perout(&request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[test]
+    fn config_is_recovered_from_request() {
+        let mut request = ptp_perout_request {
+            period: ptp_clock_time { sec: 1, nsec: 0, reserved: 0 },
+            index: 2,
+            flags: PTP_PEROUT_DUTY_CYCLE,
+            ..Default::default()
+        };
+        request.__bindgen_anon_1.start = ptp_clock_time { sec: 100, nsec: 0, reserved: 0 };
+        request.__bindgen_anon_2.on = ptp_clock_time { sec: 0, nsec: 1_000, reserved: 0 };
+        let config = PerOutConfig::from(&request);
+        assert_eq!((config.channel, config.period), (2, Duration::from_secs(1)));
+        assert_eq!(config.start.map(|start| start.sec), Some(100));
+        assert_eq!(config.on_time, Some(Duration::from_micros(1)));
+
+        request.flags = PTP_PEROUT_PHASE;
+        let config = PerOutConfig::from(&request);
+        assert!(config.start.is_none() && config.on_time.is_none());
+
+        let device = null_device();
+        assert!(device.get_perout(2).unwrap().is_none());
+    }
+
+    #[test]
+    fn one_shot_pulses_are_not_tracked_as_running() {
+        let device = null_device();
+        let mut request = ptp_perout_request {
+            period: ptp_clock_time { sec: 1, nsec: 0, reserved: 0 },
+            index: 1,
+            ..Default::default()
+        };
+        device.record_perout(&request);
+        assert_eq!(device.get_perout(1).unwrap().map(|config| config.period), Some(Duration::from_secs(1)));
+
+        request.flags = PTP_PEROUT_ONE_SHOT;
+        device.record_perout(&request);
+        assert!(device.get_perout(1).unwrap().is_none());
+        assert!(device.active_perout_channels().is_empty());
+    }
+
+    #[test]
+    fn clearing_reserved_keeps_duty_cycle_on_time() {
+        let mut request = ptp_perout_request::default();
+        request.__bindgen_anon_2.on = ptp_clock_time { sec: 0, nsec: 500, reserved: 0 };
+        assert!(!request.reserved_is_zero());
+
+        request.flags = PTP_PEROUT_DUTY_CYCLE;
+        assert!(request.reserved_is_zero());
+        request.clear_reserved();
+        // Safety: on was the field last written
+        assert_eq!(unsafe { request.__bindgen_anon_2.on.nsec }, 500);
+
+        request.flags = 0;
+        request.clear_reserved();
+        assert!(request.reserved_is_zero());
+    }
+}
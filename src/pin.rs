@@ -0,0 +1,318 @@
+//! Programmable pin configuration
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    os::raw::c_char,
+};
+
+use crate::{lock, ptp::*, PtpDevice, PtpError, PTP_PIN_GETFUNC2, PTP_PIN_SETFUNC2};
+
+/// The function assigned to a programmable pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PinFunction {
+    /// The pin is not in use
+    None,
+    /// External timestamp input
+    ExtTs,
+    /// Periodic signal output
+    PerOut,
+    /// Physical layer synchronisation
+    PhySync,
+}
+
+impl PinFunction {
+    /// The kernel's `ptp_pin_function` value
+    pub fn as_raw(&self) -> ptp_pin_function {
+        match self {
+            PinFunction::None => ptp_pin_function_PTP_PF_NONE,
+            PinFunction::ExtTs => ptp_pin_function_PTP_PF_EXTTS,
+            PinFunction::PerOut => ptp_pin_function_PTP_PF_PEROUT,
+            PinFunction::PhySync => ptp_pin_function_PTP_PF_PHYSYNC,
+        }
+    }
+
+    /// Convert a kernel `ptp_pin_function` value, if it is one this crate knows
+    #[allow(non_upper_case_globals)]
+    pub fn from_raw(func: ptp_pin_function) -> Option<PinFunction> {
+        match func {
+            ptp_pin_function_PTP_PF_NONE => Some(PinFunction::None),
+            ptp_pin_function_PTP_PF_EXTTS => Some(PinFunction::ExtTs),
+            ptp_pin_function_PTP_PF_PEROUT => Some(PinFunction::PerOut),
+            ptp_pin_function_PTP_PF_PHYSYNC => Some(PinFunction::PhySync),
+            _ => None,
+        }
+    }
+}
+
+/// A programmable pin's description and current configuration
+#[derive(Debug, Clone, Copy)]
+pub struct PinDescriptor(ptp_pin_desc);
+
+impl PinDescriptor {
+    /// The pin's name, up to the first NUL, with any invalid UTF-8 replaced
+    pub fn name(&self) -> String {
+        let bytes = self.name_bytes();
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..len]).into_owned()
+    }
+
+    /// The kernel's name buffer exactly as returned, including the NUL and anything after it
+    pub fn name_bytes(&self) -> &[u8; 64] {
+        // Safety: c_char and u8 have the same size and alignment, so the arrays have the same layout
+        unsafe { &*(&self.0.name as *const [c_char; 64] as *const [u8; 64]) }
+    }
+
+    /// The pin's index
+    pub fn index(&self) -> u32 {
+        self.0.index
+    }
+
+    /// The function currently assigned to the pin, if it is one this crate knows
+    pub fn function(&self) -> Option<PinFunction> {
+        PinFunction::from_raw(self.0.func)
+    }
+
+    /// The channel of the pin's current function
+    pub fn chan(&self) -> u32 {
+        self.0.chan
+    }
+
+    /// The underlying kernel structure
+    pub fn raw(&self) -> &ptp_pin_desc {
+        &self.0
+    }
+}
+
+/// The functions a programmable pin can perform
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PinCapabilities {
+    /// The pin can be unassigned; always true
+    pub none: bool,
+    /// The pin can be an external timestamp input
+    pub extts: bool,
+    /// The pin can be a periodic output
+    pub perout: bool,
+    /// The pin can carry physical layer synchronisation
+    pub physync: bool,
+}
+
+/// Pins are serialized with their decoded name, omitting the reserved words
+#[cfg(feature = "serde")]
+impl serde::Serialize for PinDescriptor {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("PinDescriptor", 4)?;
+        state.serialize_field("name", &self.name())?;
+        state.serialize_field("index", &self.0.index)?;
+        state.serialize_field("func", &self.0.func)?;
+        state.serialize_field("chan", &self.0.chan)?;
+        state.end()
+    }
+}
+
+impl From<ptp_pin_desc> for PinDescriptor {
+    fn from(desc: ptp_pin_desc) -> PinDescriptor {
+        PinDescriptor(desc)
+    }
+}
+
+impl From<PinDescriptor> for ptp_pin_desc {
+    fn from(desc: PinDescriptor) -> ptp_pin_desc {
+        desc.0
+    }
+}
+
+impl PtpDevice {
+    /// Get the description and current function of the pin at `index`
+    pub fn get_pin(&self, index: u32) -> Result<PinDescriptor> {
+        let mut desc = ptp_pin_desc {
+            index,
+            ..Default::default()
+        };
+        let _span = trace_span!("ptp.pin_getfunc");
+        // Safety: PTP_PIN_GETFUNC2 expects and writes to a ptp_pin_desc, which lives for the duration of the call
+        unsafe { self.ioctl_request("PTP_PIN_GETFUNC2", PTP_PIN_GETFUNC2, &mut desc)? };
+        Ok(PinDescriptor(desc))
+    }
+
+    /// Get the descriptions of all of the device's programmable pins
+    pub fn pins(&self) -> Result<Vec<PinDescriptor>> {
+        let caps = self.cached_caps()?;
+        (0..caps.n_pins.max(0) as u32).map(|index| self.get_pin(index)).collect()
+    }
+
+    /// Write a pin's function and channel without recording it as configured by this handle
+    fn write_pin(&self, index: u32, func: ptp_pin_function, chan: u32) -> Result<()> {
+        let mut desc = ptp_pin_desc {
+            index,
+            func,
+            chan,
+            ..Default::default()
+        };
+        let _span = trace_span!("ptp.pin_setfunc");
+        // Safety: PTP_PIN_SETFUNC2 reads a ptp_pin_desc, which lives for the duration of the call
+        unsafe { self.ioctl_request("PTP_PIN_SETFUNC2", PTP_PIN_SETFUNC2, &mut desc) }
+    }
+
+    /// Assign `func` on channel `chan` to the pin at `index`
+    pub fn set_pin(&self, index: u32, func: PinFunction, chan: u32) -> Result<()> {
+        self.write_pin(index, func.as_raw(), chan)?;
+        let mut resources = lock(&self.resources);
+        if func == PinFunction::None {
+            resources.pins.remove(&index);
+        } else {
+            resources.pins.insert(index);
+        }
+        Ok(())
+    }
+
+    /// The configuration of every programmable pin, for later use with
+    /// [`PtpDevice::restore_pin_layout`]
+    pub fn pin_layout(&self) -> Result<Vec<ptp_pin_desc>> {
+        Ok(self.pins()?.into_iter().map(ptp_pin_desc::from).collect())
+    }
+
+    /// Assign each pin in `layout` the function and channel it records
+    ///
+    /// Every pin is attempted even if some fail.  If any do, the error lists each failed pin and
+    /// has the kind of the first failure.
+    pub fn restore_pin_layout(&self, layout: &[ptp_pin_desc]) -> Result<()> {
+        let mut failures = Vec::new();
+        let mut kind = None;
+        for desc in layout {
+            let result = match PinFunction::from_raw(desc.func) {
+                Some(func) => self.set_pin(desc.index, func, desc.chan),
+                None => Err(Error::new(ErrorKind::InvalidInput, format!("unknown pin function {}", desc.func))),
+            };
+            if let Err(e) = result {
+                log_warn!("failed to restore pin {}: {e}", desc.index);
+                kind.get_or_insert(e.kind());
+                failures.push(format!("pin {}: {e}", desc.index));
+            }
+        }
+        match kind {
+            None => Ok(()),
+            Some(kind) => Err(Error::new(
+                kind,
+                format!("failed to restore pin layout ({})", failures.join("; ")),
+            )),
+        }
+    }
+
+    /// Assign the physical layer synchronisation function on channel `chan` to the pin at `index`
+    ///
+    /// On drivers implementing it (such as some Microchip and Intel PHYs), PHYSYNC routes a
+    /// signal from the PHY's synchronised recovered clock to the pin, typically for SyncE.  The
+    /// kernel offers no way to query which functions a pin supports, so the driver's rejection of
+    /// the request is reported as `ErrorKind::Unsupported`.
+    pub fn configure_physync(&self, pin_index: u32, chan: u32) -> Result<()> {
+        let caps = self.cached_caps()?;
+        if pin_index >= caps.n_pins as u32 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("pin {pin_index} out of range, device has {} pins", caps.n_pins),
+            ));
+        }
+        match self.set_pin(pin_index, PinFunction::PhySync, chan) {
+            Err(e) if matches!(PtpError::os_error(&e), Some(libc::EOPNOTSUPP) | Some(libc::EINVAL)) => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("pin {pin_index} does not support PHYSYNC on channel {chan}"),
+            )),
+            result => result,
+        }
+    }
+
+    /// Assign the external timestamp function on channel `chan` to the pin at `pin_index`, then
+    /// enable the channel
+    ///
+    /// `flags` are as for [`PtpDevice::enable_extts2`], with `PTP_ENABLE_FEATURE` implied.  The
+    /// pin is assigned first, since drivers check that a channel has a pin when it is enabled.  If
+    /// enabling the channel fails, the pin is returned to [`PinFunction::None`] so it is not left
+    /// half-configured, and the enable's error is returned.
+    pub fn enable_extts_on_pin(&self, pin_index: u32, chan: u32, flags: u32) -> Result<()> {
+        let caps = self.cached_caps()?;
+        if pin_index >= caps.n_pins as u32 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("pin {pin_index} out of range, device has {} pins", caps.n_pins),
+            ));
+        }
+        self.set_pin(pin_index, PinFunction::ExtTs, chan)?;
+        if let Err(e) = self.enable_extts2(chan, flags | PTP_ENABLE_FEATURE) {
+            if let Err(reset) = self.set_pin(pin_index, PinFunction::None, 0) {
+                log_warn!("failed to reset pin {pin_index} after enabling channel {chan} failed: {reset}");
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Determine which functions the pin at `index` can perform
+    ///
+    /// The kernel does not report the functions a pin supports, and sysfs only exposes the
+    /// current assignment, so this probes each function on channel 0 and lets the driver accept or
+    /// reject it.  Assigning a function can also unassign another pin using the same function and
+    /// channel, so the configuration of every pin is saved beforehand and restored afterwards.
+    /// Requires the device to be opened read-write, and briefly disturbs any signals on the pins.
+    pub fn pin_capabilities(&self, index: u32) -> Result<PinCapabilities> {
+        let caps = self.cached_caps()?;
+        let saved = self.pins()?;
+        if index as usize >= saved.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("pin {index} out of range, device has {} pins", saved.len()),
+            ));
+        }
+        let probe = |func: PinFunction| self.write_pin(index, func.as_raw(), 0).is_ok();
+        let result = PinCapabilities {
+            none: true,
+            extts: caps.n_ext_ts > 0 && probe(PinFunction::ExtTs),
+            perout: caps.n_per_out > 0 && probe(PinFunction::PerOut),
+            physync: probe(PinFunction::PhySync),
+        };
+        // Clear the probed pin first, so restoring it cannot displace another pin's function
+        let mut restored = self.write_pin(index, ptp_pin_function_PTP_PF_NONE, 0);
+        for desc in &saved {
+            if let Err(e) = self.write_pin(desc.0.index, desc.0.func, desc.0.chan) {
+                log_warn!("failed to restore pin {} after probing: {e}", desc.0.index);
+                restored = restored.and(Err(e));
+            }
+        }
+        restored.map(|_| result)
+    }
+
+    /// The function currently assigned to the pin at `index`
+    pub fn pin_function(&self, index: u32) -> Result<PinFunction> {
+        let desc = self.get_pin(index)?;
+        desc.function()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("unknown pin function {}", desc.0.func)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_name(bytes: &[u8]) -> PinDescriptor {
+        let mut desc = ptp_pin_desc::default();
+        for (dst, &src) in desc.name.iter_mut().zip(bytes) {
+            *dst = src as c_char;
+        }
+        PinDescriptor(desc)
+    }
+
+    #[test]
+    fn name_stops_at_nul() {
+        assert_eq!(with_name(b"SMA1\0junk").name(), "SMA1");
+    }
+
+    #[test]
+    fn name_without_nul_uses_whole_buffer() {
+        assert_eq!(with_name(&[b'x'; 64]).name().len(), 64);
+    }
+
+    #[test]
+    fn name_with_invalid_utf8_is_lossy() {
+        assert_eq!(with_name(b"GNSS\xff\0").name(), "GNSS\u{fffd}");
+    }
+}
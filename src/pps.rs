@@ -0,0 +1,231 @@
+//! PPS event reporting to the kernel's PPS subsystem, and reading the resulting PPS events
+
+use std::{
+    fs::File,
+    io::{Error, ErrorKind, Result},
+    os::{fd::AsRawFd, raw::c_ulong},
+    path::Path,
+    time::Duration,
+};
+
+use crate::{ioctl_error, lock, ptp::ptp_clock_time, PtpDevice, PtpError, PTP_ENABLE_PPS2};
+
+// PPS_FETCH is defined in linux/pps.h with a pointer as its size argument, so unlike the PTP
+// ioctls its value depends on the pointer width.
+// _IOWR('p', 0xa4, struct pps_fdata *) -> 0xc00870a4 (64-bit), 0xc00470a4 (32-bit)
+#[cfg(target_pointer_width = "64")]
+const PPS_FETCH: c_ulong = 0xc00870a4;
+#[cfg(target_pointer_width = "32")]
+const PPS_FETCH: c_ulong = 0xc00470a4;
+
+/// `pps_ktime.flags` bit marking a timeout as absent, so that `PPS_FETCH` waits indefinitely
+const PPS_TIME_INVALID: u32 = 1 << 0;
+
+/// `struct pps_ktime` from linux/pps.h
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct pps_ktime {
+    sec: i64,
+    nsec: i32,
+    flags: u32,
+}
+
+/// `struct pps_kinfo` from linux/pps.h
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct pps_kinfo {
+    assert_sequence: u32,
+    clear_sequence: u32,
+    assert_tu: pps_ktime,
+    clear_tu: pps_ktime,
+    current_mode: i32,
+}
+
+/// `struct pps_fdata` from linux/pps.h
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct pps_fdata {
+    info: pps_kinfo,
+    timeout: pps_ktime,
+}
+
+impl From<pps_ktime> for ptp_clock_time {
+    fn from(t: pps_ktime) -> ptp_clock_time {
+        ptp_clock_time {
+            sec: t.sec,
+            nsec: t.nsec as u32,
+            reserved: 0,
+        }
+    }
+}
+
+/// The latest events recorded by a kernel PPS source
+///
+/// The kernel counts assert (usually rising edge) and clear events separately.  Timestamps are
+/// taken from `CLOCK_REALTIME` when the event is reported; for a PHC's PPS source, that is when
+/// the PTP core handles the driver's PPS interrupt.
+#[derive(Debug, Clone, Copy)]
+pub struct PpsData {
+    /// Number of assert events seen by the source
+    pub assert_sequence: u32,
+    /// Time of the latest assert event
+    pub assert_time: ptp_clock_time,
+    /// Number of clear events seen by the source
+    pub clear_sequence: u32,
+    /// Time of the latest clear event
+    pub clear_time: ptp_clock_time,
+}
+
+impl PpsData {
+    /// Number of assert events missed between `previous` and this fetch
+    ///
+    /// Consecutive fetches each waiting for one pulse should see the assert sequence advance by
+    /// one; any larger gap is the number of pulses which were not fetched.  Handles the counter
+    /// wrapping.
+    pub fn missed_asserts_since(&self, previous: &PpsData) -> u32 {
+        self.assert_sequence.wrapping_sub(previous.assert_sequence).saturating_sub(1)
+    }
+}
+
+/// A kernel PPS source, `/dev/ppsN`
+#[derive(Debug)]
+pub struct PpsDevice {
+    file: File,
+}
+
+impl PpsDevice {
+    /// Open the PPS source at `path`
+    ///
+    /// A missing device is reported as [`PtpError::DeviceNotFound`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<PpsDevice> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => PtpError::DeviceNotFound {
+                path: path.to_path_buf(),
+            }
+            .into(),
+            _ => e,
+        })?;
+        Ok(PpsDevice { file })
+    }
+
+    /// Wait for the next event with `PPS_FETCH` and return the source's latest events
+    ///
+    /// With no timeout this blocks until an event arrives.  Otherwise it fails with
+    /// `ErrorKind::TimedOut` if no event arrives within `timeout`.
+    pub fn fetch(&self, timeout: Option<Duration>) -> Result<PpsData> {
+        let mut fdata = pps_fdata {
+            timeout: match timeout {
+                Some(t) => pps_ktime {
+                    sec: t.as_secs().min(i64::MAX as u64) as i64,
+                    nsec: t.subsec_nanos() as i32,
+                    flags: 0,
+                },
+                None => pps_ktime {
+                    flags: PPS_TIME_INVALID,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        };
+        let _span = trace_span!("pps.fetch");
+        // Safety: PPS_FETCH reads the timeout from and writes the events to a pps_fdata, which lives for the duration of the call
+        if unsafe { libc::ioctl(self.file.as_raw_fd(), PPS_FETCH as _, &mut fdata) } != 0 {
+            return Err(ioctl_error("PPS_FETCH"));
+        }
+        Ok(PpsData {
+            assert_sequence: fdata.info.assert_sequence,
+            assert_time: fdata.info.assert_tu.into(),
+            clear_sequence: fdata.info.clear_sequence,
+            clear_time: fdata.info.clear_tu.into(),
+        })
+    }
+}
+
+/// The edge of a PHC's internal once-per-second signal reported to the kernel PPS subsystem
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PpsPolarity {
+    /// The start of each second, where the pulse goes high
+    #[default]
+    Rising,
+    /// The end of each pulse, where it goes low
+    Falling,
+}
+
+impl PtpDevice {
+    /// Enable delivery of the PHC's once-per-second events, asking for the given edge
+    ///
+    /// `PTP_ENABLE_PPS` carries only an on/off flag, and the PTP core reports every internal PPS
+    /// event as an assert at the top of the second, so current kernels give drivers no way to
+    /// offer a choice of edge.  This therefore falls back to plain [`PtpDevice::enable_pps`], and
+    /// the driver reports its fixed edge, normally the rising one; asking for
+    /// [`PpsPolarity::Falling`] logs a warning since it cannot be honoured.  Check the hardware's
+    /// documentation before assuming which edge is reported, since a mismatch shifts every
+    /// timestamp by the pulse width.
+    pub fn enable_pps_with(&self, polarity: PpsPolarity) -> Result<()> {
+        if polarity == PpsPolarity::Falling {
+            log_warn!("PPS edge selection is not supported by the kernel, using the driver's default edge");
+        }
+        self.enable_pps(true)
+    }
+
+    /// Enable or disable delivery of the PHC's once-per-second events to its `/dev/ppsN` source
+    ///
+    /// The kernel requires `CAP_SYS_TIME` for this, failing with `ErrorKind::PermissionDenied`
+    /// otherwise.  See [`PtpDevice::pps_device_path`] for the corresponding PPS device.
+    pub fn enable_pps(&self, enable: bool) -> Result<()> {
+        let _span = trace_span!("ptp.enable_pps");
+        // Safety: PTP_ENABLE_PPS2 takes its argument by value rather than through a pointer
+        if unsafe { libc::ioctl(self.file.as_raw_fd(), PTP_ENABLE_PPS2 as _, enable as libc::c_int) } != 0 {
+            return Err(ioctl_error("PTP_ENABLE_PPS2"));
+        }
+        lock(&self.resources).pps = enable;
+        Ok(())
+    }
+
+    /// Whether PPS events were last enabled through this handle
+    ///
+    /// The kernel provides no way to read the current setting, so this only reflects calls to
+    /// [`PtpDevice::enable_pps`] on this `PtpDevice`; it is `false` if PPS was enabled by another
+    /// process or handle.  Fails with `ErrorKind::Unsupported` if the clock cannot generate PPS
+    /// events at all, as reported by the `pps` capability.
+    pub fn pps_enabled(&self) -> Result<bool> {
+        if self.cached_caps()?.pps == 0 {
+            return Err(Error::new(ErrorKind::Unsupported, "clock does not support PPS events"));
+        }
+        Ok(lock(&self.resources).pps)
+    }
+
+    /// Open the kernel PPS source fed by this PHC, if it has one
+    ///
+    /// See [`PtpDevice::pps_device_path`].  Events only arrive while PPS is enabled with
+    /// [`PtpDevice::enable_pps`].
+    pub fn pps_device(&self) -> Result<Option<PpsDevice>> {
+        self.pps_device_path()?.map(PpsDevice::open).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fetch_data_matches_kernel_layout() {
+        assert_eq!(std::mem::size_of::<pps_ktime>(), 16);
+        assert_eq!(std::mem::size_of::<pps_kinfo>(), 48);
+        assert_eq!(std::mem::size_of::<pps_fdata>(), 64);
+    }
+
+    #[test]
+    fn missed_asserts_handle_wrapping() {
+        let at = |assert_sequence| PpsData {
+            assert_sequence,
+            assert_time: ptp_clock_time::default(),
+            clear_sequence: 0,
+            clear_time: ptp_clock_time::default(),
+        };
+        assert_eq!(at(11).missed_asserts_since(&at(10)), 0);
+        assert_eq!(at(14).missed_asserts_since(&at(10)), 3);
+        assert_eq!(at(1).missed_asserts_since(&at(u32::MAX)), 1);
+    }
+}
@@ -38,35 +38,35 @@ pub const PTP_PEROUT_VALID_FLAGS: u32 = 7;
 pub const PTP_PEROUT_V1_VALID_FLAGS: u32 = 0;
 pub const PTP_MAX_SAMPLES: u32 = 25;
 pub const PTP_CLK_MAGIC: u8 = 61u8;
-pub type __s8 = ::std::os::raw::c_schar;
-pub type __u8 = ::std::os::raw::c_uchar;
-pub type __s16 = ::std::os::raw::c_short;
-pub type __u16 = ::std::os::raw::c_ushort;
-pub type __s32 = ::std::os::raw::c_int;
-pub type __u32 = ::std::os::raw::c_uint;
-pub type __s64 = ::std::os::raw::c_longlong;
-pub type __u64 = ::std::os::raw::c_ulonglong;
+pub type __s8 = ::core::ffi::c_schar;
+pub type __u8 = ::core::ffi::c_uchar;
+pub type __s16 = ::core::ffi::c_short;
+pub type __u16 = ::core::ffi::c_ushort;
+pub type __s32 = ::core::ffi::c_int;
+pub type __u32 = ::core::ffi::c_uint;
+pub type __s64 = ::core::ffi::c_longlong;
+pub type __u64 = ::core::ffi::c_ulonglong;
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct __kernel_fd_set {
-    pub fds_bits: [::std::os::raw::c_ulong; 16usize],
+    pub fds_bits: [::core::ffi::c_ulong; 16usize],
 }
 #[test]
 fn bindgen_test_layout___kernel_fd_set() {
-    const UNINIT: ::std::mem::MaybeUninit<__kernel_fd_set> = ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<__kernel_fd_set> = ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<__kernel_fd_set>(),
+        ::core::mem::size_of::<__kernel_fd_set>(),
         128usize,
         concat!("Size of: ", stringify!(__kernel_fd_set))
     );
     assert_eq!(
-        ::std::mem::align_of::<__kernel_fd_set>(),
+        ::core::mem::align_of::<__kernel_fd_set>(),
         8usize,
         concat!("Alignment of ", stringify!(__kernel_fd_set))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).fds_bits) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).fds_bits) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -77,48 +77,48 @@ fn bindgen_test_layout___kernel_fd_set() {
     );
 }
 pub type __kernel_sighandler_t =
-    ::std::option::Option<unsafe extern "C" fn(arg1: ::std::os::raw::c_int)>;
-pub type __kernel_key_t = ::std::os::raw::c_int;
-pub type __kernel_mqd_t = ::std::os::raw::c_int;
-pub type __kernel_old_uid_t = ::std::os::raw::c_ushort;
-pub type __kernel_old_gid_t = ::std::os::raw::c_ushort;
-pub type __kernel_old_dev_t = ::std::os::raw::c_ulong;
-pub type __kernel_long_t = ::std::os::raw::c_long;
-pub type __kernel_ulong_t = ::std::os::raw::c_ulong;
+    ::core::option::Option<unsafe extern "C" fn(arg1: ::core::ffi::c_int)>;
+pub type __kernel_key_t = ::core::ffi::c_int;
+pub type __kernel_mqd_t = ::core::ffi::c_int;
+pub type __kernel_old_uid_t = ::core::ffi::c_ushort;
+pub type __kernel_old_gid_t = ::core::ffi::c_ushort;
+pub type __kernel_old_dev_t = ::core::ffi::c_ulong;
+pub type __kernel_long_t = ::core::ffi::c_long;
+pub type __kernel_ulong_t = ::core::ffi::c_ulong;
 pub type __kernel_ino_t = __kernel_ulong_t;
-pub type __kernel_mode_t = ::std::os::raw::c_uint;
-pub type __kernel_pid_t = ::std::os::raw::c_int;
-pub type __kernel_ipc_pid_t = ::std::os::raw::c_int;
-pub type __kernel_uid_t = ::std::os::raw::c_uint;
-pub type __kernel_gid_t = ::std::os::raw::c_uint;
+pub type __kernel_mode_t = ::core::ffi::c_uint;
+pub type __kernel_pid_t = ::core::ffi::c_int;
+pub type __kernel_ipc_pid_t = ::core::ffi::c_int;
+pub type __kernel_uid_t = ::core::ffi::c_uint;
+pub type __kernel_gid_t = ::core::ffi::c_uint;
 pub type __kernel_suseconds_t = __kernel_long_t;
-pub type __kernel_daddr_t = ::std::os::raw::c_int;
-pub type __kernel_uid32_t = ::std::os::raw::c_uint;
-pub type __kernel_gid32_t = ::std::os::raw::c_uint;
+pub type __kernel_daddr_t = ::core::ffi::c_int;
+pub type __kernel_uid32_t = ::core::ffi::c_uint;
+pub type __kernel_gid32_t = ::core::ffi::c_uint;
 pub type __kernel_size_t = __kernel_ulong_t;
 pub type __kernel_ssize_t = __kernel_long_t;
 pub type __kernel_ptrdiff_t = __kernel_long_t;
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct __kernel_fsid_t {
-    pub val: [::std::os::raw::c_int; 2usize],
+    pub val: [::core::ffi::c_int; 2usize],
 }
 #[test]
 fn bindgen_test_layout___kernel_fsid_t() {
-    const UNINIT: ::std::mem::MaybeUninit<__kernel_fsid_t> = ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<__kernel_fsid_t> = ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<__kernel_fsid_t>(),
+        ::core::mem::size_of::<__kernel_fsid_t>(),
         8usize,
         concat!("Size of: ", stringify!(__kernel_fsid_t))
     );
     assert_eq!(
-        ::std::mem::align_of::<__kernel_fsid_t>(),
+        ::core::mem::align_of::<__kernel_fsid_t>(),
         4usize,
         concat!("Alignment of ", stringify!(__kernel_fsid_t))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).val) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).val) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -129,16 +129,16 @@ fn bindgen_test_layout___kernel_fsid_t() {
     );
 }
 pub type __kernel_off_t = __kernel_long_t;
-pub type __kernel_loff_t = ::std::os::raw::c_longlong;
+pub type __kernel_loff_t = ::core::ffi::c_longlong;
 pub type __kernel_old_time_t = __kernel_long_t;
 pub type __kernel_time_t = __kernel_long_t;
-pub type __kernel_time64_t = ::std::os::raw::c_longlong;
+pub type __kernel_time64_t = ::core::ffi::c_longlong;
 pub type __kernel_clock_t = __kernel_long_t;
-pub type __kernel_timer_t = ::std::os::raw::c_int;
-pub type __kernel_clockid_t = ::std::os::raw::c_int;
-pub type __kernel_caddr_t = *mut ::std::os::raw::c_char;
-pub type __kernel_uid16_t = ::std::os::raw::c_ushort;
-pub type __kernel_gid16_t = ::std::os::raw::c_ushort;
+pub type __kernel_timer_t = ::core::ffi::c_int;
+pub type __kernel_clockid_t = ::core::ffi::c_int;
+pub type __kernel_caddr_t = *mut ::core::ffi::c_char;
+pub type __kernel_uid16_t = ::core::ffi::c_ushort;
+pub type __kernel_gid16_t = ::core::ffi::c_ushort;
 pub type __s128 = i128;
 pub type __u128 = u128;
 pub type __le16 = __u16;
@@ -149,7 +149,7 @@ pub type __le64 = __u64;
 pub type __be64 = __u64;
 pub type __sum16 = __u16;
 pub type __wsum = __u32;
-pub type __poll_t = ::std::os::raw::c_uint;
+pub type __poll_t = ::core::ffi::c_uint;
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct ptp_clock_time {
@@ -159,20 +159,20 @@ pub struct ptp_clock_time {
 }
 #[test]
 fn bindgen_test_layout_ptp_clock_time() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_clock_time> = ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_clock_time> = ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_clock_time>(),
+        ::core::mem::size_of::<ptp_clock_time>(),
         16usize,
         concat!("Size of: ", stringify!(ptp_clock_time))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_clock_time>(),
+        ::core::mem::align_of::<ptp_clock_time>(),
         8usize,
         concat!("Alignment of ", stringify!(ptp_clock_time))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).sec) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).sec) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -182,7 +182,7 @@ fn bindgen_test_layout_ptp_clock_time() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).nsec) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).nsec) as usize - ptr as usize },
         8usize,
         concat!(
             "Offset of field: ",
@@ -192,7 +192,7 @@ fn bindgen_test_layout_ptp_clock_time() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).reserved) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).reserved) as usize - ptr as usize },
         12usize,
         concat!(
             "Offset of field: ",
@@ -205,33 +205,33 @@ fn bindgen_test_layout_ptp_clock_time() {
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct ptp_clock_caps {
-    pub max_adj: ::std::os::raw::c_int,
-    pub n_alarm: ::std::os::raw::c_int,
-    pub n_ext_ts: ::std::os::raw::c_int,
-    pub n_per_out: ::std::os::raw::c_int,
-    pub pps: ::std::os::raw::c_int,
-    pub n_pins: ::std::os::raw::c_int,
-    pub cross_timestamping: ::std::os::raw::c_int,
-    pub adjust_phase: ::std::os::raw::c_int,
-    pub max_phase_adj: ::std::os::raw::c_int,
-    pub rsv: [::std::os::raw::c_int; 11usize],
+    pub max_adj: ::core::ffi::c_int,
+    pub n_alarm: ::core::ffi::c_int,
+    pub n_ext_ts: ::core::ffi::c_int,
+    pub n_per_out: ::core::ffi::c_int,
+    pub pps: ::core::ffi::c_int,
+    pub n_pins: ::core::ffi::c_int,
+    pub cross_timestamping: ::core::ffi::c_int,
+    pub adjust_phase: ::core::ffi::c_int,
+    pub max_phase_adj: ::core::ffi::c_int,
+    pub rsv: [::core::ffi::c_int; 11usize],
 }
 #[test]
 fn bindgen_test_layout_ptp_clock_caps() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_clock_caps> = ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_clock_caps> = ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_clock_caps>(),
+        ::core::mem::size_of::<ptp_clock_caps>(),
         80usize,
         concat!("Size of: ", stringify!(ptp_clock_caps))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_clock_caps>(),
+        ::core::mem::align_of::<ptp_clock_caps>(),
         4usize,
         concat!("Alignment of ", stringify!(ptp_clock_caps))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).max_adj) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).max_adj) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -241,7 +241,7 @@ fn bindgen_test_layout_ptp_clock_caps() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).n_alarm) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).n_alarm) as usize - ptr as usize },
         4usize,
         concat!(
             "Offset of field: ",
@@ -251,7 +251,7 @@ fn bindgen_test_layout_ptp_clock_caps() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).n_ext_ts) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).n_ext_ts) as usize - ptr as usize },
         8usize,
         concat!(
             "Offset of field: ",
@@ -261,7 +261,7 @@ fn bindgen_test_layout_ptp_clock_caps() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).n_per_out) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).n_per_out) as usize - ptr as usize },
         12usize,
         concat!(
             "Offset of field: ",
@@ -271,7 +271,7 @@ fn bindgen_test_layout_ptp_clock_caps() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).pps) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).pps) as usize - ptr as usize },
         16usize,
         concat!(
             "Offset of field: ",
@@ -281,7 +281,7 @@ fn bindgen_test_layout_ptp_clock_caps() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).n_pins) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).n_pins) as usize - ptr as usize },
         20usize,
         concat!(
             "Offset of field: ",
@@ -291,7 +291,7 @@ fn bindgen_test_layout_ptp_clock_caps() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).cross_timestamping) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).cross_timestamping) as usize - ptr as usize },
         24usize,
         concat!(
             "Offset of field: ",
@@ -301,7 +301,7 @@ fn bindgen_test_layout_ptp_clock_caps() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).adjust_phase) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).adjust_phase) as usize - ptr as usize },
         28usize,
         concat!(
             "Offset of field: ",
@@ -311,7 +311,7 @@ fn bindgen_test_layout_ptp_clock_caps() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).max_phase_adj) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).max_phase_adj) as usize - ptr as usize },
         32usize,
         concat!(
             "Offset of field: ",
@@ -321,7 +321,7 @@ fn bindgen_test_layout_ptp_clock_caps() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
         36usize,
         concat!(
             "Offset of field: ",
@@ -334,26 +334,26 @@ fn bindgen_test_layout_ptp_clock_caps() {
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct ptp_extts_request {
-    pub index: ::std::os::raw::c_uint,
-    pub flags: ::std::os::raw::c_uint,
-    pub rsv: [::std::os::raw::c_uint; 2usize],
+    pub index: ::core::ffi::c_uint,
+    pub flags: ::core::ffi::c_uint,
+    pub rsv: [::core::ffi::c_uint; 2usize],
 }
 #[test]
 fn bindgen_test_layout_ptp_extts_request() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_extts_request> = ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_extts_request> = ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_extts_request>(),
+        ::core::mem::size_of::<ptp_extts_request>(),
         16usize,
         concat!("Size of: ", stringify!(ptp_extts_request))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_extts_request>(),
+        ::core::mem::align_of::<ptp_extts_request>(),
         4usize,
         concat!("Alignment of ", stringify!(ptp_extts_request))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -363,7 +363,7 @@ fn bindgen_test_layout_ptp_extts_request() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).flags) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).flags) as usize - ptr as usize },
         4usize,
         concat!(
             "Offset of field: ",
@@ -373,7 +373,7 @@ fn bindgen_test_layout_ptp_extts_request() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
         8usize,
         concat!(
             "Offset of field: ",
@@ -388,8 +388,8 @@ fn bindgen_test_layout_ptp_extts_request() {
 pub struct ptp_perout_request {
     pub __bindgen_anon_1: ptp_perout_request__bindgen_ty_1,
     pub period: ptp_clock_time,
-    pub index: ::std::os::raw::c_uint,
-    pub flags: ::std::os::raw::c_uint,
+    pub index: ::core::ffi::c_uint,
+    pub flags: ::core::ffi::c_uint,
     pub __bindgen_anon_2: ptp_perout_request__bindgen_ty_2,
 }
 #[repr(C)]
@@ -400,16 +400,16 @@ pub union ptp_perout_request__bindgen_ty_1 {
 }
 #[test]
 fn bindgen_test_layout_ptp_perout_request__bindgen_ty_1() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_perout_request__bindgen_ty_1> =
-        ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_perout_request__bindgen_ty_1> =
+        ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_perout_request__bindgen_ty_1>(),
+        ::core::mem::size_of::<ptp_perout_request__bindgen_ty_1>(),
         16usize,
         concat!("Size of: ", stringify!(ptp_perout_request__bindgen_ty_1))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_perout_request__bindgen_ty_1>(),
+        ::core::mem::align_of::<ptp_perout_request__bindgen_ty_1>(),
         8usize,
         concat!(
             "Alignment of ",
@@ -417,7 +417,7 @@ fn bindgen_test_layout_ptp_perout_request__bindgen_ty_1() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).start) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).start) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -427,7 +427,7 @@ fn bindgen_test_layout_ptp_perout_request__bindgen_ty_1() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).phase) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).phase) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -439,9 +439,9 @@ fn bindgen_test_layout_ptp_perout_request__bindgen_ty_1() {
 }
 impl Default for ptp_perout_request__bindgen_ty_1 {
     fn default() -> Self {
-        let mut s = ::std::mem::MaybeUninit::<Self>::uninit();
+        let mut s = ::core::mem::MaybeUninit::<Self>::uninit();
         unsafe {
-            ::std::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
+            ::core::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
             s.assume_init()
         }
     }
@@ -450,20 +450,20 @@ impl Default for ptp_perout_request__bindgen_ty_1 {
 #[derive(Copy, Clone)]
 pub union ptp_perout_request__bindgen_ty_2 {
     pub on: ptp_clock_time,
-    pub rsv: [::std::os::raw::c_uint; 4usize],
+    pub rsv: [::core::ffi::c_uint; 4usize],
 }
 #[test]
 fn bindgen_test_layout_ptp_perout_request__bindgen_ty_2() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_perout_request__bindgen_ty_2> =
-        ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_perout_request__bindgen_ty_2> =
+        ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_perout_request__bindgen_ty_2>(),
+        ::core::mem::size_of::<ptp_perout_request__bindgen_ty_2>(),
         16usize,
         concat!("Size of: ", stringify!(ptp_perout_request__bindgen_ty_2))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_perout_request__bindgen_ty_2>(),
+        ::core::mem::align_of::<ptp_perout_request__bindgen_ty_2>(),
         8usize,
         concat!(
             "Alignment of ",
@@ -471,7 +471,7 @@ fn bindgen_test_layout_ptp_perout_request__bindgen_ty_2() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).on) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).on) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -481,7 +481,7 @@ fn bindgen_test_layout_ptp_perout_request__bindgen_ty_2() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -493,29 +493,29 @@ fn bindgen_test_layout_ptp_perout_request__bindgen_ty_2() {
 }
 impl Default for ptp_perout_request__bindgen_ty_2 {
     fn default() -> Self {
-        let mut s = ::std::mem::MaybeUninit::<Self>::uninit();
+        let mut s = ::core::mem::MaybeUninit::<Self>::uninit();
         unsafe {
-            ::std::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
+            ::core::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
             s.assume_init()
         }
     }
 }
 #[test]
 fn bindgen_test_layout_ptp_perout_request() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_perout_request> = ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_perout_request> = ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_perout_request>(),
+        ::core::mem::size_of::<ptp_perout_request>(),
         56usize,
         concat!("Size of: ", stringify!(ptp_perout_request))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_perout_request>(),
+        ::core::mem::align_of::<ptp_perout_request>(),
         8usize,
         concat!("Alignment of ", stringify!(ptp_perout_request))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).period) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).period) as usize - ptr as usize },
         16usize,
         concat!(
             "Offset of field: ",
@@ -525,7 +525,7 @@ fn bindgen_test_layout_ptp_perout_request() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
         32usize,
         concat!(
             "Offset of field: ",
@@ -535,7 +535,7 @@ fn bindgen_test_layout_ptp_perout_request() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).flags) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).flags) as usize - ptr as usize },
         36usize,
         concat!(
             "Offset of field: ",
@@ -547,9 +547,9 @@ fn bindgen_test_layout_ptp_perout_request() {
 }
 impl Default for ptp_perout_request {
     fn default() -> Self {
-        let mut s = ::std::mem::MaybeUninit::<Self>::uninit();
+        let mut s = ::core::mem::MaybeUninit::<Self>::uninit();
         unsafe {
-            ::std::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
+            ::core::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
             s.assume_init()
         }
     }
@@ -557,26 +557,26 @@ impl Default for ptp_perout_request {
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct ptp_sys_offset {
-    pub n_samples: ::std::os::raw::c_uint,
-    pub rsv: [::std::os::raw::c_uint; 3usize],
+    pub n_samples: ::core::ffi::c_uint,
+    pub rsv: [::core::ffi::c_uint; 3usize],
     pub ts: [ptp_clock_time; 51usize],
 }
 #[test]
 fn bindgen_test_layout_ptp_sys_offset() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_sys_offset> = ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_sys_offset> = ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_sys_offset>(),
+        ::core::mem::size_of::<ptp_sys_offset>(),
         832usize,
         concat!("Size of: ", stringify!(ptp_sys_offset))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_sys_offset>(),
+        ::core::mem::align_of::<ptp_sys_offset>(),
         8usize,
         concat!("Alignment of ", stringify!(ptp_sys_offset))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).n_samples) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).n_samples) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -586,7 +586,7 @@ fn bindgen_test_layout_ptp_sys_offset() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
         4usize,
         concat!(
             "Offset of field: ",
@@ -596,7 +596,7 @@ fn bindgen_test_layout_ptp_sys_offset() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).ts) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).ts) as usize - ptr as usize },
         16usize,
         concat!(
             "Offset of field: ",
@@ -608,9 +608,9 @@ fn bindgen_test_layout_ptp_sys_offset() {
 }
 impl Default for ptp_sys_offset {
     fn default() -> Self {
-        let mut s = ::std::mem::MaybeUninit::<Self>::uninit();
+        let mut s = ::core::mem::MaybeUninit::<Self>::uninit();
         unsafe {
-            ::std::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
+            ::core::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
             s.assume_init()
         }
     }
@@ -618,27 +618,27 @@ impl Default for ptp_sys_offset {
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct ptp_sys_offset_extended {
-    pub n_samples: ::std::os::raw::c_uint,
-    pub rsv: [::std::os::raw::c_uint; 3usize],
+    pub n_samples: ::core::ffi::c_uint,
+    pub rsv: [::core::ffi::c_uint; 3usize],
     pub ts: [[ptp_clock_time; 3usize]; 25usize],
 }
 #[test]
 fn bindgen_test_layout_ptp_sys_offset_extended() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_sys_offset_extended> =
-        ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_sys_offset_extended> =
+        ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_sys_offset_extended>(),
+        ::core::mem::size_of::<ptp_sys_offset_extended>(),
         1216usize,
         concat!("Size of: ", stringify!(ptp_sys_offset_extended))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_sys_offset_extended>(),
+        ::core::mem::align_of::<ptp_sys_offset_extended>(),
         8usize,
         concat!("Alignment of ", stringify!(ptp_sys_offset_extended))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).n_samples) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).n_samples) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -648,7 +648,7 @@ fn bindgen_test_layout_ptp_sys_offset_extended() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
         4usize,
         concat!(
             "Offset of field: ",
@@ -658,7 +658,7 @@ fn bindgen_test_layout_ptp_sys_offset_extended() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).ts) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).ts) as usize - ptr as usize },
         16usize,
         concat!(
             "Offset of field: ",
@@ -674,25 +674,25 @@ pub struct ptp_sys_offset_precise {
     pub device: ptp_clock_time,
     pub sys_realtime: ptp_clock_time,
     pub sys_monoraw: ptp_clock_time,
-    pub rsv: [::std::os::raw::c_uint; 4usize],
+    pub rsv: [::core::ffi::c_uint; 4usize],
 }
 #[test]
 fn bindgen_test_layout_ptp_sys_offset_precise() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_sys_offset_precise> =
-        ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_sys_offset_precise> =
+        ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_sys_offset_precise>(),
+        ::core::mem::size_of::<ptp_sys_offset_precise>(),
         64usize,
         concat!("Size of: ", stringify!(ptp_sys_offset_precise))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_sys_offset_precise>(),
+        ::core::mem::align_of::<ptp_sys_offset_precise>(),
         8usize,
         concat!("Alignment of ", stringify!(ptp_sys_offset_precise))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).device) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).device) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -702,7 +702,7 @@ fn bindgen_test_layout_ptp_sys_offset_precise() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).sys_realtime) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).sys_realtime) as usize - ptr as usize },
         16usize,
         concat!(
             "Offset of field: ",
@@ -712,7 +712,7 @@ fn bindgen_test_layout_ptp_sys_offset_precise() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).sys_monoraw) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).sys_monoraw) as usize - ptr as usize },
         32usize,
         concat!(
             "Offset of field: ",
@@ -722,7 +722,7 @@ fn bindgen_test_layout_ptp_sys_offset_precise() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
         48usize,
         concat!(
             "Offset of field: ",
@@ -736,32 +736,32 @@ pub const ptp_pin_function_PTP_PF_NONE: ptp_pin_function = 0;
 pub const ptp_pin_function_PTP_PF_EXTTS: ptp_pin_function = 1;
 pub const ptp_pin_function_PTP_PF_PEROUT: ptp_pin_function = 2;
 pub const ptp_pin_function_PTP_PF_PHYSYNC: ptp_pin_function = 3;
-pub type ptp_pin_function = ::std::os::raw::c_uint;
+pub type ptp_pin_function = ::core::ffi::c_uint;
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct ptp_pin_desc {
-    pub name: [::std::os::raw::c_char; 64usize],
-    pub index: ::std::os::raw::c_uint,
-    pub func: ::std::os::raw::c_uint,
-    pub chan: ::std::os::raw::c_uint,
-    pub rsv: [::std::os::raw::c_uint; 5usize],
+    pub name: [::core::ffi::c_char; 64usize],
+    pub index: ::core::ffi::c_uint,
+    pub func: ::core::ffi::c_uint,
+    pub chan: ::core::ffi::c_uint,
+    pub rsv: [::core::ffi::c_uint; 5usize],
 }
 #[test]
 fn bindgen_test_layout_ptp_pin_desc() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_pin_desc> = ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_pin_desc> = ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_pin_desc>(),
+        ::core::mem::size_of::<ptp_pin_desc>(),
         96usize,
         concat!("Size of: ", stringify!(ptp_pin_desc))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_pin_desc>(),
+        ::core::mem::align_of::<ptp_pin_desc>(),
         4usize,
         concat!("Alignment of ", stringify!(ptp_pin_desc))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).name) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).name) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -771,7 +771,7 @@ fn bindgen_test_layout_ptp_pin_desc() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
         64usize,
         concat!(
             "Offset of field: ",
@@ -781,7 +781,7 @@ fn bindgen_test_layout_ptp_pin_desc() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).func) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).func) as usize - ptr as usize },
         68usize,
         concat!(
             "Offset of field: ",
@@ -791,7 +791,7 @@ fn bindgen_test_layout_ptp_pin_desc() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).chan) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).chan) as usize - ptr as usize },
         72usize,
         concat!(
             "Offset of field: ",
@@ -801,7 +801,7 @@ fn bindgen_test_layout_ptp_pin_desc() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
         76usize,
         concat!(
             "Offset of field: ",
@@ -813,9 +813,9 @@ fn bindgen_test_layout_ptp_pin_desc() {
 }
 impl Default for ptp_pin_desc {
     fn default() -> Self {
-        let mut s = ::std::mem::MaybeUninit::<Self>::uninit();
+        let mut s = ::core::mem::MaybeUninit::<Self>::uninit();
         unsafe {
-            ::std::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
+            ::core::ptr::write_bytes(s.as_mut_ptr(), 0, 1);
             s.assume_init()
         }
     }
@@ -824,26 +824,26 @@ impl Default for ptp_pin_desc {
 #[derive(Debug, Default, Copy, Clone)]
 pub struct ptp_extts_event {
     pub t: ptp_clock_time,
-    pub index: ::std::os::raw::c_uint,
-    pub flags: ::std::os::raw::c_uint,
-    pub rsv: [::std::os::raw::c_uint; 2usize],
+    pub index: ::core::ffi::c_uint,
+    pub flags: ::core::ffi::c_uint,
+    pub rsv: [::core::ffi::c_uint; 2usize],
 }
 #[test]
 fn bindgen_test_layout_ptp_extts_event() {
-    const UNINIT: ::std::mem::MaybeUninit<ptp_extts_event> = ::std::mem::MaybeUninit::uninit();
+    const UNINIT: ::core::mem::MaybeUninit<ptp_extts_event> = ::core::mem::MaybeUninit::uninit();
     let ptr = UNINIT.as_ptr();
     assert_eq!(
-        ::std::mem::size_of::<ptp_extts_event>(),
+        ::core::mem::size_of::<ptp_extts_event>(),
         32usize,
         concat!("Size of: ", stringify!(ptp_extts_event))
     );
     assert_eq!(
-        ::std::mem::align_of::<ptp_extts_event>(),
+        ::core::mem::align_of::<ptp_extts_event>(),
         8usize,
         concat!("Alignment of ", stringify!(ptp_extts_event))
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).t) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).t) as usize - ptr as usize },
         0usize,
         concat!(
             "Offset of field: ",
@@ -853,7 +853,7 @@ fn bindgen_test_layout_ptp_extts_event() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).index) as usize - ptr as usize },
         16usize,
         concat!(
             "Offset of field: ",
@@ -863,7 +863,7 @@ fn bindgen_test_layout_ptp_extts_event() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).flags) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).flags) as usize - ptr as usize },
         20usize,
         concat!(
             "Offset of field: ",
@@ -873,7 +873,7 @@ fn bindgen_test_layout_ptp_extts_event() {
         )
     );
     assert_eq!(
-        unsafe { ::std::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
+        unsafe { ::core::ptr::addr_of!((*ptr).rsv) as usize - ptr as usize },
         24usize,
         concat!(
             "Offset of field: ",
@@ -0,0 +1,149 @@
+//! Capture and deterministic replay of offset measurements, for testing servos
+//!
+//! A servo written against [`PtpBackend`] rather than [`PtpDevice`] can be run against a trace
+//! captured from real hardware with [`record_offsets`] and replayed with [`ReplayDevice`].  With
+//! the `serde` feature, traces can be saved as JSON with [`write_recording`] and shared.
+
+use std::{
+    collections::VecDeque,
+    io::{Error, ErrorKind, Result},
+    sync::Mutex,
+};
+
+use crate::{lock, OffsetMeasurement, PtpDevice};
+
+/// The clock operations a disciplining loop needs
+pub trait PtpBackend {
+    /// Measure the offset between the clock and the system clock
+    fn measure_offset(&self) -> Result<OffsetMeasurement>;
+
+    /// Set the clock's frequency adjustment in parts per billion
+    fn adjust_frequency(&self, ppb: f64) -> Result<()>;
+
+    /// Step the clock by `offset_ns` nanoseconds
+    fn step_clock(&self, offset_ns: i64) -> Result<()>;
+}
+
+impl PtpBackend for PtpDevice {
+    fn measure_offset(&self) -> Result<OffsetMeasurement> {
+        PtpDevice::measure_offset(self)
+    }
+
+    fn adjust_frequency(&self, ppb: f64) -> Result<()> {
+        PtpDevice::adjust_frequency(self, ppb)
+    }
+
+    fn step_clock(&self, offset_ns: i64) -> Result<()> {
+        PtpDevice::step_clock(self, offset_ns)
+    }
+}
+
+/// Take `count` consecutive measurements with [`PtpDevice::measure_offset`]
+///
+/// The measurements are taken back to back; callers wanting the spacing of a real servo loop
+/// should sleep between single-measurement calls instead.
+pub fn record_offsets(device: &PtpDevice, count: usize) -> Result<Vec<OffsetMeasurement>> {
+    (0..count).map(|_| device.measure_offset()).collect()
+}
+
+/// A [`PtpBackend`] which returns recorded measurements in order
+///
+/// The recording is replayed unchanged, regardless of the corrections applied: it captures how
+/// the real clock behaved, not a model of it.  The corrections are kept so that tests can check
+/// what the servo did.  Once the recording is exhausted, `measure_offset` fails with
+/// `ErrorKind::UnexpectedEof`.
+#[derive(Debug, Default)]
+pub struct ReplayDevice {
+    measurements: Mutex<VecDeque<OffsetMeasurement>>,
+    frequencies: Mutex<Vec<f64>>,
+    steps: Mutex<Vec<i64>>,
+}
+
+impl ReplayDevice {
+    /// Replay `measurements` in order
+    pub fn new(measurements: Vec<OffsetMeasurement>) -> ReplayDevice {
+        ReplayDevice {
+            measurements: Mutex::new(measurements.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Number of measurements not yet replayed
+    pub fn remaining(&self) -> usize {
+        lock(&self.measurements).len()
+    }
+
+    /// The frequency adjustments applied so far, in order
+    pub fn frequencies(&self) -> Vec<f64> {
+        lock(&self.frequencies).clone()
+    }
+
+    /// The steps applied so far, in order
+    pub fn steps(&self) -> Vec<i64> {
+        lock(&self.steps).clone()
+    }
+}
+
+impl PtpBackend for ReplayDevice {
+    fn measure_offset(&self) -> Result<OffsetMeasurement> {
+        lock(&self.measurements)
+            .pop_front()
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "replay recording exhausted"))
+    }
+
+    fn adjust_frequency(&self, ppb: f64) -> Result<()> {
+        lock(&self.frequencies).push(ppb);
+        Ok(())
+    }
+
+    fn step_clock(&self, offset_ns: i64) -> Result<()> {
+        lock(&self.steps).push(offset_ns);
+        Ok(())
+    }
+}
+
+/// Write a recording as a JSON array of measurements in the `wire` format
+#[cfg(feature = "serde")]
+pub fn write_recording<W: std::io::Write>(measurements: &[OffsetMeasurement], writer: W) -> Result<()> {
+    serde_json::to_writer(writer, measurements).map_err(Error::from)
+}
+
+/// Read a recording written by [`write_recording`]
+#[cfg(feature = "serde")]
+pub fn read_recording<R: std::io::Read>(reader: R) -> Result<Vec<OffsetMeasurement>> {
+    serde_json::from_reader(reader).map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::measurement;
+    use crate::PiServo;
+
+    #[test]
+    fn replays_in_order_and_records_corrections() {
+        let replay = ReplayDevice::new(vec![measurement(0, 5_000_000), measurement(1, 100)]);
+        let mut servo = PiServo::default();
+        while let Ok(m) = replay.measure_offset() {
+            let correction = servo.sample(m.offset_ns);
+            if let Some(step_ns) = correction.step_ns {
+                replay.step_clock(step_ns).unwrap();
+            }
+            replay.adjust_frequency(correction.ppb).unwrap();
+        }
+        assert_eq!(replay.remaining(), 0);
+        assert_eq!(replay.steps(), vec![-5_000_000]);
+        assert_eq!(replay.frequencies().len(), 2);
+        assert_eq!(replay.measure_offset().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn recording_round_trips_through_json() {
+        let recording = vec![measurement(0, 10), measurement(1, -20)];
+        let mut json = Vec::new();
+        write_recording(&recording, &mut json).unwrap();
+        let back = read_recording(json.as_slice()).unwrap();
+        assert_eq!(back.iter().map(|m| m.offset_ns).collect::<Vec<_>>(), vec![10, -20]);
+    }
+}
@@ -0,0 +1,186 @@
+//! Scheduled sampling of the offset between a PHC and the system clock, and work aligned to PHC
+//! seconds
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::Result,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{ptp::ptp_clock_time, OffsetMeasurement, PtpDevice};
+
+/// How long before a PHC second [`PtpDevice::at_next_second`] stops sleeping and starts polling
+/// the system clock, to absorb the oversleep of `thread::sleep`
+const SPIN_LEAD: Duration = Duration::from_micros(500);
+
+/// `CLOCK_REALTIME` now, as a `ptp_clock_time`
+fn realtime_now() -> ptp_clock_time {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or_else(|_| ptp_clock_time::default(), ptp_clock_time::from_duration)
+}
+
+/// The `CLOCK_REALTIME` time in nanoseconds at which the PHC reaches its next whole second,
+/// according to `m`
+fn next_second_deadline(m: &OffsetMeasurement) -> i128 {
+    let boundary = (m.phc_time.sec as i128 + 1) * 1_000_000_000;
+    boundary - m.offset_ns as i128
+}
+
+/// When [`PtpDevice::sample_offsets`] takes its measurements
+///
+/// Every deadline is computed from the clock rather than by adding up sleeps, so the schedule
+/// does not drift however long each measurement takes.  Deadlines which have already passed,
+/// e.g. because the consumer was slow, are skipped rather than sampled in a burst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleSchedule {
+    /// Sample every interval, starting immediately
+    FixedInterval(Duration),
+    /// Sample just after each whole second of `CLOCK_REALTIME`, so samples from every agent on
+    /// a synchronised system share a phase
+    AlignedToSecond,
+    /// Sample every `base`, each sample delayed from its slot by a random amount up to `spread`,
+    /// so that many agents started together do not poll in lockstep
+    Jittered {
+        /// The interval between the slots samples are taken in
+        base: Duration,
+        /// The largest random delay from the start of a slot
+        spread: Duration,
+    },
+}
+
+/// An endless iterator of offset measurements on a [`SampleSchedule`], from
+/// [`PtpDevice::sample_offsets`]
+///
+/// Each call to `next` sleeps until the next deadline and then returns the result of
+/// [`PtpDevice::measure_offset`]; a failed measurement does not end the iteration.
+pub struct OffsetSamples<'a> {
+    device: &'a PtpDevice,
+    schedule: SampleSchedule,
+    start: Instant,
+    /// The index of the next slot of a fixed or jittered schedule
+    slot: u128,
+    /// State of the xorshift generator for jitter, which must be non-zero
+    rng: u64,
+}
+
+impl OffsetSamples<'_> {
+    /// The next jitter value, uniform in `0..=spread`
+    fn jitter(&mut self, spread: Duration) -> u128 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng as u128 % (spread.as_nanos() + 1)
+    }
+
+    /// How long to wait for the next sample, `elapsed` after the iterator was created and
+    /// `subsec_nanos` after the last whole second of `CLOCK_REALTIME`
+    fn wait(&mut self, elapsed: Duration, subsec_nanos: u32) -> Duration {
+        let (base, spread) = match self.schedule {
+            SampleSchedule::AlignedToSecond => {
+                return Duration::from_secs(1) - Duration::from_nanos(subsec_nanos.into());
+            }
+            SampleSchedule::FixedInterval(base) => (base.as_nanos(), Duration::ZERO),
+            SampleSchedule::Jittered { base, spread } => (base.as_nanos(), spread),
+        };
+        let elapsed = elapsed.as_nanos();
+        if base == 0 {
+            return Duration::ZERO;
+        }
+        if base * self.slot < elapsed {
+            self.slot = elapsed.div_ceil(base);
+        }
+        let deadline = base * self.slot + self.jitter(spread);
+        self.slot += 1;
+        Duration::from_nanos(deadline.saturating_sub(elapsed).try_into().unwrap_or(u64::MAX))
+    }
+}
+
+impl Iterator for OffsetSamples<'_> {
+    type Item = Result<OffsetMeasurement>;
+
+    fn next(&mut self) -> Option<Result<OffsetMeasurement>> {
+        let wait = self.wait(self.start.elapsed(), realtime_now().nsec);
+        thread::sleep(wait);
+        Some(self.device.measure_offset())
+    }
+}
+
+impl PtpDevice {
+    /// Call `f` as close as possible to the start of the PHC's next whole second
+    ///
+    /// The offset to the system clock is measured once with [`PtpDevice::measure_offset`], and
+    /// the boundary converted to a `CLOCK_REALTIME` deadline with it.  This sleeps until shortly
+    /// before the deadline, then polls the system clock until it passes, so the oversleep of the
+    /// scheduler is absorbed as long as it is under half a millisecond.  The residual error is
+    /// the uncertainty of the offset, about its half delay, plus the drift between the clocks
+    /// over the wait, which is up to a second at the frequency error, plus the delay between the
+    /// poll seeing the deadline and `f` starting, usually a few microseconds but unbounded if
+    /// the thread is preempted.  `f` is not called if the offset cannot be measured.
+    pub fn at_next_second<F: FnOnce()>(&self, f: F) -> Result<()> {
+        let deadline = next_second_deadline(&self.measure_offset()?);
+        let remaining = |now: ptp_clock_time| u64::try_from(deadline - now.as_nanos()).ok();
+        if let Some(ns) = remaining(realtime_now()) {
+            thread::sleep(Duration::from_nanos(ns).saturating_sub(SPIN_LEAD));
+        }
+        while remaining(realtime_now()).is_some_and(|ns| ns > 0) {
+            std::hint::spin_loop();
+        }
+        f();
+        Ok(())
+    }
+
+    /// Measure the offset with [`PtpDevice::measure_offset`] repeatedly, on `schedule`
+    ///
+    /// The iterator never ends; use `take` or stop consuming it to finish sampling.
+    pub fn sample_offsets(&self, schedule: SampleSchedule) -> OffsetSamples<'_> {
+        OffsetSamples {
+            device: self,
+            schedule,
+            start: Instant::now(),
+            slot: 0,
+            rng: RandomState::new().build_hasher().finish() | 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{measurement, null_device};
+
+    #[test]
+    fn deadlines_follow_the_schedule() {
+        let device = null_device();
+        let ms = Duration::from_millis;
+
+        let mut fixed = device.sample_offsets(SampleSchedule::FixedInterval(ms(100)));
+        assert_eq!(fixed.wait(ms(0), 0), ms(0));
+        assert_eq!(fixed.wait(ms(30), 0), ms(70));
+        // A slow consumer skips the slots it missed rather than catching up
+        assert_eq!(fixed.wait(ms(350), 0), ms(50));
+        assert_eq!(fixed.wait(ms(401), 0), ms(99));
+
+        let mut aligned = device.sample_offsets(SampleSchedule::AlignedToSecond);
+        assert_eq!(aligned.wait(ms(5), 250_000_000), ms(750));
+
+        let mut jittered = device.sample_offsets(SampleSchedule::Jittered { base: ms(100), spread: ms(10) });
+        for slot in 0..100 {
+            let elapsed = ms(slot * 100);
+            assert!(jittered.wait(elapsed, 0) <= ms(10));
+        }
+        assert!(device.sample_offsets(SampleSchedule::FixedInterval(ms(0))).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn next_second_is_converted_with_the_offset() {
+        // PHC at 10.25 s, so its next second comes 0.75 s later, at 10.75 s on the system clock
+        assert_eq!(next_second_deadline(&measurement(10, 250_000_000)), 10_750_000_000);
+        // PHC at 9.75 s, reaching 10 s at 10.25 s on the system clock
+        assert_eq!(next_second_deadline(&measurement(10, -250_000_000)), 10_250_000_000);
+        // PHC just short of 10 s, a nanosecond behind the system clock
+        assert_eq!(next_second_deadline(&measurement(10, -1)), 10_000_000_001);
+    }
+}
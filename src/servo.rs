@@ -0,0 +1,282 @@
+//! A simple proportional-integral servo for disciplining a PHC to the system clock
+
+use std::{
+    collections::VecDeque,
+    io::{Error, ErrorKind, Result},
+    time::Duration,
+};
+
+use crate::{lock, OffsetMeasurement, PtpDevice};
+
+/// The correction chosen by a servo for one offset sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrequencyCorrection {
+    /// Frequency adjustment to apply, in parts per billion
+    pub ppb: f64,
+    /// If set, the clock should be stepped by this many nanoseconds before applying `ppb`
+    pub step_ns: Option<i64>,
+}
+
+/// A proportional-integral servo acting on offsets measured per [`crate::OFFSET_CONVENTION`] (`phc - system`)
+///
+/// The gains are in ppb per nanosecond of offset and assume roughly one sample per second, as in
+/// the linuxptp PI servo.  Offsets larger than the step threshold are corrected by stepping the
+/// clock instead of slewing it.
+#[derive(Debug, Clone)]
+pub struct PiServo {
+    kp: f64,
+    ki: f64,
+    step_threshold_ns: i64,
+    max_ppb: f64,
+    /// Integral term: the frequency needed to cancel the clock's own drift
+    drift_ppb: f64,
+}
+
+impl Default for PiServo {
+    fn default() -> Self {
+        PiServo::new(0.7, 0.3)
+    }
+}
+
+impl PiServo {
+    /// Create a servo with the given proportional and integral gains
+    pub fn new(kp: f64, ki: f64) -> PiServo {
+        PiServo {
+            kp,
+            ki,
+            step_threshold_ns: 1_000_000,
+            max_ppb: 500_000.0,
+            drift_ppb: 0.0,
+        }
+    }
+
+    /// Step rather than slew when the offset magnitude exceeds `ns`; 0 disables stepping
+    pub fn with_step_threshold(mut self, ns: i64) -> PiServo {
+        self.step_threshold_ns = ns;
+        self
+    }
+
+    /// Limit the frequency adjustment to +/- `ppb`
+    pub fn with_max_frequency(mut self, ppb: f64) -> PiServo {
+        self.max_ppb = ppb;
+        self
+    }
+
+    /// Start from a known frequency adjustment, e.g. the one currently applied to the clock
+    pub fn with_drift(mut self, ppb: f64) -> PiServo {
+        self.drift_ppb = ppb;
+        self
+    }
+
+    /// The servo's current estimate of the frequency needed to cancel the clock's drift
+    pub fn drift_ppb(&self) -> f64 {
+        self.drift_ppb
+    }
+
+    /// Process an offset sample, returning the correction to apply
+    pub fn sample(&mut self, offset_ns: i64) -> FrequencyCorrection {
+        if self.step_threshold_ns > 0 && offset_ns.unsigned_abs() > self.step_threshold_ns as u64 {
+            return FrequencyCorrection {
+                ppb: self.drift_ppb,
+                step_ns: Some(-offset_ns),
+            };
+        }
+        let offset = offset_ns as f64;
+        self.drift_ppb = (self.drift_ppb - self.ki * offset).clamp(-self.max_ppb, self.max_ppb);
+        FrequencyCorrection {
+            ppb: (self.drift_ppb - self.kp * offset).clamp(-self.max_ppb, self.max_ppb),
+            step_ns: None,
+        }
+    }
+}
+
+/// Settings for a [`Discipline`] loop
+#[derive(Debug, Clone, Copy)]
+pub struct DisciplineConfig {
+    /// Proportional gain of the servo in ppb per nanosecond
+    pub kp: f64,
+    /// Integral gain of the servo in ppb per nanosecond
+    pub ki: f64,
+    /// Step rather than slew when a measured offset exceeds this many nanoseconds; 0 disables stepping
+    pub step_threshold_ns: i64,
+    /// Limit on the frequency adjustment in ppb
+    pub max_ppb: f64,
+    /// Offset measurements taken each tick, of which the one with the smallest delay is used
+    pub samples: u32,
+    /// Number of ticks whose offsets are averaged before being passed to the servo
+    pub window: usize,
+}
+
+impl Default for DisciplineConfig {
+    fn default() -> Self {
+        DisciplineConfig {
+            kp: 0.7,
+            ki: 0.3,
+            step_threshold_ns: 1_000_000,
+            max_ppb: 500_000.0,
+            samples: 1,
+            window: 4,
+        }
+    }
+}
+
+/// What a [`Discipline`] loop measured and did in one tick
+#[derive(Debug, Clone, Copy)]
+pub struct DisciplineStatus {
+    /// The measurement used this tick
+    pub measurement: OffsetMeasurement,
+    /// The moving average of recent offsets which was passed to the servo
+    pub filtered_offset_ns: i64,
+    /// The correction which was applied
+    pub correction: FrequencyCorrection,
+}
+
+/// A PHC disciplining loop: measure, average, and correct the clock once per [`Discipline::tick`]
+///
+/// Created with [`PtpDevice::run_discipline`].  Each tick the offset to the system clock is
+/// measured, averaged with those of the previous ticks in the window, and passed to a
+/// [`PiServo`].  An offset beyond the step threshold bypasses the average, so the clock is
+/// stepped as soon as it is seen, and the window restarts afterwards.  The caller decides when
+/// to tick; the servo's gains assume about once per second.
+pub struct Discipline<'a> {
+    device: &'a PtpDevice,
+    config: DisciplineConfig,
+    servo: PiServo,
+    offsets: VecDeque<i64>,
+}
+
+impl Discipline<'_> {
+    /// Measure the clock and apply one correction
+    pub fn tick(&mut self) -> Result<DisciplineStatus> {
+        let mut measurement = self.device.measure_offset()?;
+        for _ in 1..self.config.samples {
+            let m = self.device.measure_offset()?;
+            if m.delay_ns < measurement.delay_ns {
+                measurement = m;
+            }
+        }
+
+        let offset_ns = measurement.offset_ns;
+        let threshold = self.config.step_threshold_ns;
+        let filtered_offset_ns = if threshold > 0 && offset_ns.unsigned_abs() > threshold as u64 {
+            self.offsets.clear();
+            offset_ns
+        } else {
+            if self.offsets.len() == self.config.window {
+                self.offsets.pop_front();
+            }
+            self.offsets.push_back(offset_ns);
+            let sum: i128 = self.offsets.iter().map(|&offset| offset as i128).sum();
+            (sum / self.offsets.len() as i128) as i64
+        };
+
+        let correction = self.servo.sample(filtered_offset_ns);
+        if let Some(step_ns) = correction.step_ns {
+            self.device.step_clock(step_ns)?;
+        }
+        self.device.adjust_frequency(correction.ppb)?;
+        Ok(DisciplineStatus {
+            measurement,
+            filtered_offset_ns,
+            correction,
+        })
+    }
+
+    /// The servo driving the loop, e.g. for its drift estimate
+    pub fn servo(&self) -> &PiServo {
+        &self.servo
+    }
+}
+
+impl PtpDevice {
+    /// Start disciplining this clock to the system clock
+    ///
+    /// The device must have been opened read-write.  The servo starts from the frequency
+    /// adjustment currently applied to the clock, so restarting a loop does not disturb it.
+    /// Fails with `ErrorKind::InvalidInput` if `samples` or `window` is zero.
+    pub fn run_discipline(&self, config: DisciplineConfig) -> Result<Discipline<'_>> {
+        self.require_writable("run_discipline")?;
+        if config.samples == 0 || config.window == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "discipline samples and window must be non-zero",
+            ));
+        }
+        let servo = PiServo::new(config.kp, config.ki)
+            .with_step_threshold(config.step_threshold_ns)
+            .with_max_frequency(config.max_ppb)
+            .with_drift(self.current_frequency_ppb()?);
+        Ok(Discipline {
+            device: self,
+            config,
+            servo,
+            offsets: VecDeque::with_capacity(config.window),
+        })
+    }
+
+    /// Measure the offset to the system clock, run it through `servo`, and apply the result
+    ///
+    /// The device must have been opened read-write.  Returns the correction which was applied.
+    pub fn discipline_step(&self, servo: &mut PiServo) -> Result<FrequencyCorrection> {
+        self.require_writable("discipline_step")?;
+        let measurement = self.measure_offset()?;
+        let correction = servo.sample(measurement.offset_ns);
+        if let Some(step_ns) = correction.step_ns {
+            self.step_clock(step_ns)?;
+        }
+        self.adjust_frequency(correction.ppb)?;
+        Ok(correction)
+    }
+
+    /// Apply `correction`, limiting how fast the frequency adjustment changes
+    ///
+    /// The frequency moves towards `correction.ppb` by at most `max_rate_ppb_per_s` for each second
+    /// of `dt`, the time since the previous call, starting from the frequency this method last
+    /// applied, or the clock's current frequency on the first call.  A step in the correction is
+    /// applied in full first.  The device must have been opened read-write.  Returns the frequency
+    /// actually applied, which is also what the next call starts from.  Fails with
+    /// `ErrorKind::InvalidInput` unless `max_rate_ppb_per_s` is positive.
+    pub fn apply_correction_limited(
+        &self,
+        correction: FrequencyCorrection,
+        max_rate_ppb_per_s: f64,
+        dt: Duration,
+    ) -> Result<f64> {
+        if max_rate_ppb_per_s.is_nan() || max_rate_ppb_per_s <= 0.0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("frequency rate limit must be positive, not {max_rate_ppb_per_s}"),
+            ));
+        }
+        self.require_writable("apply_correction_limited")?;
+        let mut limited = lock(&self.limited_ppb);
+        let last = match *limited {
+            Some(ppb) => ppb,
+            None => self.current_frequency_ppb()?,
+        };
+        let max_change = max_rate_ppb_per_s * dt.as_secs_f64();
+        let ppb = last + (correction.ppb - last).clamp(-max_change, max_change);
+        if let Some(step_ns) = correction.step_ns {
+            self.step_clock(step_ns)?;
+        }
+        self.adjust_frequency(ppb)?;
+        *limited = Some(ppb);
+        Ok(ppb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[test]
+    fn limited_correction_validates_before_touching_the_clock() {
+        let device = null_device();
+        let correction = FrequencyCorrection { ppb: 100.0, step_ns: None };
+        let e = device.apply_correction_limited(correction, f64::NAN, Duration::from_secs(1)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        let e = device.apply_correction_limited(correction, 10.0, Duration::from_secs(1)).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::PermissionDenied);
+    }
+}
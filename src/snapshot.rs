@@ -0,0 +1,40 @@
+//! One-shot diagnostic capture of a device's state
+
+use std::io::Result;
+
+use crate::{ptp::*, ClockCaps, OffsetMeasurement, PinDescriptor, PtpDevice};
+
+/// Everything about a clock at one moment, for diagnostics and bug reports
+///
+/// Only the capabilities and time are required; the remaining fields are `None` if they could
+/// not be read, so a snapshot can be taken of a partially working device.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceSnapshot {
+    /// The kernel name of the device, e.g. `ptp0`
+    pub device: Option<String>,
+    /// The driver-assigned clock name
+    pub clock_name: Option<String>,
+    /// The clock's capabilities
+    pub caps: ClockCaps,
+    /// The device time when the snapshot was taken
+    pub time: ptp_clock_time,
+    /// An offset measurement using the best available method
+    pub offset: Option<OffsetMeasurement>,
+    /// The programmable pins and their current functions
+    pub pins: Option<Vec<PinDescriptor>>,
+}
+
+impl PtpDevice {
+    /// Capture the device's capabilities, time, offset, pins, and name in one structure
+    pub fn snapshot(&self) -> Result<DeviceSnapshot> {
+        Ok(DeviceSnapshot {
+            device: self.kernel_name().ok(),
+            clock_name: self.clock_name().ok(),
+            caps: self.cached_caps()?.into(),
+            time: self.read_time()?,
+            offset: self.measure_offset().ok(),
+            pins: self.pins().ok(),
+        })
+    }
+}
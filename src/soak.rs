@@ -0,0 +1,260 @@
+//! Long-running qualification of a PHC against offset and MTIE limits
+
+use std::{
+    io::{Result, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    stats::{RollingStats, TimeErrorStats},
+    CsvLogger, OffsetMeasurement, PtpDevice, SampleSchedule,
+};
+
+/// Limits and schedule for a [`SoakTest`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoakConfig {
+    /// When offsets are sampled
+    pub schedule: SampleSchedule,
+    /// How long the test runs for
+    pub duration: Duration,
+    /// Flag offsets larger than this many nanoseconds; 0 disables the check
+    pub max_offset_ns: i64,
+    /// Flag changes in offset between consecutive samples larger than this many nanoseconds; 0
+    /// disables the check
+    pub step_threshold_ns: i64,
+    /// The highest MTIE in nanoseconds allowed at each observation interval
+    pub mtie_mask: Vec<(Duration, f64)>,
+}
+
+impl Default for SoakConfig {
+    fn default() -> Self {
+        SoakConfig {
+            schedule: SampleSchedule::FixedInterval(Duration::from_secs(1)),
+            duration: Duration::from_secs(24 * 60 * 60),
+            max_offset_ns: 1_000,
+            step_threshold_ns: 1_000,
+            mtie_mask: Vec::new(),
+        }
+    }
+}
+
+/// Something a [`SoakTest`] found wrong, identified by the index of the sample it was found at
+#[derive(Debug, Clone, PartialEq)]
+pub enum Anomaly {
+    /// The offset changed by `jump_ns` since the previous successful sample
+    Step {
+        /// Index of the sample after the step
+        sample: u64,
+        /// Change in offset in nanoseconds
+        jump_ns: i64,
+    },
+    /// The offset exceeded [`SoakConfig::max_offset_ns`]
+    OffsetExceeded {
+        /// Index of the sample
+        sample: u64,
+        /// The offset in nanoseconds
+        offset_ns: i64,
+    },
+    /// Measuring the offset failed, leaving a gap in the record
+    SampleFailed {
+        /// Index of the sample
+        sample: u64,
+        /// The error the measurement failed with
+        error: String,
+    },
+    /// The MTIE over the run exceeded [`SoakConfig::mtie_mask`] at an observation interval
+    MtieExceeded {
+        /// The observation interval actually used, as reported by [`TimeErrorStats::mtie`]
+        tau: Duration,
+        /// The MTIE in nanoseconds
+        mtie_ns: f64,
+        /// The limit from the mask in nanoseconds
+        limit_ns: f64,
+    },
+    /// The run had too few samples to evaluate the MTIE at an interval of
+    /// [`SoakConfig::mtie_mask`], e.g. because it was cut short, so that limit was not checked
+    MtieNotEvaluated {
+        /// The observation interval from the mask
+        tau: Duration,
+    },
+}
+
+/// The outcome of a [`SoakTest`]
+#[derive(Debug, Clone)]
+pub struct SoakReport {
+    /// Number of samples attempted, including those which failed
+    pub samples: u64,
+    /// Summary statistics of the successful samples' offsets
+    pub stats: RollingStats,
+    /// The MTIE at each interval of the mask with enough samples, as `(tau, mtie_ns)`
+    pub mtie: Vec<(Duration, f64)>,
+    /// Everything flagged during the run, in the order it was found
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl SoakReport {
+    /// Whether the run met every limit of its configuration, with no failed samples
+    pub fn passed(&self) -> bool {
+        self.anomalies.is_empty()
+    }
+}
+
+/// A qualification run which samples a PHC's offset for a long time and checks it against limits
+///
+/// Created with [`PtpDevice::soak_test`].  Every successful sample is written to the writer as a
+/// [`CsvLogger`] row and flushed straight away, so a crash loses at most the sample in progress.
+/// Steps, offsets beyond the limit and failed samples are flagged as they happen; the MTIE mask
+/// is checked over the whole run when it finishes, and any interval of it the run was too short
+/// to evaluate is flagged too.
+pub struct SoakTest<'a, W: Write> {
+    device: &'a PtpDevice,
+    config: SoakConfig,
+    logger: CsvLogger<W>,
+    samples: u64,
+    last_offset: Option<i64>,
+    stats: RollingStats,
+    time_error: TimeErrorStats,
+    anomalies: Vec<Anomaly>,
+}
+
+impl<W: Write> SoakTest<'_, W> {
+    /// Record the result of one sample, failing only if it cannot be written out
+    fn record(&mut self, result: Result<OffsetMeasurement>) -> Result<()> {
+        let sample = self.samples;
+        self.samples += 1;
+        let m = match result {
+            Ok(m) => m,
+            Err(e) => {
+                let error = e.to_string();
+                self.anomalies.push(Anomaly::SampleFailed { sample, error });
+                return Ok(());
+            }
+        };
+        self.logger.log(&m)?;
+        self.logger.flush()?;
+
+        let max_offset_ns = self.config.max_offset_ns;
+        if max_offset_ns > 0 && m.offset_ns.unsigned_abs() > max_offset_ns as u64 {
+            self.anomalies.push(Anomaly::OffsetExceeded { sample, offset_ns: m.offset_ns });
+        }
+        let threshold = self.config.step_threshold_ns;
+        if let Some(last) = self.last_offset.replace(m.offset_ns) {
+            let jump_ns = m.offset_ns.saturating_sub(last);
+            if threshold > 0 && jump_ns.unsigned_abs() > threshold as u64 {
+                self.anomalies.push(Anomaly::Step { sample, jump_ns });
+            }
+        }
+        self.stats.update(&m);
+        self.time_error.add_measurement(&m);
+        Ok(())
+    }
+
+    /// Check the MTIE mask and produce the report
+    fn finish(mut self) -> SoakReport {
+        let mut mtie = Vec::new();
+        for &(tau, limit_ns) in &self.config.mtie_mask {
+            let evaluated = self.time_error.mtie(&[tau]);
+            if evaluated.is_empty() {
+                self.anomalies.push(Anomaly::MtieNotEvaluated { tau });
+            }
+            for (tau, mtie_ns) in evaluated {
+                if mtie_ns > limit_ns {
+                    self.anomalies.push(Anomaly::MtieExceeded { tau, mtie_ns, limit_ns });
+                }
+                mtie.push((tau, mtie_ns));
+            }
+        }
+        SoakReport {
+            samples: self.samples,
+            stats: self.stats,
+            mtie,
+            anomalies: self.anomalies,
+        }
+    }
+
+    /// Sample for the configured duration and report the results
+    ///
+    /// Fails only if writing a record fails; measurement failures are reported as anomalies.
+    pub fn run(mut self) -> Result<SoakReport> {
+        let start = Instant::now();
+        for result in self.device.sample_offsets(self.config.schedule) {
+            if start.elapsed() > self.config.duration {
+                break;
+            }
+            self.record(result)?;
+        }
+        Ok(self.finish())
+    }
+}
+
+impl PtpDevice {
+    /// Prepare a [`SoakTest`] of this device, streaming its samples to `writer` as CSV
+    pub fn soak_test<W: Write>(&self, config: SoakConfig, writer: W) -> SoakTest<'_, W> {
+        SoakTest {
+            device: self,
+            config,
+            logger: CsvLogger::new(writer),
+            samples: 0,
+            last_offset: None,
+            stats: RollingStats::new(Duration::MAX),
+            time_error: TimeErrorStats::new(),
+            anomalies: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind};
+
+    use super::*;
+    use crate::test_support::{measurement, null_device};
+
+    #[test]
+    fn flags_anomalies_and_checks_the_mask() {
+        let device = null_device();
+        let config = SoakConfig {
+            max_offset_ns: 100,
+            step_threshold_ns: 50,
+            mtie_mask: vec![(Duration::from_secs(1), 1_000.0), (Duration::from_secs(2), 100.0)],
+            ..Default::default()
+        };
+        let mut rows = Vec::new();
+        let mut soak = device.soak_test(config, &mut rows);
+        for (sec, offset_ns) in [(0, 10), (1, 20), (3, 150), (4, 90)] {
+            soak.record(Ok(measurement(sec, offset_ns))).unwrap();
+            if sec == 1 {
+                soak.record(Err(Error::from(ErrorKind::TimedOut))).unwrap();
+            }
+        }
+        let report = soak.finish();
+
+        assert_eq!(report.samples, 5);
+        assert_eq!(report.stats.count(), 4);
+        assert_eq!(report.stats.max(), Some(150));
+        assert!(!report.passed());
+        assert_eq!(report.anomalies.len(), 5, "{:?}", report.anomalies);
+        assert!(matches!(report.anomalies[0], Anomaly::SampleFailed { sample: 2, .. }));
+        assert_eq!(report.anomalies[1], Anomaly::OffsetExceeded { sample: 3, offset_ns: 150 });
+        assert_eq!(report.anomalies[2], Anomaly::Step { sample: 3, jump_ns: 130 });
+        assert_eq!(report.anomalies[3], Anomaly::Step { sample: 4, jump_ns: -60 });
+        assert!(matches!(report.anomalies[4], Anomaly::MtieExceeded { limit_ns: 100.0, .. }));
+        assert_eq!(String::from_utf8(rows).unwrap().lines().count(), 5);
+    }
+
+    #[test]
+    fn short_runs_do_not_pass_the_mask() {
+        let device = null_device();
+        let config = SoakConfig {
+            mtie_mask: vec![(Duration::from_secs(60), 1_000.0)],
+            ..Default::default()
+        };
+        let mut soak = device.soak_test(config, std::io::sink());
+        for sec in 0..3 {
+            soak.record(Ok(measurement(sec, 0))).unwrap();
+        }
+        let report = soak.finish();
+        assert!(!report.passed());
+        assert_eq!(report.anomalies, vec![Anomaly::MtieNotEvaluated { tau: Duration::from_secs(60) }]);
+    }
+}
@@ -0,0 +1,560 @@
+//! Statistical analysis of collected offset measurements
+
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{ptp::ptp_clock_time, OffsetMeasurement};
+
+/// A series of time error samples, the storage shared by [`AllanDeviation`] and
+/// [`TimeErrorStats`]
+#[derive(Debug, Clone, Default)]
+struct PhaseSeries {
+    /// Timestamps in seconds and time errors in nanoseconds
+    samples: Vec<(f64, f64)>,
+}
+
+impl PhaseSeries {
+    /// Add an offset (in nanoseconds) captured at `timestamp`
+    fn push(&mut self, timestamp: ptp_clock_time, offset_ns: i64) {
+        self.samples.push((timestamp.as_nanos() as f64 / 1e9, offset_ns as f64));
+    }
+
+    /// Number of samples
+    fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// The time errors in nanoseconds, oldest first
+    fn phases_ns(&self) -> impl Iterator<Item = f64> + '_ {
+        self.samples.iter().map(|s| s.1)
+    }
+
+    /// Mean interval between samples in seconds, if there are at least two
+    fn tau0(&self) -> Option<f64> {
+        let (first, last) = (self.samples.first()?, self.samples.last()?);
+        let n = self.samples.len();
+        (n >= 2 && last.0 > first.0).then(|| (last.0 - first.0) / (n - 1) as f64)
+    }
+
+    /// Each interval as a multiple `n` of `tau0` no greater than `limit`, with the effective
+    /// interval `n * tau0`; those which round to zero or exceed `limit` are left out
+    fn multiples(&self, taus: &[Duration], limit: usize) -> Vec<(usize, Duration)> {
+        let Some(tau0) = self.tau0() else {
+            return Vec::new();
+        };
+        taus.iter()
+            .map(|tau| (tau.as_secs_f64() / tau0).round() as usize)
+            .filter(|&n| n > 0 && n <= limit)
+            .map(|n| (n, Duration::from_secs_f64(n as f64 * tau0)))
+            .collect()
+    }
+}
+
+/// Accumulator for the overlapping Allan deviation of a series of PHC offsets
+///
+/// Offsets are treated as phase (time error) samples.  The samples are assumed to be taken at a
+/// roughly constant interval; the basic interval `tau0` is taken as the mean spacing of the
+/// timestamps supplied.
+#[derive(Debug, Clone, Default)]
+pub struct AllanDeviation {
+    series: PhaseSeries,
+}
+
+impl AllanDeviation {
+    /// Create an empty accumulator
+    pub fn new() -> AllanDeviation {
+        AllanDeviation::default()
+    }
+
+    /// Add an offset (in nanoseconds) captured at `timestamp`
+    pub fn add(&mut self, timestamp: ptp_clock_time, offset_ns: i64) {
+        self.series.push(timestamp, offset_ns);
+    }
+
+    /// Add an offset measurement, using its system time as the timestamp
+    pub fn add_measurement(&mut self, m: &OffsetMeasurement) {
+        self.add(m.sys_time, m.offset_ns);
+    }
+
+    /// Number of samples collected
+    pub fn len(&self) -> usize {
+        self.series.len()
+    }
+
+    /// Whether no samples have been collected
+    pub fn is_empty(&self) -> bool {
+        self.series.len() == 0
+    }
+
+    /// Mean interval between samples in seconds, if at least two samples have been collected
+    pub fn tau0(&self) -> Option<f64> {
+        self.series.tau0()
+    }
+
+    /// Compute the overlapping Allan deviation at each of the requested averaging intervals
+    ///
+    /// Each tau is rounded to the nearest multiple `m` of `tau0`.  Taus which round to zero or
+    /// which need more than the available samples (`2m + 1`) are omitted from the result, so the
+    /// returned `(tau, adev)` pairs may be fewer than requested.  The tau reported is the
+    /// effective `m * tau0` actually used.
+    pub fn compute(&self, taus: &[Duration]) -> Vec<(Duration, f64)> {
+        // Phases in seconds, so the deviation is dimensionless
+        let x: Vec<f64> = self.series.phases_ns().map(|x| x / 1e9).collect();
+        let (n, tau0) = (x.len(), self.series.tau0().unwrap_or_default());
+        self.series
+            .multiples(taus, n.saturating_sub(1) / 2)
+            .into_iter()
+            .map(|(m, tau)| {
+                let terms = n - 2 * m;
+                let sum: f64 = (0..terms)
+                    .map(|i| {
+                        let d = x[i + 2 * m] - 2.0 * x[i + m] + x[i];
+                        d * d
+                    })
+                    .sum();
+                let tau_s = m as f64 * tau0;
+                let avar = sum / (2.0 * tau_s * tau_s * terms as f64);
+                (tau, avar.sqrt())
+            })
+            .collect()
+    }
+}
+
+impl Extend<(ptp_clock_time, i64)> for AllanDeviation {
+    fn extend<I: IntoIterator<Item = (ptp_clock_time, i64)>>(&mut self, iter: I) {
+        for (timestamp, offset_ns) in iter {
+            self.add(timestamp, offset_ns);
+        }
+    }
+}
+
+/// Accumulator for the Maximum Time Interval Error and Time Deviation of a series of PHC offsets
+///
+/// These are the ITU-T G.810 wander metrics that telecom masks are specified in.  As for
+/// [`AllanDeviation`], offsets are treated as time error samples taken at a roughly constant
+/// interval, with `tau0` the mean spacing of the timestamps.  Both metrics are reported in
+/// nanoseconds.
+#[derive(Debug, Clone, Default)]
+pub struct TimeErrorStats {
+    series: PhaseSeries,
+}
+
+impl TimeErrorStats {
+    /// Create an empty accumulator
+    pub fn new() -> TimeErrorStats {
+        TimeErrorStats::default()
+    }
+
+    /// Add an offset (in nanoseconds) captured at `timestamp`
+    pub fn add(&mut self, timestamp: ptp_clock_time, offset_ns: i64) {
+        self.series.push(timestamp, offset_ns);
+    }
+
+    /// Add an offset measurement, using its system time as the timestamp
+    pub fn add_measurement(&mut self, m: &OffsetMeasurement) {
+        self.add(m.sys_time, m.offset_ns);
+    }
+
+    /// Number of samples collected
+    pub fn len(&self) -> usize {
+        self.series.len()
+    }
+
+    /// Whether no samples have been collected
+    pub fn is_empty(&self) -> bool {
+        self.series.len() == 0
+    }
+
+    /// Mean interval between samples in seconds, if at least two samples have been collected
+    pub fn tau0(&self) -> Option<f64> {
+        self.series.tau0()
+    }
+
+    /// Compute the MTIE at each of the requested observation intervals
+    ///
+    /// The MTIE at tau is the largest peak-to-peak time error within any window of tau.  Each tau
+    /// is rounded to the nearest multiple `n` of `tau0`; taus which round to zero or need more
+    /// than the available samples (`n + 1`) are omitted, so the returned `(tau, mtie)` pairs may
+    /// be fewer than requested.  The tau reported is the effective `n * tau0` actually used.
+    pub fn mtie(&self, taus: &[Duration]) -> Vec<(Duration, f64)> {
+        let x: Vec<f64> = self.series.phases_ns().collect();
+        self.series.multiples(taus, x.len().saturating_sub(1))
+            .into_iter()
+            .map(|(n, tau)| {
+                // Indices of the window's running maximum and minimum, in decreasing and
+                // increasing order of value respectively
+                let (mut max, mut min) = (VecDeque::new(), VecDeque::new());
+                let mut mtie = 0f64;
+                for i in 0..x.len() {
+                    while max.back().is_some_and(|&j| x[j] <= x[i]) {
+                        max.pop_back();
+                    }
+                    while min.back().is_some_and(|&j| x[j] >= x[i]) {
+                        min.pop_back();
+                    }
+                    max.push_back(i);
+                    min.push_back(i);
+                    if i < n {
+                        continue;
+                    }
+                    let start = i - n;
+                    while max.front().is_some_and(|&j| j < start) {
+                        max.pop_front();
+                    }
+                    while min.front().is_some_and(|&j| j < start) {
+                        min.pop_front();
+                    }
+                    mtie = mtie.max(x[max[0]] - x[min[0]]);
+                }
+                (tau, mtie)
+            })
+            .collect()
+    }
+
+    /// Compute the TDEV at each of the requested observation intervals
+    ///
+    /// Taus are rounded to a multiple `n` of `tau0` as for [`TimeErrorStats::mtie`], and omitted
+    /// if they need more than the available samples (`3n + 1`).
+    pub fn tdev(&self, taus: &[Duration]) -> Vec<(Duration, f64)> {
+        // prefix[i] is the sum of the first i time errors, so each window sum is a difference
+        let prefix: Vec<f64> = std::iter::once(0.0)
+            .chain(self.series.phases_ns().scan(0.0, |sum, x| {
+                *sum += x;
+                Some(*sum)
+            }))
+            .collect();
+        let len = self.series.len();
+        let sum = |from: usize, n: usize| prefix[from + n] - prefix[from];
+        self.series.multiples(taus, len.saturating_sub(1) / 3)
+            .into_iter()
+            .map(|(n, tau)| {
+                let terms = len - 3 * n + 1;
+                let total: f64 = (0..terms)
+                    .map(|j| {
+                        let d = sum(j + 2 * n, n) - 2.0 * sum(j + n, n) + sum(j, n);
+                        d * d
+                    })
+                    .sum();
+                let tvar = total / (6.0 * (n * n) as f64 * terms as f64);
+                (tau, tvar.sqrt())
+            })
+            .collect()
+    }
+}
+
+impl Extend<(ptp_clock_time, i64)> for TimeErrorStats {
+    fn extend<I: IntoIterator<Item = (ptp_clock_time, i64)>>(&mut self, iter: I) {
+        for (timestamp, offset_ns) in iter {
+            self.add(timestamp, offset_ns);
+        }
+    }
+}
+
+/// The frequency error of the PHC relative to the system clock between two measurements, in ppb
+///
+/// This is the change in offset divided by the system time elapsed.  Since offset = phc - sys, a
+/// positive result means the PHC is running fast.  Returns `None` unless `end` was taken after
+/// `start` by the system clock.
+pub fn estimate_frequency_ppb(start: &OffsetMeasurement, end: &OffsetMeasurement) -> Option<f64> {
+    let sys_elapsed = end.sys_time.as_nanos() - start.sys_time.as_nanos();
+    (sys_elapsed > 0).then(|| (end.offset_ns - start.offset_ns) as f64 / sys_elapsed as f64 * 1e9)
+}
+
+/// Iterator adaptor turning a series of offset measurements into frequency error estimates
+///
+/// Each item is the system time of a measurement and the [`estimate_frequency_ppb`] between it
+/// and the one before.  The first measurement has no predecessor and yields nothing, and a
+/// measurement whose system time does not advance on its predecessor is skipped, so the output
+/// has at most one fewer item than the input.
+#[derive(Debug, Clone)]
+pub struct FrequencyEstimator<I> {
+    inner: I,
+    prev: Option<OffsetMeasurement>,
+}
+
+impl<I: Iterator<Item = OffsetMeasurement>> FrequencyEstimator<I> {
+    /// Wrap an iterator of offset measurements
+    pub fn new<T: IntoIterator<IntoIter = I>>(measurements: T) -> FrequencyEstimator<I> {
+        FrequencyEstimator {
+            inner: measurements.into_iter(),
+            prev: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = OffsetMeasurement>> Iterator for FrequencyEstimator<I> {
+    type Item = (ptp_clock_time, f64);
+
+    fn next(&mut self) -> Option<(ptp_clock_time, f64)> {
+        loop {
+            let m = self.inner.next()?;
+            match self.prev.replace(m) {
+                Some(prev) => match estimate_frequency_ppb(&prev, &m) {
+                    Some(ppb) => return Some((m.sys_time, ppb)),
+                    // Keep the earlier measurement as the base rather than one which went backwards
+                    None => self.prev = Some(prev),
+                },
+                None => continue,
+            }
+        }
+    }
+}
+
+/// Summary statistics over the offset measurements within a sliding time window
+///
+/// Measurements are timestamped by their system time; adding a measurement evicts any older
+/// than `window` before it.  Each update is O(1) amortized.
+#[derive(Debug, Clone)]
+pub struct RollingStats {
+    window: Duration,
+    /// (timestamp ns, offset ns) in arrival order
+    samples: VecDeque<(i128, i64)>,
+    /// Monotonic queues of candidates for the minimum and maximum offset
+    min_queue: VecDeque<(i128, i64)>,
+    max_queue: VecDeque<(i128, i64)>,
+    /// Running sums, using wrapping arithmetic so additions and removals cancel exactly
+    sum: i128,
+    sum_sq: i128,
+}
+
+impl RollingStats {
+    /// Create an empty set of statistics covering the given window
+    pub fn new(window: Duration) -> RollingStats {
+        RollingStats {
+            window,
+            samples: VecDeque::new(),
+            min_queue: VecDeque::new(),
+            max_queue: VecDeque::new(),
+            sum: 0,
+            sum_sq: 0,
+        }
+    }
+
+    /// The window covered by these statistics
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Add a measurement, evicting those which have fallen out of the window
+    pub fn update(&mut self, m: &OffsetMeasurement) {
+        let now = m.sys_time.as_nanos();
+        let value = m.offset_ns;
+
+        self.samples.push_back((now, value));
+        self.sum = self.sum.wrapping_add(value as i128);
+        self.sum_sq = self.sum_sq.wrapping_add(value as i128 * value as i128);
+        while self.min_queue.back().is_some_and(|&(_, v)| v >= value) {
+            self.min_queue.pop_back();
+        }
+        self.min_queue.push_back((now, value));
+        while self.max_queue.back().is_some_and(|&(_, v)| v <= value) {
+            self.max_queue.pop_back();
+        }
+        self.max_queue.push_back((now, value));
+
+        let cutoff = now - self.window.as_nanos() as i128;
+        while let Some(&(t, v)) = self.samples.front() {
+            if t >= cutoff {
+                break;
+            }
+            self.samples.pop_front();
+            self.sum = self.sum.wrapping_sub(v as i128);
+            self.sum_sq = self.sum_sq.wrapping_sub(v as i128 * v as i128);
+        }
+        while self.min_queue.front().is_some_and(|&(t, _)| t < cutoff) {
+            self.min_queue.pop_front();
+        }
+        while self.max_queue.front().is_some_and(|&(t, _)| t < cutoff) {
+            self.max_queue.pop_front();
+        }
+    }
+
+    /// Number of measurements currently within the window
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Mean offset in nanoseconds
+    pub fn mean(&self) -> Option<f64> {
+        let n = self.samples.len();
+        (n > 0).then(|| self.sum as f64 / n as f64)
+    }
+
+    /// Population standard deviation of the offset in nanoseconds
+    pub fn stddev(&self) -> Option<f64> {
+        let n = self.samples.len() as i128;
+        if n == 0 {
+            return None;
+        }
+        // n^2 * variance = n * sum(x^2) - sum(x)^2, which is exact in integers
+        let scaled = n
+            .wrapping_mul(self.sum_sq)
+            .wrapping_sub(self.sum.wrapping_mul(self.sum));
+        Some((scaled as f64).sqrt() / n as f64)
+    }
+
+    /// Minimum offset in nanoseconds
+    pub fn min(&self) -> Option<i64> {
+        self.min_queue.front().map(|&(_, v)| v)
+    }
+
+    /// Maximum offset in nanoseconds
+    pub fn max(&self) -> Option<i64> {
+        self.max_queue.front().map(|&(_, v)| v)
+    }
+}
+
+/// Least-squares trend of the offset over a sliding time window
+///
+/// As for [`RollingStats`], measurements are timestamped by their system time and those older
+/// than `window` before the newest are evicted.  The fitted slope is the frequency error of the
+/// PHC relative to the system clock, which a servo can use to anticipate where the offset is
+/// heading rather than react to where it is.
+#[derive(Debug, Clone)]
+pub struct OffsetTrend {
+    window: Duration,
+    /// (timestamp ns, offset ns) in arrival order
+    samples: VecDeque<(i128, i64)>,
+}
+
+impl OffsetTrend {
+    /// Create an empty trend covering the given window
+    pub fn new(window: Duration) -> OffsetTrend {
+        OffsetTrend {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// The window covered by the trend
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Add a measurement, evicting those which have fallen out of the window
+    pub fn update(&mut self, m: &OffsetMeasurement) {
+        let now = m.sys_time.as_nanos();
+        self.samples.push_back((now, m.offset_ns));
+        let cutoff = now - self.window.as_nanos() as i128;
+        while self.samples.front().is_some_and(|&(t, _)| t < cutoff) {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Number of measurements currently within the window
+    pub fn count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// The fitted line as (slope in ns per second, offset in ns at the newest sample)
+    ///
+    /// Times are taken relative to the newest sample, so that the f64 arithmetic does not lose
+    /// precision to the size of Unix timestamps in nanoseconds.
+    fn fit(&self) -> Option<(f64, f64)> {
+        let &(newest, _) = self.samples.back()?;
+        let n = self.samples.len() as f64;
+        let points = || self.samples.iter().map(|&(t, offset)| ((t - newest) as f64 / 1e9, offset as f64));
+        let (mean_t, mean_offset) = points().fold((0.0, 0.0), |(t, o), (pt, po)| (t + pt / n, o + po / n));
+        let (sto, stt) = points().fold((0.0, 0.0), |(sto, stt), (t, o)| {
+            (sto + (t - mean_t) * (o - mean_offset), stt + (t - mean_t) * (t - mean_t))
+        });
+        if stt == 0.0 {
+            return None;
+        }
+        let slope = sto / stt;
+        Some((slope, mean_offset - slope * mean_t))
+    }
+
+    /// The rate of change of the offset in ppb (nanoseconds per second)
+    ///
+    /// A positive slope means the PHC is gaining on the system clock.  Returns `None` until the
+    /// window holds measurements at two different times.
+    pub fn slope_ppb(&self) -> Option<f64> {
+        self.fit().map(|(slope, _)| slope)
+    }
+
+    /// The offset in nanoseconds the trend predicts `ahead` of the newest measurement
+    pub fn predict(&self, ahead: Duration) -> Option<f64> {
+        self.fit().map(|(slope, offset)| offset + slope * ahead.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::measurement;
+
+    fn at(sec: i64) -> ptp_clock_time {
+        ptp_clock_time { sec, ..Default::default() }
+    }
+
+    #[test]
+    fn constant_frequency_offset_has_zero_adev() {
+        let mut adev = AllanDeviation::new();
+        adev.extend((0..100).map(|i| (at(i), i * 50)));
+        for (_, dev) in adev.compute(&[Duration::from_secs(1), Duration::from_secs(10)]) {
+            assert!(dev.abs() < 1e-15);
+        }
+    }
+
+    #[test]
+    fn alternating_phase() {
+        // x alternates 0, 1ns, 0, 1ns: at tau = 1s each second difference is +/-2ns
+        let mut adev = AllanDeviation::new();
+        adev.extend((0..11).map(|i| (at(i), i % 2)));
+        let result = adev.compute(&[Duration::from_secs(1), Duration::from_secs(6)]);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].0, Duration::from_secs(1));
+        assert!((result[0].1 - 2e-9 / 2f64.sqrt()).abs() < 1e-18);
+    }
+
+    #[test]
+    fn mtie_and_tdev_of_known_series() {
+        let mut ramp = TimeErrorStats::new();
+        ramp.extend((0..31).map(|i| (at(i), i * 50)));
+        let taus = [Duration::from_secs(1), Duration::from_secs(10), Duration::from_secs(40)];
+        assert_eq!(ramp.mtie(&taus), vec![(taus[0], 50.0), (taus[1], 500.0)]);
+        // A constant frequency offset has no second difference
+        assert!(ramp.tdev(&taus).iter().all(|&(_, tdev)| tdev.abs() < 1e-9));
+
+        // x alternates 0, 1ns, 0, 1ns: at n = 1 each second difference is +/-2ns
+        let mut alternating = TimeErrorStats::new();
+        alternating.extend((0..11).map(|i| (at(i), i % 2)));
+        assert_eq!(alternating.mtie(&taus[..2]), vec![(taus[0], 1.0), (taus[1], 1.0)]);
+        let tdev = alternating.tdev(&taus);
+        assert_eq!(tdev.len(), 1);
+        assert!((tdev[0].1 - (2f64 / 3.0).sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn frequency_estimator_skips_first_and_stalled_samples() {
+        let input = [measurement(0, 0), measurement(1, 50), measurement(1, 70), measurement(3, 10)];
+        let output: Vec<_> = FrequencyEstimator::new(input).map(|(t, ppb)| (t.sec, ppb)).collect();
+        assert_eq!(output, vec![(1, 50.0), (3, -20.0)]);
+    }
+
+    #[test]
+    fn trend_fits_slope_over_window() {
+        let mut trend = OffsetTrend::new(Duration::from_secs(3));
+        trend.update(&measurement(0, 10_000));
+        assert_eq!(trend.slope_ppb(), None);
+        for (sec, offset) in [(1, 0), (2, 50), (3, 100), (4, 150)] {
+            trend.update(&measurement(sec, offset));
+        }
+        assert_eq!(trend.count(), 4);
+        assert!((trend.slope_ppb().unwrap() - 50.0).abs() < 1e-9);
+        assert!((trend.predict(Duration::from_secs(2)).unwrap() - 250.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rolling_window_evicts_old_samples() {
+        let mut stats = RollingStats::new(Duration::from_secs(2));
+        for (sec, offset) in [(0, 100), (1, -50), (2, 10), (3, 20), (4, 30)] {
+            stats.update(&measurement(sec, offset));
+        }
+        assert_eq!(stats.count(), 3);
+        assert_eq!(stats.min(), Some(10));
+        assert_eq!(stats.max(), Some(30));
+        assert_eq!(stats.mean(), Some(20.0));
+        assert!((stats.stddev().unwrap() - (200f64 / 3.0).sqrt()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,502 @@
+//! Discovery of PTP device attributes through sysfs
+
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{ptp::ptp_clock_caps, OffsetMethod, PinFunction, PtpDevice, PtpError};
+
+/// Directory containing the kernel's PPS sources
+const PPS_CLASS_DIR: &str = "/sys/class/pps";
+
+/// Directory containing the kernel's PTP clocks
+const PTP_CLASS_DIR: &str = "/sys/class/ptp";
+
+/// Identification of the hardware and driver behind a PHC
+///
+/// Fields are `None` when the driver does not provide the corresponding sysfs attribute, as is
+/// the case for virtual and software clocks.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DriverInfo {
+    /// The driver-assigned clock name, from `clock_name`
+    pub clock_name: Option<String>,
+    /// The kernel driver bound to the parent device, e.g. `ice` or `ptp_ocp`
+    pub driver: Option<String>,
+    /// The bus of the parent device, e.g. `pci`
+    pub bus: Option<String>,
+    /// The parent device's address on its bus, e.g. `0000:01:00.0`
+    pub address: Option<String>,
+}
+
+/// The index `N` of a PTP clock's kernel name `ptpN`
+///
+/// The kernel names clocks with the decimal index and no padding, so anything else, such as
+/// `ptp`, `ptp0a`, `ptp+1` or `ptp01`, is rejected.
+pub(crate) fn parse_ptp_index(name: &str) -> Option<u32> {
+    let digits = name.strip_prefix("ptp")?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) || (digits.len() > 1 && digits.starts_with('0')) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// The final component of the target of the symlink at `path`
+fn link_name(path: &Path) -> Option<String> {
+    let target = fs::read_link(path).ok()?;
+    Some(target.file_name()?.to_string_lossy().into_owned())
+}
+
+/// The indices of the PTP clocks in `/sys/class/ptp`, in ascending order
+///
+/// The result is empty on a kernel without PTP clock support.
+fn clock_indices() -> Result<Vec<u32>> {
+    let entries = match fs::read_dir(PTP_CLASS_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut indices = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name();
+        if let Some(index) = name.to_str().and_then(parse_ptp_index) {
+            indices.push(index);
+        }
+    }
+    indices.sort_unstable();
+    Ok(indices)
+}
+
+/// Open every PTP clock, in index order, with its capabilities
+///
+/// Clocks are found through `/sys/class/ptp` and opened read-only.  Clocks the caller has no
+/// permission to open, any which disappear while being listed, and any which fail to report
+/// their capabilities, such as a driver which is only half bound, are skipped; other failures
+/// are returned.  The result is empty on a kernel without PTP clock support.
+pub fn list_devices_with_caps() -> Result<Vec<(PtpDevice, ptp_clock_caps)>> {
+    let indices = clock_indices()?;
+    let mut devices = Vec::with_capacity(indices.len());
+    for index in indices {
+        let device = match PtpDevice::builder().open_index(index) {
+            Ok(device) => device,
+            Err(e) if matches!(e.kind(), ErrorKind::PermissionDenied | ErrorKind::NotFound) => {
+                log_debug!("skipping /dev/ptp{index}: {e}");
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        devices.extend(with_caps(index, device));
+    }
+    Ok(devices)
+}
+
+/// `device`, which is `/dev/ptp{index}`, with its capabilities, or `None` if it fails to report them
+fn with_caps(index: u32, device: PtpDevice) -> Option<(PtpDevice, ptp_clock_caps)> {
+    match device.cached_caps() {
+        Ok(caps) => Some((device, caps)),
+        Err(e) => {
+            log_debug!("skipping /dev/ptp{index}: {e}");
+            None
+        }
+    }
+}
+
+/// One row of [`list_clock_report`]
+///
+/// The fields read from the device are `None` if it could not be opened or did not answer, in
+/// which case `note` says why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockReport {
+    /// The clock's index, as in `/dev/ptpN`
+    pub index: u32,
+    /// The driver-assigned clock name, from sysfs `clock_name`
+    pub name: Option<String>,
+    /// The maximum frequency adjustment in ppb
+    pub max_adj_ppb: Option<i32>,
+    /// Whether the clock supports precise cross timestamps
+    pub cross_timestamping: Option<bool>,
+    /// Number of programmable pins
+    pub n_pins: Option<u32>,
+    /// The offset method [`PtpDevice::measure_offset`] uses for the clock
+    pub method: Option<OffsetMethod>,
+    /// Why some of the above could not be read, if any could not
+    pub note: Option<String>,
+}
+
+impl ClockReport {
+    /// The report for clock `index`, as far as it can be read
+    fn read(index: u32) -> ClockReport {
+        let name = fs::read_to_string(Path::new(PTP_CLASS_DIR).join(format!("ptp{index}/clock_name")))
+            .ok()
+            .map(|name| name.trim().to_string());
+        let mut report = ClockReport {
+            index,
+            name,
+            max_adj_ppb: None,
+            cross_timestamping: None,
+            n_pins: None,
+            method: None,
+            note: None,
+        };
+        let device = match PtpDevice::builder().open_index(index) {
+            Ok(device) => device,
+            Err(e) => {
+                report.note = Some(format!("cannot open: {e}"));
+                return report;
+            }
+        };
+        match device.caps() {
+            Ok(caps) => {
+                report.max_adj_ppb = Some(caps.max_adj_ppb());
+                report.cross_timestamping = Some(caps.cross_timestamping());
+                report.n_pins = Some(caps.n_pins());
+            }
+            Err(e) => report.note = Some(format!("cannot read capabilities: {e}")),
+        }
+        match device.preferred_method() {
+            Ok(method) => report.method = Some(method),
+            Err(e) => report.note = report.note.or_else(|| Some(format!("cannot measure offset: {e}"))),
+        }
+        report
+    }
+}
+
+/// A report of every PTP clock, in index order, for listing tools
+///
+/// Clocks are found through `/sys/class/ptp` and opened read-only to read their capabilities
+/// and find their offset method, which takes a measurement.  A clock which cannot be opened or
+/// read is still listed, with what could not be read left as `None` and the reason in
+/// [`ClockReport::note`].  Only failing to list the clocks is an error.
+pub fn list_clock_report() -> Result<Vec<ClockReport>> {
+    Ok(clock_indices()?.into_iter().map(ClockReport::read).collect())
+}
+
+/// Open the first PTP clock, in index order, whose capabilities satisfy `predicate`
+///
+/// For example, `find_device(|caps| caps.cross_timestamping != 0 && caps.n_per_out >= 2)`.  Uses
+/// [`list_devices_with_caps`], so clocks which cannot be opened for lack of permission, or which
+/// fail to report their capabilities, are skipped.  Returns `None` if no clock matches.
+pub fn find_device<F: Fn(&ptp_clock_caps) -> bool>(predicate: F) -> Result<Option<PtpDevice>> {
+    Ok(list_devices_with_caps()?
+        .into_iter()
+        .find(|(_, caps)| predicate(caps))
+        .map(|(device, _)| device))
+}
+
+/// A one-line summary of the PTP clock at `path`, for diagnostic tools
+///
+/// For example `ptp0 (ens1f0): ice-0000:01:00.0, cross-ts, 2 pins, 4 extts, 4 perout, precise
+/// offset +312ns`.  The summary is best effort: anything which cannot be read, such as the
+/// network interface of a clock with none, is shown as `unknown`.  Only failing to open the
+/// clock is an error.
+pub fn describe_device(path: &Path) -> Result<String> {
+    let device = PtpDevice::new(path.to_path_buf())?;
+    let unknown = || "unknown".to_string();
+    let name = device.kernel_name().unwrap_or_else(|_| unknown());
+    let interface = device.interface_name().unwrap_or_else(unknown);
+    let clock_name = device.clock_name().unwrap_or_else(|_| unknown());
+    let caps = match device.caps() {
+        Ok(caps) => format!(
+            "{}, {} pins, {} extts, {} perout",
+            if caps.cross_timestamping() { "cross-ts" } else { "no cross-ts" },
+            caps.n_pins(),
+            caps.n_ext_ts(),
+            caps.n_per_out()
+        ),
+        Err(_) => "capabilities unknown".to_string(),
+    };
+    let offset = match device.measure_offset() {
+        Ok(m) => format!("{} offset {:+}ns", m.method.as_str(), m.offset_ns),
+        Err(_) => "offset unknown".to_string(),
+    };
+    Ok(format!("{name} ({interface}): {clock_name}, {caps}, {offset}"))
+}
+
+impl PtpDevice {
+    /// The name of the first network interface of the hardware providing this clock, if any
+    pub(crate) fn interface_name(&self) -> Option<String> {
+        let mut names: Vec<String> = fs::read_dir(self.sysfs_path().ok()?.join("device/net"))
+            .ok()?
+            .filter_map(|entry| Some(entry.ok()?.file_name().to_string_lossy().into_owned()))
+            .collect();
+        names.sort_unstable();
+        names.into_iter().next()
+    }
+
+    /// The sysfs directory for this device, e.g. `/sys/devices/.../ptp/ptp0`
+    ///
+    /// This is derived from the device number of the open file rather than the path used to open
+    /// it, so it is correct for symlinks and file descriptors received from elsewhere.
+    pub fn sysfs_path(&self) -> Result<PathBuf> {
+        let rdev = self.file.metadata()?.rdev();
+        if rdev == 0 {
+            return Err(Error::new(ErrorKind::InvalidInput, "not a character device"));
+        }
+        let link = format!("/sys/dev/char/{}:{}", libc::major(rdev), libc::minor(rdev));
+        fs::canonicalize(link)
+    }
+
+    /// Open the PTP clock whose driver-assigned name (e.g. `ptp_ocp0`) is `name`
+    ///
+    /// Clock names are stable across reboots, unlike device indices.  Fails with
+    /// [`PtpError::DeviceNotFound`] if no clock has that name, including when the kernel has no
+    /// PTP clocks at all.
+    pub fn from_clock_name(name: &str) -> Result<PtpDevice> {
+        let not_found = || -> Error {
+            PtpError::DeviceNotFound {
+                path: format!("clock named {name}").into(),
+            }
+            .into()
+        };
+        let entries = match fs::read_dir(PTP_CLASS_DIR) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err(not_found()),
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let Ok(clock_name) = fs::read_to_string(entry.path().join("clock_name")) else {
+                continue;
+            };
+            if clock_name.trim() == name {
+                return PtpDevice::new(Path::new("/dev").join(entry.file_name()));
+            }
+        }
+        Err(not_found())
+    }
+
+    /// Read the sysfs attribute `name` of this clock with surrounding whitespace trimmed
+    ///
+    /// This gives access to attributes without a dedicated method, such as `max_phase_adjustment`
+    /// or `n_vclocks`.  Fails with `ErrorKind::Unsupported` if this kernel or driver does not
+    /// provide the attribute, and `ErrorKind::InvalidInput` if `name` is not a single path
+    /// component.
+    pub fn sysfs_attribute(&self, name: &str) -> Result<String> {
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid sysfs attribute name {name:?}"),
+            ));
+        }
+        match fs::read_to_string(self.sysfs_path()?.join(name)) {
+            Ok(value) => Ok(value.trim().to_string()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("sysfs attribute {name} is not available for this clock"),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The driver-assigned name of this clock, from sysfs `clock_name`
+    pub fn clock_name(&self) -> Result<String> {
+        self.sysfs_attribute("clock_name")
+    }
+
+    /// The maximum phase adjustment in nanoseconds, from sysfs `max_phase_adjustment`
+    ///
+    /// This should match `max_phase_adj` in the clock's capabilities, and serves as a fallback on
+    /// kernels whose `PTP_CLOCK_GETCAPS` predates that field and leaves it zero.  In debug builds,
+    /// a disagreement between the two is logged as a warning, since it points to a driver bug or
+    /// an ABI mismatch.
+    pub fn sysfs_max_phase_adjustment(&self) -> Result<i64> {
+        let value = self.sysfs_attribute("max_phase_adjustment")?;
+        let max = value.parse::<i64>().map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid max_phase_adjustment {value:?}: {e}"),
+            )
+        })?;
+        if cfg!(debug_assertions) {
+            if let Ok(caps) = self.cached_caps() {
+                if caps.max_phase_adj != 0 && i64::from(caps.max_phase_adj) != max {
+                    log_warn!(
+                        "sysfs max_phase_adjustment {max} differs from capabilities max_phase_adj {}",
+                        caps.max_phase_adj
+                    );
+                }
+            }
+        }
+        Ok(max)
+    }
+
+    /// Open this physical clock's virtual clock number `vclock_index`, counting from 0
+    ///
+    /// Virtual clocks created by writing `n_vclocks` appear as child devices of the physical
+    /// clock in sysfs, and are numbered here in order of their `/dev/ptpM` index.  The virtual
+    /// clock is opened read-write if this device was.  Fails with [`PtpError::DeviceNotFound`] if
+    /// there is no such virtual clock.
+    pub fn vclock(&self, vclock_index: u32) -> Result<PtpDevice> {
+        let mut indices: Vec<u32> = Vec::new();
+        for entry in fs::read_dir(self.sysfs_path()?)? {
+            let name = entry?.file_name();
+            if let Some(index) = name.to_str().and_then(parse_ptp_index) {
+                indices.push(index);
+            }
+        }
+        indices.sort_unstable();
+        match indices.get(vclock_index as usize) {
+            Some(index) => PtpDevice::builder().read_write(self.is_writable()?).open_index(*index),
+            None => Err(PtpError::DeviceNotFound {
+                path: format!("virtual clock {vclock_index} of {}", self.kernel_name()?).into(),
+            }
+            .into()),
+        }
+    }
+
+    /// The name and current function of every programmable pin, read from sysfs `pins/`
+    ///
+    /// Each file in the directory is named after a pin and holds its function and channel.
+    /// Reading them needs no ioctl, so it works even where `PTP_PIN_GETFUNC` is restricted to
+    /// writable handles.  The kernel creates the directory for drivers which declare programmable
+    /// pins, such as `igb`, `ice`, `mlx5_core`, `dp83640` and the Marvell switch drivers; if it
+    /// is absent this falls back to [`PtpDevice::pins`].  Pins are returned in name order, since
+    /// sysfs does not record their indices.
+    pub fn pins_from_sysfs(&self) -> Result<Vec<(String, PinFunction)>> {
+        let invalid = |name: &str, func: &str| {
+            Error::new(ErrorKind::InvalidData, format!("invalid function {func:?} for pin {name}"))
+        };
+        let entries = match fs::read_dir(self.sysfs_path()?.join("pins")) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return self
+                    .pins()?
+                    .into_iter()
+                    .map(|pin| {
+                        let name = pin.name();
+                        match pin.function() {
+                            Some(function) => Ok((name, function)),
+                            None => Err(invalid(&name, &pin.raw().func.to_string())),
+                        }
+                    })
+                    .collect();
+            }
+            Err(e) => return Err(e),
+        };
+        let mut pins = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let value = fs::read_to_string(entry.path())?;
+            let function = value
+                .split_whitespace()
+                .next()
+                .and_then(|func| func.parse().ok())
+                .and_then(PinFunction::from_raw)
+                .ok_or_else(|| invalid(&name, value.trim()))?;
+            pins.push((name, function));
+        }
+        pins.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok(pins)
+    }
+
+    /// Identify the driver and hardware providing this clock
+    pub fn driver_info(&self) -> Result<DriverInfo> {
+        let path = self.sysfs_path()?;
+        let device = path.join("device");
+        Ok(DriverInfo {
+            clock_name: self.clock_name().ok(),
+            driver: link_name(&device.join("driver")),
+            bus: link_name(&device.join("subsystem")),
+            address: link_name(&device),
+        })
+    }
+
+    /// The kernel name of this device, e.g. `ptp0`
+    pub(crate) fn kernel_name(&self) -> Result<String> {
+        let path = self.sysfs_path()?;
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "sysfs path has no file name"))
+    }
+
+    /// The index `N` of this clock's `/dev/ptpN` device
+    ///
+    /// Like [`PtpDevice::sysfs_path`], this comes from the device number of the open file, so it
+    /// is correct whatever path was used to open it, including symlinks such as udev's
+    /// `/dev/ptp_hyperv`.  Fails with `ErrorKind::InvalidData` if the kernel's name for the
+    /// device is not of the form `ptpN`.
+    pub fn device_index(&self) -> Result<u32> {
+        let name = self.kernel_name()?;
+        parse_ptp_index(&name).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("device name {name:?} is not of the form ptpN"),
+            )
+        })
+    }
+
+    /// Path of the `/dev/ppsN` device fed by this PHC's PPS output, if there is one
+    ///
+    /// The PTP core registers its PPS source with the name `ptpN`, which is matched against
+    /// `/sys/class/pps/*/name`.  A PPS source is only registered for clocks which report `pps`
+    /// in their capabilities.
+    pub fn pps_device_path(&self) -> Result<Option<PathBuf>> {
+        let name = self.kernel_name()?;
+        let entries = match fs::read_dir(PPS_CLASS_DIR) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let Ok(pps_name) = fs::read_to_string(entry.path().join("name")) else {
+                continue;
+            };
+            if pps_name.trim() == name {
+                return Ok(Some(Path::new("/dev").join(entry.file_name())));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[test]
+    fn devices_failing_getcaps_are_skipped() {
+        assert!(with_caps(0, null_device()).is_none());
+    }
+
+    #[test]
+    fn unknown_clock_name_is_device_not_found() {
+        let e = PtpDevice::from_clock_name("no-such-clock").err().unwrap();
+        assert!(matches!(PtpError::from_io_error(&e), Some(PtpError::DeviceNotFound { .. })), "{e}");
+    }
+
+    #[test]
+    fn ptp_index_parsing_rejects_odd_names() {
+        assert_eq!(parse_ptp_index("ptp0"), Some(0));
+        assert_eq!(parse_ptp_index("ptp12"), Some(12));
+        for name in ["ptp", "ptp0a", "ptp+1", "ptp-1", "ptp01", "ptp 1", "pps0", "ptp99999999999"] {
+            assert_eq!(parse_ptp_index(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn description_is_best_effort() {
+        assert_eq!(
+            describe_device(Path::new("/dev/null")).unwrap(),
+            "null (unknown): unknown, capabilities unknown, offset unknown"
+        );
+        assert!(describe_device(Path::new("/dev/ptp-missing")).is_err());
+    }
+
+    #[test]
+    fn clock_report_lists_every_clock() {
+        let report = list_clock_report().unwrap();
+        assert_eq!(report.len(), clock_indices().unwrap().len());
+        assert!(report.windows(2).all(|pair| pair[0].index < pair[1].index));
+    }
+
+    #[test]
+    fn device_index_needs_a_ptp_device() {
+        let device = null_device();
+        assert!(device.device_index().is_err());
+    }
+}
@@ -0,0 +1,23 @@
+//! Fixtures shared by the unit tests
+
+use std::fs::File;
+
+use crate::{ptp::ptp_clock_time, OffsetMeasurement, OffsetMethod, PtpDevice};
+
+/// A device backed by `/dev/null`: it is read-only, and every ioctl on it fails with `ENOTTY`
+pub(crate) fn null_device() -> PtpDevice {
+    PtpDevice::from_file(File::open("/dev/null").unwrap())
+}
+
+/// A precise measurement at `sec` seconds on the system clock, with the PHC `offset_ns` ahead
+pub(crate) fn measurement(sec: i64, offset_ns: i64) -> OffsetMeasurement {
+    let sys_time = ptp_clock_time { sec, ..Default::default() };
+    OffsetMeasurement {
+        phc_time: ptp_clock_time::from_nanos(sys_time.as_nanos() + offset_ns as i128),
+        sys_time,
+        offset_ns,
+        delay_ns: 0,
+        method: OffsetMethod::Precise,
+        mono: None,
+    }
+}
@@ -0,0 +1,260 @@
+//! Helpers for working with `ptp_clock_time` values
+//!
+//! A PHC has no inherent timescale: it counts whatever it was set to.  PTP itself distributes
+//! TAI, so a PHC synchronised by linuxptp normally runs on TAI, while the system clock
+//! (`CLOCK_REALTIME`) runs on UTC.  The two differ by the TAI - UTC offset, currently 37 seconds.
+
+use core::{fmt, time::Duration};
+
+use crate::ptp::ptp_clock_time;
+
+const NSEC_PER_SEC: i128 = 1_000_000_000;
+
+const SEC_PER_DAY: i64 = 86_400;
+
+/// A time broken down into UTC calendar fields, from [`ptp_clock_time::to_utc_parts`]
+///
+/// Dates use the proleptic Gregorian calendar, so years before 1583 are not those historically
+/// in use.  `Display` formats it as RFC 3339 with nanoseconds, e.g. `2024-02-29T12:34:56.000000123Z`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UtcParts {
+    /// The year, which is 0 for 1 BC and negative before that
+    pub year: i64,
+    /// The month, from 1 for January to 12
+    pub month: u8,
+    /// The day of the month, from 1
+    pub day: u8,
+    /// The hour, from 0 to 23
+    pub hour: u8,
+    /// The minute, from 0 to 59
+    pub minute: u8,
+    /// The second, from 0 to 59; leap seconds cannot be represented in Unix time
+    pub second: u8,
+    /// The nanoseconds of the second, unrounded
+    pub nanosecond: u32,
+    /// The day of the week, from 0 for Sunday to 6 for Saturday
+    pub weekday: u8,
+    /// The day of the year, from 1 for the 1st of January to 366
+    pub yday: u16,
+}
+
+impl fmt::Display for UtcParts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.nanosecond
+        )
+    }
+}
+
+impl ptp_clock_time {
+    /// Total nanoseconds represented by this time, relative to the clock's epoch
+    pub fn as_nanos(&self) -> i128 {
+        self.sec as i128 * NSEC_PER_SEC + self.nsec as i128
+    }
+
+    /// Create a time from a count of nanoseconds relative to the clock's epoch
+    pub fn from_nanos(nanos: i128) -> ptp_clock_time {
+        ptp_clock_time {
+            sec: nanos.div_euclid(NSEC_PER_SEC) as i64,
+            nsec: nanos.rem_euclid(NSEC_PER_SEC) as u32,
+            reserved: 0,
+        }
+    }
+
+    /// Create a time from a duration since the clock's epoch
+    pub fn from_duration(duration: Duration) -> ptp_clock_time {
+        ptp_clock_time {
+            sec: duration.as_secs() as i64,
+            nsec: duration.subsec_nanos(),
+            reserved: 0,
+        }
+    }
+
+    /// The duration since the clock's epoch, or `None` if this time is before the epoch
+    ///
+    /// This is the fallible counterpart of `Duration::from`, which clamps such times to zero.
+    /// Providing a `TryFrom` as well is not possible, since the standard library's blanket
+    /// implementation already derives an infallible one from `From`.
+    pub fn to_duration(&self) -> Option<Duration> {
+        (self.sec >= 0).then(|| Duration::new(self.sec as u64, self.nsec))
+    }
+
+    /// The time halfway between this time and `other`, rounded down to the nanosecond
+    ///
+    /// The sum is formed in 128 bits, so this is exact for any pair of times, with the
+    /// nanoseconds carried into the seconds as needed.
+    pub fn midpoint(&self, other: &ptp_clock_time) -> ptp_clock_time {
+        ptp_clock_time::from_nanos((self.as_nanos() + other.as_nanos()).div_euclid(2))
+    }
+
+    /// Whether this time is within `tolerance_ns` nanoseconds of `other`
+    ///
+    /// The difference is computed in 128 bits, so it cannot overflow even for times at opposite
+    /// extremes of the representable range.  A negative tolerance matches nothing.
+    pub fn approx_eq(&self, other: &ptp_clock_time, tolerance_ns: i64) -> bool {
+        let diff = self.as_nanos() - other.as_nanos();
+        tolerance_ns >= 0 && diff.unsigned_abs() <= tolerance_ns as u128
+    }
+
+    /// Break this time down into calendar fields, treating it as seconds since the Unix epoch in UTC
+    ///
+    /// The date is found with Howard Hinnant's `civil_from_days` algorithm, which is exact for every
+    /// representable time.  A PHC on TAI should be converted with [`ptp_clock_time::tai_to_utc`]
+    /// first.
+    pub fn to_utc_parts(&self) -> UtcParts {
+        let days = self.sec.div_euclid(SEC_PER_DAY);
+        let secs = self.sec.rem_euclid(SEC_PER_DAY);
+
+        // Count from 0000-03-01, so that leap days fall at the end of each year
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let (month, year) = if mp < 10 { (mp + 3, yoe + era * 400) } else { (mp - 9, yoe + era * 400 + 1) };
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let yday = if mp < 10 { doy + 60 + leap as i64 } else { doy - 305 };
+
+        UtcParts {
+            year,
+            month: month as u8,
+            day: day as u8,
+            hour: (secs / 3_600) as u8,
+            minute: (secs / 60 % 60) as u8,
+            second: (secs % 60) as u8,
+            nanosecond: self.nsec,
+            // 1970-01-01 was a Thursday
+            weekday: (days + 4).rem_euclid(7) as u8,
+            yday: yday as u16,
+        }
+    }
+
+    /// Convert a time on the TAI timescale to UTC
+    ///
+    /// `tai_utc_offset` is TAI - UTC in seconds, as reported by the kernel's `tai` field or an
+    /// external source.  The offset must be the one in force at the time being converted.
+    pub fn tai_to_utc(&self, tai_utc_offset: i32) -> ptp_clock_time {
+        ptp_clock_time {
+            sec: self.sec.saturating_sub(tai_utc_offset as i64),
+            ..*self
+        }
+    }
+
+    /// Convert a time on the UTC timescale to TAI
+    ///
+    /// `tai_utc_offset` is TAI - UTC in seconds; see [`ptp_clock_time::tai_to_utc`].
+    pub fn utc_to_tai(&self, tai_utc_offset: i32) -> ptp_clock_time {
+        ptp_clock_time {
+            sec: self.sec.saturating_add(tai_utc_offset as i64),
+            ..*self
+        }
+    }
+}
+
+impl From<libc::timespec> for ptp_clock_time {
+    fn from(ts: libc::timespec) -> ptp_clock_time {
+        ptp_clock_time {
+            sec: ts.tv_sec as _,
+            nsec: ts.tv_nsec as _,
+            reserved: 0,
+        }
+    }
+}
+
+/// Times before the clock's epoch are clamped to zero; use [`ptp_clock_time::to_duration`] to
+/// detect them instead
+impl From<ptp_clock_time> for Duration {
+    fn from(time: ptp_clock_time) -> Duration {
+        time.to_duration().unwrap_or(Duration::ZERO)
+    }
+}
+
+/// Times are serialized as `{ "sec": ..., "nsec": ... }`, omitting the reserved field
+#[cfg(feature = "serde")]
+impl serde::Serialize for ptp_clock_time {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("ptp_clock_time", 2)?;
+        state.serialize_field("sec", &self.sec)?;
+        state.serialize_field("nsec", &self.nsec)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ptp_clock_time {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<ptp_clock_time, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct Time {
+            sec: i64,
+            nsec: u32,
+        }
+        let Time { sec, nsec } = Time::deserialize(deserializer)?;
+        if nsec >= 1_000_000_000 {
+            return Err(serde::de::Error::custom("nsec must be less than 1000000000"));
+        }
+        Ok(ptp_clock_time { sec, nsec, reserved: 0 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approx_eq_handles_extremes() {
+        let a = ptp_clock_time::from_nanos(1_000);
+        assert!(a.approx_eq(&ptp_clock_time::from_nanos(1_010), 10));
+        assert!(!a.approx_eq(&ptp_clock_time::from_nanos(1_011), 10));
+        assert!(!a.approx_eq(&a, -1));
+
+        let min = ptp_clock_time { sec: i64::MIN, nsec: 0, reserved: 0 };
+        let max = ptp_clock_time { sec: i64::MAX, nsec: 999_999_999, reserved: 0 };
+        assert!(!min.approx_eq(&max, i64::MAX));
+        assert!(max.approx_eq(&max, 0));
+    }
+
+    #[test]
+    fn midpoint_carries_nanoseconds_and_handles_extremes() {
+        let a = ptp_clock_time { sec: 1, nsec: 999_999_999, reserved: 0 };
+        let b = ptp_clock_time { sec: 3, nsec: 1, reserved: 0 };
+        assert_eq!(a.midpoint(&b).as_nanos(), 2_500_000_000);
+        assert_eq!(b.midpoint(&a).as_nanos(), 2_500_000_000);
+
+        let max = ptp_clock_time { sec: i64::MAX, nsec: 999_999_999, reserved: 0 };
+        assert_eq!(max.midpoint(&max).as_nanos(), max.as_nanos());
+        let min = ptp_clock_time { sec: i64::MIN, nsec: 0, reserved: 0 };
+        assert_eq!(min.midpoint(&max).as_nanos(), -1);
+    }
+
+    #[test]
+    fn utc_parts_match_known_dates() {
+        let parts = |sec, nsec| ptp_clock_time { sec, nsec, reserved: 0 }.to_utc_parts();
+        assert_eq!(parts(0, 0).to_string(), "1970-01-01T00:00:00.000000000Z");
+        assert_eq!(parts(0, 0).weekday, 4);
+
+        let leap_day = parts(951_827_696, 123);
+        assert_eq!(leap_day.to_string(), "2000-02-29T12:34:56.000000123Z");
+        assert_eq!((leap_day.weekday, leap_day.yday), (2, 60));
+        assert_eq!(parts(978_220_800, 0).yday, 366);
+
+        let before_epoch = parts(-1, 999_999_999);
+        assert_eq!(before_epoch.to_string(), "1969-12-31T23:59:59.999999999Z");
+        assert_eq!((before_epoch.weekday, before_epoch.yday), (3, 365));
+    }
+
+    #[test]
+    fn duration_conversion_is_exact_and_rejects_negative_times() {
+        let time = ptp_clock_time { sec: 12, nsec: 345_678_901, reserved: 0 };
+        assert_eq!(Duration::from(time), Duration::new(12, 345_678_901));
+        assert_eq!(ptp_clock_time::from_duration(Duration::from(time)).as_nanos(), time.as_nanos());
+
+        let before_epoch = ptp_clock_time::from_nanos(-1);
+        assert_eq!(before_epoch.to_duration(), None);
+        assert_eq!(Duration::from(before_epoch), Duration::ZERO);
+    }
+}
@@ -0,0 +1,111 @@
+//! Metadata of OCP Time Card clocks, from the `ptp_ocp` driver's sysfs attributes
+//!
+//! Besides its PHC, the `ptp_ocp` driver registers a `timecard` class device, e.g.
+//! `/sys/class/timecard/ocp0`, whose `ptp` link points back at the PHC.  Its attributes describe
+//! the card's GNSS receiver and timing configuration.  The firmware version is reported through
+//! devlink rather than sysfs, so it is not available here.
+
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+};
+
+use crate::PtpDevice;
+
+/// Directory containing the `ptp_ocp` driver's timecard devices
+const TIMECARD_CLASS_DIR: &str = "/sys/class/timecard";
+
+/// An attribute of a timecard device with surrounding whitespace trimmed, if the driver provides it
+fn attribute(path: &Path, name: &str) -> Result<Option<String>> {
+    match fs::read_to_string(path.join(name)) {
+        Ok(value) => Ok(Some(value.trim().to_string())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// The metadata of an OCP Time Card, from [`TimeCard::from_ptp`]
+///
+/// Fields are `None` when the driver version or the card's firmware does not provide the
+/// corresponding attribute.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TimeCard {
+    /// The timecard device's sysfs directory, e.g. `/sys/class/timecard/ocp0`
+    pub path: PathBuf,
+    /// The card's serial number, from `serialnum`
+    pub serial_number: Option<String>,
+    /// The GNSS receiver's state, from `gnss_sync`: `SYNC`, or `LOST @ <time>` once it has lost lock
+    pub gnss_sync: Option<String>,
+    /// The source the card's clock is disciplined to, e.g. `GNSS`, from `clock_source`
+    pub clock_source: Option<String>,
+    /// The TAI - UTC offset the card applies, in seconds, from `utc_tai_offset`
+    pub utc_tai_offset: Option<i32>,
+}
+
+impl TimeCard {
+    /// Read the metadata of the Time Card whose PHC is `device`
+    ///
+    /// Fails with `ErrorKind::Unsupported` if the clock is not provided by the `ptp_ocp` driver,
+    /// and `ErrorKind::NotFound` if no timecard device links to it.
+    pub fn from_ptp(device: &PtpDevice) -> Result<TimeCard> {
+        let driver = device.driver_info()?.driver;
+        if driver.as_deref() != Some("ptp_ocp") {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("clock is provided by {}, not ptp_ocp", driver.as_deref().unwrap_or("an unknown driver")),
+            ));
+        }
+        let ptp_path = device.sysfs_path()?;
+        for entry in fs::read_dir(TIMECARD_CLASS_DIR)? {
+            let path = entry?.path();
+            if fs::canonicalize(path.join("ptp")).is_ok_and(|linked| linked == ptp_path) {
+                return TimeCard::from_path(path);
+            }
+        }
+        Err(Error::new(ErrorKind::NotFound, "no timecard device links to this clock"))
+    }
+
+    /// Read the metadata from the timecard device directory `path`
+    fn from_path(path: PathBuf) -> Result<TimeCard> {
+        let utc_tai_offset = match attribute(&path, "utc_tai_offset")? {
+            Some(value) => Some(value.parse().map_err(|e| {
+                Error::new(ErrorKind::InvalidData, format!("invalid utc_tai_offset {value:?}: {e}"))
+            })?),
+            None => None,
+        };
+        Ok(TimeCard {
+            serial_number: attribute(&path, "serialnum")?,
+            gnss_sync: attribute(&path, "gnss_sync")?,
+            clock_source: attribute(&path, "clock_source")?,
+            utc_tai_offset,
+            path,
+        })
+    }
+
+    /// Whether the GNSS receiver is locked, if the card reports it
+    pub fn gnss_synced(&self) -> Option<bool> {
+        self.gnss_sync.as_deref().map(|state| state == "SYNC")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_attributes_and_tolerates_missing_ones() {
+        let dir = std::env::temp_dir().join(format!("ptp-time-timecard-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("serialnum"), "fb:12:34:56:78:9a\n").unwrap();
+        fs::write(dir.join("gnss_sync"), "LOST @ 2024-01-01T00:00:00\n").unwrap();
+        fs::write(dir.join("utc_tai_offset"), "37\n").unwrap();
+
+        let card = TimeCard::from_path(dir.clone()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(card.serial_number.as_deref(), Some("fb:12:34:56:78:9a"));
+        assert_eq!(card.gnss_synced(), Some(false));
+        assert_eq!(card.clock_source, None);
+        assert_eq!(card.utc_tai_offset, Some(37));
+    }
+}
@@ -0,0 +1,204 @@
+//! The timescale a PHC runs on and the epoch it counts from, for converting its times to UTC
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    time::SystemTime,
+};
+
+use crate::{lock, ptp::ptp_clock_time, PtpDevice};
+
+/// The timescale of a PHC's times, which decides how they are converted to UTC
+///
+/// This cannot be detected reliably: [`PtpDevice::timescale_offset_to_utc`] can tell TAI from
+/// UTC on a synchronised clock, but a leap-smeared clock is indistinguishable from UTC except
+/// around a leap second.  It is therefore set explicitly with [`PtpDevice::set_timescale_mode`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimescaleMode {
+    /// The PHC runs on UTC, so its times are used unchanged
+    #[default]
+    Utc,
+    /// The PHC runs on TAI, which is `offset` seconds ahead of UTC
+    Tai {
+        /// TAI - UTC in seconds, 37 since 2017
+        offset: i32,
+    },
+    /// The PHC runs on a leap-smeared UTC, which absorbs leap seconds by stretching the seconds
+    /// around them
+    ///
+    /// Its times are already as close to UTC as a smeared clock gets, so they are used unchanged.
+    /// Applying a leap second offset on top of the smear would correct it twice.
+    LeapSmeared,
+}
+
+/// Seconds from the Unix epoch to the GPS epoch, 1980-01-06T00:00:00Z
+const GPS_EPOCH_UNIX_SECS: i64 = 315_964_800;
+
+/// The time a PHC counts from, which decides the base added to its times to convert them to UTC
+///
+/// Conversions first rebase a time to the Unix epoch and then apply the [`TimescaleMode`], so
+/// a clock on GPS time should be left on [`TimescaleMode::Utc`]: [`Epoch::Gps`] already
+/// accounts for the leap seconds between GPS time and UTC.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Epoch {
+    /// 1970-01-01T00:00:00Z, as used by `CLOCK_REALTIME` and most PHCs
+    #[default]
+    Unix,
+    /// GPS time, which counts from 1980-01-06T00:00:00Z without leap seconds
+    Gps {
+        /// GPS - UTC in seconds, 18 since 2017, i.e. TAI - UTC less the 19 seconds TAI is ahead
+        /// of GPS time
+        leap_seconds: i32,
+    },
+    /// A clock which reads zero at `base` seconds after the Unix epoch, on its timescale
+    Custom {
+        /// Seconds from the Unix epoch to the time the clock reads zero
+        base: i64,
+    },
+}
+
+impl Epoch {
+    /// Seconds to add to a time counted from this epoch to count it from the Unix epoch
+    pub fn unix_offset(&self) -> i64 {
+        match *self {
+            Epoch::Unix => 0,
+            Epoch::Gps { leap_seconds } => GPS_EPOCH_UNIX_SECS - leap_seconds as i64,
+            Epoch::Custom { base } => base,
+        }
+    }
+}
+
+impl PtpDevice {
+    /// The epoch this device's times are assumed to count from; [`Epoch::Unix`] by default
+    pub fn epoch(&self) -> Epoch {
+        *lock(&self.epoch)
+    }
+
+    /// Set the epoch this device's times count from, for [`PtpDevice::to_utc`] and friends
+    pub fn set_epoch(&self, epoch: Epoch) {
+        *lock(&self.epoch) = epoch;
+    }
+
+    /// The timescale this device's times are assumed to be on; [`TimescaleMode::Utc`] by default
+    pub fn timescale_mode(&self) -> TimescaleMode {
+        *lock(&self.timescale)
+    }
+
+    /// Set the timescale this device's times are on, for [`PtpDevice::to_utc`] and friends
+    pub fn set_timescale_mode(&self, mode: TimescaleMode) {
+        *lock(&self.timescale) = mode;
+    }
+
+    /// Convert a time read from this device to UTC according to its [`Epoch`] and
+    /// [`TimescaleMode`]
+    ///
+    /// Times which would be out of the range of `ptp_clock_time` saturate at its limits.
+    pub fn to_utc(&self, time: ptp_clock_time) -> ptp_clock_time {
+        let time = ptp_clock_time {
+            sec: time.sec.saturating_add(self.epoch().unix_offset()),
+            ..time
+        };
+        self.timescale_to_utc(time)
+    }
+
+    /// Convert a time counted from the Unix epoch on this device's timescale to UTC
+    fn timescale_to_utc(&self, time: ptp_clock_time) -> ptp_clock_time {
+        match self.timescale_mode() {
+            TimescaleMode::Utc | TimescaleMode::LeapSmeared => time,
+            TimescaleMode::Tai { offset } => time.tai_to_utc(offset),
+        }
+    }
+
+    /// Convert a UTC time to this device's timescale and epoch according to its
+    /// [`TimescaleMode`] and [`Epoch`]
+    ///
+    /// Times which would be out of the range of `ptp_clock_time` saturate at its limits.
+    pub fn from_utc(&self, time: ptp_clock_time) -> ptp_clock_time {
+        let time = match self.timescale_mode() {
+            TimescaleMode::Utc | TimescaleMode::LeapSmeared => time,
+            TimescaleMode::Tai { offset } => time.utc_to_tai(offset),
+        };
+        ptp_clock_time {
+            sec: time.sec.saturating_sub(self.epoch().unix_offset()),
+            ..time
+        }
+    }
+
+    /// Format a time read from this device as RFC 3339 UTC, via [`PtpDevice::to_utc`], e.g.
+    /// `2024-02-29T12:34:56.000000123Z`
+    pub fn to_rfc3339(&self, time: ptp_clock_time) -> String {
+        self.to_utc(time).to_utc_parts().to_string()
+    }
+
+    /// Convert a time read from this device to a `SystemTime`, via [`PtpDevice::to_utc`]
+    ///
+    /// Fails with `ErrorKind::InvalidData` if the time is before the Unix epoch, or too late for
+    /// its epoch to be added or for a `SystemTime` to represent it.
+    pub fn to_system_time(&self, time: ptp_clock_time) -> Result<SystemTime> {
+        let out_of_range = || Error::new(ErrorKind::InvalidData, "device time is out of range for SystemTime");
+        let sec = time.sec.checked_add(self.epoch().unix_offset()).ok_or_else(out_of_range)?;
+        let since_epoch = self
+            .timescale_to_utc(ptp_clock_time { sec, ..time })
+            .to_duration()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "device time is before the Unix epoch"))?;
+        SystemTime::UNIX_EPOCH.checked_add(since_epoch).ok_or_else(out_of_range)
+    }
+
+    /// Convert a `SystemTime` to this device's timescale, via [`PtpDevice::from_utc`]
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if the time is before the Unix epoch.
+    pub fn from_system_time(&self, time: SystemTime) -> Result<ptp_clock_time> {
+        let since_epoch = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "time is before the Unix epoch"))?;
+        Ok(self.from_utc(ptp_clock_time::from_duration(since_epoch)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::null_device;
+
+    #[test]
+    fn smeared_times_pass_through_unchanged() {
+        let device = null_device();
+        let time = ptp_clock_time { sec: 1_700_000_037, nsec: 5, reserved: 0 };
+        assert_eq!(device.to_utc(time).sec, time.sec);
+
+        device.set_timescale_mode(TimescaleMode::Tai { offset: 37 });
+        assert_eq!(device.to_utc(time).sec, 1_700_000_000);
+        assert_eq!(device.from_utc(device.to_utc(time)).sec, time.sec);
+
+        device.set_timescale_mode(TimescaleMode::LeapSmeared);
+        assert_eq!(device.to_utc(time).sec, time.sec);
+        assert_eq!(device.from_utc(time).sec, time.sec);
+    }
+
+    #[test]
+    fn epochs_rebase_to_unix() {
+        let device = null_device();
+        let zero = ptp_clock_time::default();
+        assert_eq!(device.to_rfc3339(zero), "1970-01-01T00:00:00.000000000Z");
+
+        device.set_epoch(Epoch::Gps { leap_seconds: 0 });
+        assert_eq!(device.to_rfc3339(zero), "1980-01-06T00:00:00.000000000Z");
+        device.set_epoch(Epoch::Gps { leap_seconds: 18 });
+        let time = ptp_clock_time { sec: 1_400_000_018, nsec: 0, reserved: 0 };
+        assert_eq!(device.to_utc(time).sec, 1_715_964_800);
+        assert_eq!(device.from_utc(device.to_utc(time)).sec, time.sec);
+
+        device.set_epoch(Epoch::Custom { base: 1_000 });
+        let expected = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000);
+        assert_eq!(device.to_system_time(zero).unwrap(), expected);
+
+        // Garbage device times and extreme bases saturate rather than overflow
+        device.set_epoch(Epoch::Custom { base: i64::MAX });
+        let late = ptp_clock_time { sec: i64::MAX - 1, nsec: 0, reserved: 0 };
+        assert_eq!(device.to_utc(late).sec, i64::MAX);
+        assert_eq!(device.to_system_time(late).unwrap_err().kind(), ErrorKind::InvalidData);
+        device.set_epoch(Epoch::Custom { base: i64::MIN });
+        assert_eq!(device.from_utc(late).sec, i64::MAX);
+        device.set_timescale_mode(TimescaleMode::Tai { offset: 37 });
+        assert_eq!(device.to_utc(ptp_clock_time { sec: i64::MIN, nsec: 0, reserved: 0 }).sec, i64::MIN);
+    }
+}
@@ -0,0 +1,148 @@
+//! Stable serialized forms of measurements and capabilities
+//!
+//! [`OffsetMeasurement`] and [`ClockCaps`] are serialized through the versioned structures in
+//! this module rather than their in-memory layout, so the output only changes when
+//! [`SCHEMA_VERSION`] does.  Field names are snake_case, and durations are integers whose name
+//! ends in their unit.  Version 1 of the shapes is:
+//!
+//! ```text
+//! OffsetMeasurement: { "schema_version": 1, "phc_time": { "sec": i64, "nsec": u32 },
+//!   "sys_time": { "sec": i64, "nsec": u32 }, "offset_ns": i64, "delay_ns": i64,
+//!   "method": "precise" | "extended" | "plain", "mono_ns": u64 (omitted if not measured) }
+//! ClockCaps: { "schema_version": 1, "max_adj_ppb": i32, "n_alarm": u32, "n_ext_ts": u32,
+//!   "n_per_out": u32, "pps": bool, "n_pins": u32, "cross_timestamping": bool,
+//!   "adjust_phase": bool, "max_phase_adj_ns": i32 }
+//! ```
+//!
+//! Measurements without `schema_version`, as written before it was added, are read as version 1;
+//! other versions are rejected.
+
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+
+use crate::{ptp::ptp_clock_time, ClockCaps, OffsetMeasurement, OffsetMethod};
+
+/// The version of the serialized shapes described in this module
+pub const SCHEMA_VERSION: u32 = 1;
+
+fn schema_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+/// Version 1 of the serialized form of [`OffsetMeasurement`]
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub(crate) struct MeasurementV1 {
+    #[serde(default = "schema_version")]
+    schema_version: u32,
+    phc_time: ptp_clock_time,
+    sys_time: ptp_clock_time,
+    offset_ns: i64,
+    delay_ns: i64,
+    method: OffsetMethod,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mono_ns: Option<u64>,
+}
+
+impl From<OffsetMeasurement> for MeasurementV1 {
+    fn from(m: OffsetMeasurement) -> MeasurementV1 {
+        MeasurementV1 {
+            schema_version: SCHEMA_VERSION,
+            phc_time: m.phc_time,
+            sys_time: m.sys_time,
+            offset_ns: m.offset_ns,
+            delay_ns: m.delay_ns,
+            method: m.method,
+            mono_ns: m.mono.map(|mono| mono.as_nanos() as u64),
+        }
+    }
+}
+
+impl TryFrom<MeasurementV1> for OffsetMeasurement {
+    type Error = String;
+
+    fn try_from(m: MeasurementV1) -> Result<OffsetMeasurement, String> {
+        if m.schema_version != SCHEMA_VERSION {
+            return Err(format!("unsupported measurement schema_version {}", m.schema_version));
+        }
+        Ok(OffsetMeasurement {
+            phc_time: m.phc_time,
+            sys_time: m.sys_time,
+            offset_ns: m.offset_ns,
+            delay_ns: m.delay_ns,
+            method: m.method,
+            mono: m.mono_ns.map(Duration::from_nanos),
+        })
+    }
+}
+
+/// Version 1 of the serialized form of [`ClockCaps`]
+#[derive(Clone, Copy, serde::Serialize)]
+pub(crate) struct CapsV1 {
+    schema_version: u32,
+    max_adj_ppb: i32,
+    n_alarm: u32,
+    n_ext_ts: u32,
+    n_per_out: u32,
+    pps: bool,
+    n_pins: u32,
+    cross_timestamping: bool,
+    adjust_phase: bool,
+    max_phase_adj_ns: i32,
+}
+
+impl From<ClockCaps> for CapsV1 {
+    fn from(caps: ClockCaps) -> CapsV1 {
+        CapsV1 {
+            schema_version: SCHEMA_VERSION,
+            max_adj_ppb: caps.max_adj_ppb(),
+            n_alarm: caps.n_alarm(),
+            n_ext_ts: caps.n_ext_ts(),
+            n_per_out: caps.n_per_out(),
+            pps: caps.pps(),
+            n_pins: caps.n_pins(),
+            cross_timestamping: caps.cross_timestamping(),
+            adjust_phase: caps.adjust_phase(),
+            max_phase_adj_ns: caps.max_phase_adj_ns(),
+        }
+    }
+}
+
+/// Serialize a measurement in the current wire format, e.g. with `#[serde(serialize_with)]`
+pub fn serialize_measurement<S: Serializer>(m: &OffsetMeasurement, serializer: S) -> Result<S::Ok, S::Error> {
+    MeasurementV1::from(*m).serialize(serializer)
+}
+
+/// Serialize capabilities in the current wire format, e.g. with `#[serde(serialize_with)]`
+pub fn serialize_caps<S: Serializer>(caps: &ClockCaps, serializer: S) -> Result<S::Ok, S::Error> {
+    CapsV1::from(*caps).serialize(serializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measurement_round_trips_and_accepts_unversioned_records() {
+        let m = OffsetMeasurement {
+            phc_time: ptp_clock_time { sec: 10, nsec: 5, reserved: 0 },
+            sys_time: ptp_clock_time { sec: 9, nsec: 7, reserved: 0 },
+            offset_ns: 999_999_998,
+            delay_ns: 40,
+            method: OffsetMethod::Precise,
+            mono: Some(Duration::new(3, 4)),
+        };
+        let json = serde_json::to_string(&m).unwrap();
+        assert_eq!(
+            json,
+            r#"{"schema_version":1,"phc_time":{"sec":10,"nsec":5},"sys_time":{"sec":9,"nsec":7},"offset_ns":999999998,"delay_ns":40,"method":"precise","mono_ns":3000000004}"#
+        );
+        let back: OffsetMeasurement = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.mono, m.mono);
+
+        let old = r#"{"phc_time":{"sec":1,"nsec":0},"sys_time":{"sec":1,"nsec":0},"offset_ns":0,"delay_ns":0,"method":"plain"}"#;
+        assert!(serde_json::from_str::<OffsetMeasurement>(old).is_ok());
+        let future = old.replacen('{', r#"{"schema_version":2,"#, 1);
+        assert!(serde_json::from_str::<OffsetMeasurement>(&future).is_err());
+    }
+}
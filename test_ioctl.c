@@ -2,12 +2,19 @@
 
 #include <stdio.h>
 #include <linux/ptp_clock.h>
+#include <linux/pps.h>
 
 int main() {
     printf("PTP_CLOCK_GETCAPS: 0x%08x\n", PTP_CLOCK_GETCAPS);
     printf("PTP_SYS_OFFSET: 0x%08x\n", PTP_SYS_OFFSET);
     printf("PTP_SYS_OFFSET_PRECISE: 0x%08x\n", PTP_SYS_OFFSET_PRECISE);
     printf("PTP_SYS_OFFSET_EXTENDED: 0x%08x\n", PTP_SYS_OFFSET_EXTENDED);
+    printf("PTP_EXTTS_REQUEST2: 0x%08x\n", PTP_EXTTS_REQUEST2);
+    printf("PTP_PEROUT_REQUEST2: 0x%08x\n", PTP_PEROUT_REQUEST2);
+    printf("PTP_PIN_GETFUNC2: 0x%08x\n", PTP_PIN_GETFUNC2);
+    printf("PTP_PIN_SETFUNC2: 0x%08x\n", PTP_PIN_SETFUNC2);
+    printf("PTP_ENABLE_PPS2: 0x%08x\n", PTP_ENABLE_PPS2);
+    printf("PPS_FETCH: 0x%08lx\n", (unsigned long)PPS_FETCH);
 
     return 0;
 }